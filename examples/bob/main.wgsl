@@ -10,6 +10,12 @@ struct GameState {
     at_edge: u32,
 }
 
+// Runs on load and on reset (F2)
+@compute @workgroup_size(1)
+fn init() {
+    @engine.state.player_pos = vec2f(@engine.screen_width, @engine.screen_height) * 0.5;
+}
+
 @compute @workgroup_size(1)
 fn update() {
     var vel = vec2f(0.0);
@@ -1,14 +1,7 @@
 @set_title("Stanford Bunny")
 @set_size(800, 600)
 
-// Transforms buffer that vertex shader can access
-struct Transforms {
-    rotation: f32,
-    _padding: vec3f, // Align to 16 bytes
-}
-@group(3) @binding(0) var<uniform> transforms: Transforms;
-
-// Game state stores rotation to write to transforms
+// Game state stores the rotation, which the vertex stage reads back
 struct GameState {
     rotation: f32,
 }
@@ -72,8 +65,8 @@ fn vs_main(@builtin(vertex_index) idx: u32) -> VertexOutput {
     let pos = @model("bunny.obj").positions[idx];
     let normal = @model("bunny.obj").normals[idx];
 
-    // Simple rotation
-    let model_matrix = mat4_rotate_y(0.5);
+    // Turned by update(), auto-rotating or with LEFT/RIGHT
+    let model_matrix = mat4_rotate_y(@engine.state.rotation);
     let world_pos = model_matrix * vec4f(pos, 1.0);
 
     // Camera
@@ -1,8 +1,8 @@
 @set_title("Hello!")
 @set_size(600, 300)
 
-@import("draw2d.wgsl")
-@import("font.wgsl")
+@import("std:draw2d")
+@import("std:font")
 
 // Display scaling (renders at 300x150, displayed at 600x300)
 const DISPLAY_SCALE = 2.0;
@@ -35,11 +35,11 @@ fn fs_render(@builtin(position) coord: vec4f) -> @location(0) vec4f {
     var color = vec4f(0.1, 0.1, 0.2, 1.0);
 
     // Render text (scale 2.0)
-    color = blend_over(color, font_text(game_coord, vec2f(50.0, 50.0), @str("Hello, World!"), 2.0, @texture("font8x8.png"), font8));
-    color = blend_over(color, font_text(game_coord, vec2f(95.0, 90.0), @str("@str() works!"), 1.0, @texture("font8x8.png"), font8));
+    color = blend_over(color, font_text(game_coord, vec2f(50.0, 50.0), @str("Hello, World!"), 2.0, @texture("std:font8x8.png"), font8));
+    color = blend_over(color, font_text(game_coord, vec2f(95.0, 90.0), @str("@str() works!"), 1.0, @texture("std:font8x8.png"), font8));
 
     // FPS display (top-left corner)
-    // color = blend_over(color, font_fps(game_coord, vec2f(5.0, 5.0), @engine.delta_time, 1.0, @texture("font8x8.png"), font8));
+    // color = blend_over(color, font_fps(game_coord, vec2f(5.0, 5.0), @engine.delta_time, 1.0, @texture("std:font8x8.png"), font8));
 
     return color;
 }
@@ -1,8 +1,8 @@
 @set_title("Input Demo - Keyboard, Controller & Mouse")
 @set_size(640, 420)
 
-@import("draw2d.wgsl")
-@import("font.wgsl")
+@import("std:draw2d")
+@import("std:font")
 
 struct GameState {
     dummy: u32
@@ -79,70 +79,70 @@ fn fs_render(@builtin(position) coord: vec4f) -> @location(0) vec4f {
     let start_y = 6.0;
 
     // Row 0: 1-0 - = BKSP
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 0.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 49u, @engine.keys[KEY_1] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 1.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 50u, @engine.keys[KEY_2] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 2.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 51u, @engine.keys[KEY_3] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 3.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 52u, @engine.keys[KEY_4] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 4.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 53u, @engine.keys[KEY_5] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 5.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 54u, @engine.keys[KEY_6] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 6.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 55u, @engine.keys[KEY_7] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 7.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 56u, @engine.keys[KEY_8] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 8.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 57u, @engine.keys[KEY_9] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 9.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 48u, @engine.keys[KEY_0] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 10.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 45u, @engine.keys[KEY_MINUS] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 11.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 61u, @engine.keys[KEY_EQUAL] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_wide(coord.xy, vec2f(start_x + 12.0 * (key_w + key_gap), start_y), vec2f(key_w * 2.0 + key_gap, key_h), @str("BK"), @engine.keys[KEY_BACKSPACE] == 1u, @texture("font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 0.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 49u, @engine.keys[KEY_1] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 1.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 50u, @engine.keys[KEY_2] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 2.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 51u, @engine.keys[KEY_3] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 3.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 52u, @engine.keys[KEY_4] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 4.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 53u, @engine.keys[KEY_5] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 5.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 54u, @engine.keys[KEY_6] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 6.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 55u, @engine.keys[KEY_7] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 7.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 56u, @engine.keys[KEY_8] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 8.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 57u, @engine.keys[KEY_9] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 9.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 48u, @engine.keys[KEY_0] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 10.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 45u, @engine.keys[KEY_MINUS] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 11.0 * (key_w + key_gap), start_y), vec2f(key_w, key_h), 61u, @engine.keys[KEY_EQUAL] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_wide(coord.xy, vec2f(start_x + 12.0 * (key_w + key_gap), start_y), vec2f(key_w * 2.0 + key_gap, key_h), @str("BK"), @engine.keys[KEY_BACKSPACE] == 1u, @texture("std:font8x8.png")));
 
     // Row 1: TAB Q-P [ ]
     let r1 = start_y + key_h + key_gap;
-    color = blend_over(color, key_wide(coord.xy, vec2f(start_x + 0.0 * (key_w + key_gap), r1), vec2f(key_w * 2.0, key_h), @str("TAB"), @engine.keys[KEY_TAB] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 2.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 81u, @engine.keys[KEY_Q] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 3.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 87u, @engine.keys[KEY_W] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 4.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 69u, @engine.keys[KEY_E] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 5.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 82u, @engine.keys[KEY_R] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 6.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 84u, @engine.keys[KEY_T] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 7.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 89u, @engine.keys[KEY_Y] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 8.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 85u, @engine.keys[KEY_U] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 9.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 73u, @engine.keys[KEY_I] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 10.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 79u, @engine.keys[KEY_O] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 11.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 80u, @engine.keys[KEY_P] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 12.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 91u, @engine.keys[KEY_BRACKET_LEFT] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 13.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 93u, @engine.keys[KEY_BRACKET_RIGHT] == 1u, @texture("font8x8.png")));
+    color = blend_over(color, key_wide(coord.xy, vec2f(start_x + 0.0 * (key_w + key_gap), r1), vec2f(key_w * 2.0, key_h), @str("TAB"), @engine.keys[KEY_TAB] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 2.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 81u, @engine.keys[KEY_Q] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 3.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 87u, @engine.keys[KEY_W] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 4.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 69u, @engine.keys[KEY_E] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 5.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 82u, @engine.keys[KEY_R] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 6.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 84u, @engine.keys[KEY_T] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 7.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 89u, @engine.keys[KEY_Y] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 8.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 85u, @engine.keys[KEY_U] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 9.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 73u, @engine.keys[KEY_I] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 10.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 79u, @engine.keys[KEY_O] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 11.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 80u, @engine.keys[KEY_P] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 12.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 91u, @engine.keys[KEY_BRACKET_LEFT] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 13.0 * (key_w + key_gap), r1), vec2f(key_w, key_h), 93u, @engine.keys[KEY_BRACKET_RIGHT] == 1u, @texture("std:font8x8.png")));
 
     // Row 2: CAPS A-L ; ' ENT
     let r2 = start_y + 2.0 * (key_h + key_gap);
-    color = blend_over(color, key_wide(coord.xy, vec2f(start_x + 0.0 * (key_w + key_gap), r2), vec2f(key_w * 2.0, key_h), @str("CAP"), @engine.keys[KEY_CAPS_LOCK] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 2.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 65u, @engine.keys[KEY_A] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 3.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 83u, @engine.keys[KEY_S] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 4.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 68u, @engine.keys[KEY_D] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 5.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 70u, @engine.keys[KEY_F] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 6.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 71u, @engine.keys[KEY_G] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 7.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 72u, @engine.keys[KEY_H] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 8.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 74u, @engine.keys[KEY_J] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 9.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 75u, @engine.keys[KEY_K] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 10.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 76u, @engine.keys[KEY_L] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 11.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 59u, @engine.keys[KEY_SEMICOLON] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 12.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 39u, @engine.keys[KEY_QUOTE] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_wide(coord.xy, vec2f(start_x + 13.0 * (key_w + key_gap), r2), vec2f(key_w * 2.0 + key_gap, key_h), @str("ENT"), @engine.keys[KEY_ENTER] == 1u, @texture("font8x8.png")));
+    color = blend_over(color, key_wide(coord.xy, vec2f(start_x + 0.0 * (key_w + key_gap), r2), vec2f(key_w * 2.0, key_h), @str("CAP"), @engine.keys[KEY_CAPS_LOCK] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 2.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 65u, @engine.keys[KEY_A] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 3.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 83u, @engine.keys[KEY_S] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 4.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 68u, @engine.keys[KEY_D] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 5.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 70u, @engine.keys[KEY_F] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 6.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 71u, @engine.keys[KEY_G] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 7.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 72u, @engine.keys[KEY_H] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 8.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 74u, @engine.keys[KEY_J] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 9.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 75u, @engine.keys[KEY_K] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 10.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 76u, @engine.keys[KEY_L] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 11.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 59u, @engine.keys[KEY_SEMICOLON] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 12.0 * (key_w + key_gap), r2), vec2f(key_w, key_h), 39u, @engine.keys[KEY_QUOTE] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_wide(coord.xy, vec2f(start_x + 13.0 * (key_w + key_gap), r2), vec2f(key_w * 2.0 + key_gap, key_h), @str("ENT"), @engine.keys[KEY_ENTER] == 1u, @texture("std:font8x8.png")));
 
     // Row 3: SHIFT Z-M , . / SHIFT
     let r3 = start_y + 3.0 * (key_h + key_gap);
-    color = blend_over(color, key_wide(coord.xy, vec2f(start_x + 0.0 * (key_w + key_gap), r3), vec2f(key_w * 2.0, key_h), @str("SH"), @engine.keys[KEY_SHIFT_LEFT] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 2.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 90u, @engine.keys[KEY_Z] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 3.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 88u, @engine.keys[KEY_X] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 4.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 67u, @engine.keys[KEY_C] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 5.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 86u, @engine.keys[KEY_V] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 6.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 66u, @engine.keys[KEY_B] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 7.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 78u, @engine.keys[KEY_N] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 8.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 77u, @engine.keys[KEY_M] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 9.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 44u, @engine.keys[KEY_COMMA] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 10.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 46u, @engine.keys[KEY_PERIOD] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 11.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 47u, @engine.keys[KEY_SLASH] == 1u, @texture("font8x8.png")));
-    color = blend_over(color, key_wide(coord.xy, vec2f(start_x + 12.0 * (key_w + key_gap), r3), vec2f(key_w * 2.0 + key_gap, key_h), @str("SH"), @engine.keys[KEY_SHIFT_RIGHT] == 1u, @texture("font8x8.png")));
+    color = blend_over(color, key_wide(coord.xy, vec2f(start_x + 0.0 * (key_w + key_gap), r3), vec2f(key_w * 2.0, key_h), @str("SH"), @engine.keys[KEY_SHIFT_LEFT] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 2.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 90u, @engine.keys[KEY_Z] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 3.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 88u, @engine.keys[KEY_X] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 4.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 67u, @engine.keys[KEY_C] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 5.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 86u, @engine.keys[KEY_V] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 6.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 66u, @engine.keys[KEY_B] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 7.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 78u, @engine.keys[KEY_N] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 8.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 77u, @engine.keys[KEY_M] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 9.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 44u, @engine.keys[KEY_COMMA] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 10.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 46u, @engine.keys[KEY_PERIOD] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_with_bg(coord.xy, vec2f(start_x + 11.0 * (key_w + key_gap), r3), vec2f(key_w, key_h), 47u, @engine.keys[KEY_SLASH] == 1u, @texture("std:font8x8.png")));
+    color = blend_over(color, key_wide(coord.xy, vec2f(start_x + 12.0 * (key_w + key_gap), r3), vec2f(key_w * 2.0 + key_gap, key_h), @str("SH"), @engine.keys[KEY_SHIFT_RIGHT] == 1u, @texture("std:font8x8.png")));
 
     // Row 4: SPACE
     let r4 = start_y + 4.0 * (key_h + key_gap);
-    color = blend_over(color, key_wide(coord.xy, vec2f(start_x +5.0 * (key_w + key_gap), r4), vec2f(key_w * 5.0, key_h), @str("SPACE"), @engine.keys[KEY_SPACE] == 1u, @texture("font8x8.png")));
+    color = blend_over(color, key_wide(coord.xy, vec2f(start_x +5.0 * (key_w + key_gap), r4), vec2f(key_w * 5.0, key_h), @str("SPACE"), @engine.keys[KEY_SPACE] == 1u, @texture("std:font8x8.png")));
 
     // ========================================================================
     // L/R Shoulder Buttons
@@ -1 +0,0 @@
-../lib/draw3d.wgsl
\ No newline at end of file
@@ -1,7 +1,7 @@
 @set_title("Ray Marched Primitives")
 @set_size(800, 600)
 
-@import("draw3d.wgsl")
+@import("std:draw3d")
 
 // Game state
 struct GameState {
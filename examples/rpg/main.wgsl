@@ -16,18 +16,19 @@ const MOVE_SPEED = 64.0;
 // Zoom factor (makes tiles appear larger on screen)
 const ZOOM = 4.0;
 
+// Start in the center of the map
 @compute @workgroup_size(1)
-fn update() {
-    // Initialize player position to center of map on first frame
-    if (@engine.state.player_pos.x == 0.0 && @engine.state.player_pos.y == 0.0) {
-        let map_center = vec2f(
-            f32(LEVEL1_WIDTH) * LEVEL1_TILE_WIDTH * 0.5,
-            f32(LEVEL1_HEIGHT) * LEVEL1_TILE_HEIGHT * 0.5
-        );
-        @engine.state.player_pos = map_center;
-        @engine.state.camera_pos = map_center;
-    }
+fn init() {
+    let map_center = vec2f(
+        f32(LEVEL1_WIDTH) * LEVEL1_TILE_WIDTH * 0.5,
+        f32(LEVEL1_HEIGHT) * LEVEL1_TILE_HEIGHT * 0.5
+    );
+    @engine.state.player_pos = map_center;
+    @engine.state.camera_pos = map_center;
+}
 
+@compute @workgroup_size(1)
+fn update() {
     var vel = vec2f(0.0);
 
     // Handle input
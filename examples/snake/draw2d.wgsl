@@ -1 +0,0 @@
-../lib/draw2d.wgsl
\ No newline at end of file
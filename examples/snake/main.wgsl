@@ -1,7 +1,7 @@
 @set_title("Snake")
 @set_size(600, 600)
 
-@import("draw2d.wgsl")
+@import("std:draw2d")
 
 // Grid settings
 const GRID_SIZE = 20.0;     // 20x20 grid
@@ -22,6 +22,11 @@ struct GameState {
     snake: array<vec2f, 100>, // snake body positions (x, y) in grid coords
 }
 
+// Kept in the save file between runs
+@persist struct SaveData {
+    high_score: f32,
+}
+
 // Simple random number generator (LCG)
 fn random_f32(seed: f32) -> f32 {
     let s = u32(seed * 1000.0);
@@ -85,16 +90,25 @@ fn init_game(state: ptr<function, GameState>) {
     (*state).score = 0.0;
 }
 
+// Runs on load and again after @engine.reset()
 @compute @workgroup_size(1)
-fn update() {
-    // Initialize on first frame (when direction is zero)
-    if (@engine.state.direction.x == 0.0 && @engine.state.direction.y == 0.0) {
-        var state = @engine.state;
-        init_game(&state);
-        @engine.state = state;
-        return;
+fn init() {
+    var state = @engine.state;
+    init_game(&state);
+    @engine.state = state;
+}
+
+// End the run, saving the score if it beat the best one
+fn game_over() {
+    @engine.state.game_over = 1.0;
+    if (@engine.state.score > @engine.persist.high_score) {
+        @engine.persist.high_score = @engine.state.score;
+        @engine.save();
     }
+}
 
+@compute @workgroup_size(1)
+fn update() {
     // Game over - press any non-dpad button to restart
     if (@engine.state.game_over > 0.5) {
         // Check A, B, X, Y, L, R, START, SELECT buttons
@@ -102,9 +116,7 @@ fn update() {
             @engine.buttons[BTN_X] == 1 || @engine.buttons[BTN_Y] == 1 ||
             @engine.buttons[BTN_L] == 1 || @engine.buttons[BTN_R] == 1 ||
             @engine.buttons[BTN_START] == 1 || @engine.buttons[BTN_SELECT] == 1) {
-            var state = @engine.state;
-            init_game(&state);
-            @engine.state = state;
+            @engine.reset();
         }
         return;
     }
@@ -145,14 +157,14 @@ fn update() {
     // Check wall collision
     if (new_head.x < 0.0 || new_head.x >= GRID_SIZE ||
         new_head.y < 0.0 || new_head.y >= GRID_SIZE) {
-        @engine.state.game_over = 1.0;
+        game_over();
         return;
     }
 
     // Check self collision
     var state = @engine.state;
     if (check_self_collision(&state, new_head)) {
-        @engine.state.game_over = 1.0;
+        game_over();
         return;
     }
 
@@ -179,6 +191,34 @@ fn update() {
     @engine.state.snake[0] = new_head;
 }
 
+// Unit tests, run by `wgsleng test` after init() (and any "setup" frames in tests.json)
+@test @compute @workgroup_size(1)
+fn test_init() {
+    @assert(@engine.state.length == 1.0, "the snake starts one segment long");
+    @assert(all(@engine.state.snake[0] == vec2f(10.0, 10.0)), "the snake starts in the center");
+    @assert(@engine.state.game_over == 0.0, "the game isn't over yet");
+    @assert(any(@engine.state.food_pos != @engine.state.snake[0]), "food doesn't spawn on the snake");
+}
+
+@test @compute @workgroup_size(1)
+fn test_self_collision() {
+    var state = @engine.state;
+    state.length = 3.0;
+    state.snake[0] = vec2f(5.0, 5.0);
+    state.snake[1] = vec2f(4.0, 5.0);
+    state.snake[2] = vec2f(3.0, 5.0);
+    @assert(check_self_collision(&state, vec2f(4.0, 5.0)), "moving into the body collides");
+    @assert(!check_self_collision(&state, vec2f(5.0, 5.0)), "the head doesn't collide with itself");
+    @assert(!check_self_collision(&state, vec2f(6.0, 5.0)), "an empty cell doesn't collide");
+}
+
+@test @compute @workgroup_size(1)
+fn test_moves_right() {
+    let head = @engine.state.snake[0];
+    @assert(head.x > 10.0 && head.y == 10.0, "the snake heads right on its own");
+    @assert(@engine.state.score == 0.0, "nothing was eaten yet");
+}
+
 @vertex
 fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4f {
     let x = f32((i << 1u) & 2u) * 2.0 - 1.0;
@@ -1 +0,0 @@
-../lib/font.wgsl
\ No newline at end of file
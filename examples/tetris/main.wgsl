@@ -3,8 +3,8 @@
 // 320x400 * DISPLAY_SCALE
 @set_size(960, 1200)
 
-@import("draw2d.wgsl")
-@import("font.wgsl")
+@import("std:draw2d")
+@import("std:font")
 
 // Define font (texture must be passed separately)
 const font8 = Font(
@@ -488,7 +488,7 @@ fn fs_render(@builtin(position) coord: vec4f) -> @location(0) vec4f {
     let preview_shape = get_piece_shape(@engine.state.next_piece, 0.0);
     let preview_color = get_piece_color(@engine.state.next_piece);
 
-    color = blend_over(color, font_text(game_coord.xy, vec2f(sidebar_x, 20.0), @str("NEXT"), 1.0, @texture("font8x8.png"), font8));
+    color = blend_over(color, font_text(game_coord.xy, vec2f(sidebar_x, 20.0), @str("NEXT"), 1.0, @texture("std:font8x8.png"), font8));
 
     for (var y = 0; y < 4; y++) {
         for (var x = 0; x < 4; x++) {
@@ -504,23 +504,23 @@ fn fs_render(@builtin(position) coord: vec4f) -> @location(0) vec4f {
     }
 
     // FPS display (top-left corner)
-    // color = blend_over(color, font_fps(game_coord.xy, vec2f(5.0, 5.0), @engine.delta_time, 1.0, @texture("font8x8.png"), font8));
+    // color = blend_over(color, font_fps(game_coord.xy, vec2f(5.0, 5.0), @engine.delta_time, 1.0, @texture("std:font8x8.png"), font8));
 
     // Sidebar: Score and stats
-    color = blend_over(color, font_text(game_coord.xy, vec2f(sidebar_x, 120.0), @str("SCORE:"), 1.0, @texture("font8x8.png"), font8));
-    color = blend_over(color, font_number(game_coord.xy, vec2f(sidebar_x + 48.0, 120.0), u32(@engine.state.score), 1.0, @texture("font8x8.png"), font8));
+    color = blend_over(color, font_text(game_coord.xy, vec2f(sidebar_x, 120.0), @str("SCORE:"), 1.0, @texture("std:font8x8.png"), font8));
+    color = blend_over(color, font_number(game_coord.xy, vec2f(sidebar_x + 48.0, 120.0), u32(@engine.state.score), 1.0, @texture("std:font8x8.png"), font8));
 
-    color = blend_over(color, font_text(game_coord.xy, vec2f(sidebar_x, 132.0), @str("LINES:"), 1.0, @texture("font8x8.png"), font8));
-    color = blend_over(color, font_number(game_coord.xy, vec2f(sidebar_x + 48.0, 132.0), u32(@engine.state.lines), 1.0, @texture("font8x8.png"), font8));
+    color = blend_over(color, font_text(game_coord.xy, vec2f(sidebar_x, 132.0), @str("LINES:"), 1.0, @texture("std:font8x8.png"), font8));
+    color = blend_over(color, font_number(game_coord.xy, vec2f(sidebar_x + 48.0, 132.0), u32(@engine.state.lines), 1.0, @texture("std:font8x8.png"), font8));
 
-    color = blend_over(color, font_text(game_coord.xy, vec2f(sidebar_x, 144.0), @str("LEVEL:"), 1.0, @texture("font8x8.png"), font8));
-    color = blend_over(color, font_number(game_coord.xy, vec2f(sidebar_x + 48.0, 144.0), u32(@engine.state.level), 1.0, @texture("font8x8.png"), font8));
+    color = blend_over(color, font_text(game_coord.xy, vec2f(sidebar_x, 144.0), @str("LEVEL:"), 1.0, @texture("std:font8x8.png"), font8));
+    color = blend_over(color, font_number(game_coord.xy, vec2f(sidebar_x + 48.0, 144.0), u32(@engine.state.level), 1.0, @texture("std:font8x8.png"), font8));
 
     // Game over overlay
     if (@engine.state.game_over > 0.5) {
         color = blend_over(color, vec4f(0.5, 0.0, 0.0, 0.3));
-        color = blend_over(color, font_text(game_coord.xy, vec2f(board_x + 10.0, 180.0), @str("GAME OVER"), 2.0, @texture("font8x8.png"), font8));
-        color = blend_over(color, font_text(game_coord.xy, vec2f(board_x + 10.0, 220.0), @str("PRESS START"), 1.0, @texture("font8x8.png"), font8));
+        color = blend_over(color, font_text(game_coord.xy, vec2f(board_x + 10.0, 180.0), @str("GAME OVER"), 2.0, @texture("std:font8x8.png"), font8));
+        color = blend_over(color, font_text(game_coord.xy, vec2f(board_x + 10.0, 220.0), @str("PRESS START"), 1.0, @texture("std:font8x8.png"), font8));
     }
 
     return color;
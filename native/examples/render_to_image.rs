@@ -1,7 +1,7 @@
 // Simple tool to render a WGSL shader to a PNG image for testing
 use std::fs::File;
 use wgpu::util::DeviceExt;
-use wgsleng::{GameSource, PreprocessorState, OSC_FLOAT_COUNT, KEY_ARRAY_SIZE};
+use wgsleng::{GameSource, PreprocessorState};
 
 #[tokio::main]
 async fn main() {
@@ -15,28 +15,17 @@ async fn main() {
     let output_path = &args[2];
 
     // Determine entry file
-    let entry_file = if shader_path.ends_with(".wgsl") {
-        std::path::Path::new(shader_path)
-            .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string()
-    } else {
-        "main.wgsl".to_string()
-    };
+    let entry_file = GameSource::entry_file(shader_path);
 
     // Open game source (handles .wgsl files, directories, or .zip files)
-    let mut game_source = GameSource::open(shader_path)
-        .expect("Failed to open game source");
+    let mut game_source = GameSource::open(shader_path).unwrap_or_else(|e| fail(e));
 
     // Read and preprocess shader using the same logic as main program
-    let shader_code = game_source.read_text(&entry_file)
-        .expect("Failed to read shader file");
+    let shader_code = game_source.read_text(&entry_file).unwrap_or_else(|e| fail(e));
 
-    let mut preprocessor = PreprocessorState::new(game_source);
+    let mut preprocessor = PreprocessorState::new(game_source).with_entry_file(&entry_file);
     let (processed_code, metadata) = preprocessor.preprocess_shader(&shader_code, true)
-        .expect("Failed to preprocess shader");
+        .unwrap_or_else(|e| fail(e));
 
     // Debug: print processed shader if requested
     if std::env::var("DEBUG_SHADER").is_ok() {
@@ -79,17 +68,9 @@ async fn main() {
     let mut models = Vec::new();
     let mut model_vertex_count = 0usize;
     for model_file in &metadata.models {
-        let model_data = preprocessor.game_source.read_file(model_file)
-            .expect(&format!("Failed to load model: {}", model_file));
-        let model_path = std::path::PathBuf::from(model_file);
-
-        // Write to temp file for OBJ loader
-        let temp_path = std::env::temp_dir().join(model_path.file_name().unwrap());
-        std::fs::write(&temp_path, model_data)
-            .expect("Failed to write temp model file");
-
-        let model = wgsleng::ObjModel::load(&temp_path)
-            .expect("Failed to load OBJ model");
+        let model = preprocessor.read_text(model_file)
+            .and_then(|text| wgsleng::ObjModel::parse(model_file, &text))
+            .unwrap_or_else(|e| fail(e));
         model_vertex_count = model.vertex_count();
 
         // Create positions buffer
@@ -123,10 +104,9 @@ async fn main() {
     // Load textures if present
     let mut textures = Vec::new();
     for texture_file in &metadata.textures {
-        let img_data = preprocessor.game_source.read_file(texture_file)
-            .expect(&format!("Failed to load texture: {}", texture_file));
+        let img_data = preprocessor.read_file(texture_file).unwrap_or_else(|e| fail(e));
         let img = image::load_from_memory(&img_data)
-            .expect("Failed to decode image")
+            .unwrap_or_else(|source| fail(wgsleng::Error::Image { path: texture_file.clone(), source }))
             .to_rgba8();
         let dimensions = img.dimensions();
 
@@ -213,26 +193,16 @@ async fn main() {
 
     let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-    // Calculate buffer layout matching WGSL struct (must match main.rs State::new)
-    let button_size = 12 * 4; // 48 bytes
-    let float_data_size = 8 * 4; // 32 bytes: time, delta, w, h, mouse(vec4f)
-    // metadata.state_size is already aligned to the struct's own alignment by the preprocessor
-    let aligned_state_size = metadata.state_size;
-    let audio_size = metadata.sounds.len() * 4;
-    let osc_size = wgsleng::OSC_FLOAT_COUNT * 4;
-    let keys_size = wgsleng::KEY_ARRAY_SIZE * 4;
-
-    let total_size_unaligned = button_size + float_data_size + aligned_state_size + audio_size + osc_size + keys_size;
-    let total_size = ((total_size_unaligned + 15) / 16) * 16;
+    // Buffer layout matching the GameEngineHost struct (computed by the preprocessor)
+    let total_size = metadata.engine_size;
 
     // Create engine buffer
     let mut init_data = vec![0u8; total_size];
 
-    // Initialize screen size in floats section (offset 48+8 = 56)
-    let width_bytes = (width as f32).to_le_bytes();
-    let height_bytes = (height as f32).to_le_bytes();
-    init_data[56..60].copy_from_slice(&width_bytes);
-    init_data[60..64].copy_from_slice(&height_bytes);
+    // Initialize screen size in floats section
+    let floats = metadata.engine_offset("time").unwrap_or(0);
+    init_data[floats + 8..floats + 12].copy_from_slice(&(width as f32).to_le_bytes());
+    init_data[floats + 12..floats + 16].copy_from_slice(&(height as f32).to_le_bytes());
 
     let engine_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: Some("Engine Buffer"),
@@ -240,136 +210,95 @@ async fn main() {
         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
     });
 
-    // Create bind group layouts matching main engine
-    // Group 0: sampler (always) and textures (if present)
-    let mut render_group0_entries = vec![
-        wgpu::BindGroupLayoutEntry {
-            binding: 0,
-            visibility: wgpu::ShaderStages::FRAGMENT,
-            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-            count: None,
-        }
-    ];
-
-    for i in 0..metadata.textures.len() {
-        render_group0_entries.push(wgpu::BindGroupLayoutEntry {
-            binding: (i + 1) as u32,
-            visibility: wgpu::ShaderStages::FRAGMENT,
-            ty: wgpu::BindingType::Texture {
-                sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                view_dimension: wgpu::TextureViewDimension::D2,
-                multisampled: false,
-            },
-            count: None,
-        });
-    }
-
-    let bind_group_layout0 = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("Bind Group Layout 0"),
-        entries: &render_group0_entries,
-    });
-
-    // Group 1: engine buffer
-    let bind_group_layout1 = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("Bind Group Layout 1"),
-        entries: &[wgpu::BindGroupLayoutEntry {
-            binding: 0,
-            visibility: wgpu::ShaderStages::FRAGMENT,
-            ty: wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Storage { read_only: false },
-                has_dynamic_offset: false,
-                min_binding_size: None,
-            },
-            count: None,
-        }],
+    // No update has run, so the vertex stage's copy of the engine buffer is the initial data too
+    let engine_view_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Engine View Buffer"),
+        contents: &init_data,
+        usage: wgpu::BufferUsages::STORAGE,
     });
 
-    // Group 2: model buffers
-    let mut model_layout_entries = vec![];
-    for i in 0..metadata.models.len() {
-        let binding_base = 1 + i * 2;
-        // Positions buffer
-        model_layout_entries.push(wgpu::BindGroupLayoutEntry {
-            binding: binding_base as u32,
-            visibility: wgpu::ShaderStages::VERTEX,
-            ty: wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                has_dynamic_offset: false,
-                min_binding_size: None,
-            },
-            count: None,
-        });
-        // Normals buffer
-        model_layout_entries.push(wgpu::BindGroupLayoutEntry {
-            binding: (binding_base + 1) as u32,
-            visibility: wgpu::ShaderStages::VERTEX,
-            ty: wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                has_dynamic_offset: false,
-                min_binding_size: None,
-            },
-            count: None,
-        });
-    }
+    // No update has run, so @storage() buffers are still all zero
+    let storage_buffers: Vec<_> = metadata.storage_buffers.iter().map(|storage| device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(&storage.name),
+        size: storage.size as u64,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    })).collect();
 
-    let bind_group_layout2 = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("Bind Group Layout 2"),
-        entries: &model_layout_entries,
-    });
+    // One target per @pass(); there is no previous frame, so a pass reading itself or a later pass sees black.
+    // Videos and cameras have no frames here either, so they are black as well.
+    let pass_texture = || device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Pass Target"),
+        size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba16Float,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    }).create_view(&wgpu::TextureViewDescriptor::default());
+    let pass_views: Vec<_> = metadata.passes.iter().map(|_| pass_texture()).collect();
+    let black_view = pass_texture();
+
+    // No update has run, so canvases are still blank
+    let canvas_views: Vec<_> = metadata.canvases.iter().map(|canvas| device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(&canvas.name),
+        size: wgpu::Extent3d { width: canvas.width, height: canvas.height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: canvas.texture_format(),
+        usage: wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    }).create_view(&wgpu::TextureViewDescriptor::default())).collect();
 
-    // Create bind groups
     let texture_views: Vec<_> = textures.iter()
         .map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()))
         .collect();
 
-    let mut group0_entries = vec![
-        wgpu::BindGroupEntry {
-            binding: 0,
-            resource: wgpu::BindingResource::Sampler(&sampler),
-        }
-    ];
-
-    for (i, view) in texture_views.iter().enumerate() {
-        group0_entries.push(wgpu::BindGroupEntry {
-            binding: (i + 1) as u32,
-            resource: wgpu::BindingResource::TextureView(view),
-        });
-    }
-
-    let bind_group0 = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: Some("Bind Group 0"),
-        layout: &bind_group_layout0,
-        entries: &group0_entries,
+    // Bind group layouts and bind groups follow the same binding plan as the main engine
+    let bindings = metadata.binding_plan();
+    let render_slots = |group: u32| bindings.iter().filter(move |b| b.group == group && b.visibility.intersects(wgpu::ShaderStages::VERTEX_FRAGMENT));
+    let layout = |group: u32| device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some(&format!("Bind Group Layout {}", group)),
+        entries: &render_slots(group).map(|b| b.layout_entry(wgpu::ShaderStages::VERTEX_FRAGMENT)).collect::<Vec<_>>(),
     });
-
-    let bind_group1 = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: Some("Bind Group 1"),
-        layout: &bind_group_layout1,
-        entries: &[wgpu::BindGroupEntry {
-            binding: 0,
-            resource: engine_buffer.as_entire_binding(),
-        }],
+    let (bind_group_layout0, bind_group_layout1, bind_group_layout2) = (layout(0), layout(1), layout(2));
+
+    // The resource for a slot, by the name the preprocessor gave it; `stage` picks which pass targets are drawn
+    let resource = |name: &str, stage: usize| -> wgpu::BindingResource {
+        let index = |prefix: &str| name.strip_prefix(prefix).and_then(|rest| rest.split('_').next()?.parse::<usize>().ok());
+        if name == "_engine_sampler" {
+            wgpu::BindingResource::Sampler(&sampler)
+        } else if name == "_engine" {
+            engine_buffer.as_entire_binding()
+        } else if name == "_engine_view" {
+            engine_view_buffer.as_entire_binding()
+        } else if let Some(i) = index("_texture_") {
+            wgpu::BindingResource::TextureView(&texture_views[i])
+        } else if let Some(i) = index("_buffer_") {
+            wgpu::BindingResource::TextureView(if i < stage { &pass_views[i] } else { &black_view })
+        } else if let Some(i) = index("_canvas_") {
+            wgpu::BindingResource::TextureView(&canvas_views[i])
+        } else if let Some(i) = index("_storage_") {
+            storage_buffers[i].as_entire_binding()
+        } else if let Some(i) = index("_model_") {
+            let (positions, normals) = &models[i];
+            if name.ends_with("_positions") { positions.as_entire_binding() } else { normals.as_entire_binding() }
+        } else {
+            wgpu::BindingResource::TextureView(&black_view)
+        }
+    };
+    let bind_group = |group: u32, layout: &wgpu::BindGroupLayout, stage: usize| device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some(&format!("Bind Group {}", group)),
+        layout,
+        entries: &render_slots(group).map(|b| wgpu::BindGroupEntry { binding: b.binding, resource: resource(&b.name, stage) }).collect::<Vec<_>>(),
     });
 
-    // Create bind group 2 for models
-    let mut model_entries = vec![];
-    for (i, (positions_buffer, normals_buffer)) in models.iter().enumerate() {
-        let binding_base = 1 + i * 2;
-        model_entries.push(wgpu::BindGroupEntry {
-            binding: binding_base as u32,
-            resource: positions_buffer.as_entire_binding(),
-        });
-        model_entries.push(wgpu::BindGroupEntry {
-            binding: (binding_base + 1) as u32,
-            resource: normals_buffer.as_entire_binding(),
-        });
-    }
-
-    let bind_group2 = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: Some("Bind Group 2"),
-        layout: &bind_group_layout2,
-        entries: &model_entries,
-    });
+    // Group 0 for each stage: the passes in order, then fs_render
+    let bind_groups0: Vec<_> = (0..=metadata.passes.len()).map(|stage| bind_group(0, &bind_group_layout0, stage)).collect();
+    let bind_group1 = bind_group(1, &bind_group_layout1, 0);
+    let bind_group2 = bind_group(2, &bind_group_layout2, 0);
 
     let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("Pipeline Layout"),
@@ -409,11 +338,62 @@ async fn main() {
         cache: None,
     });
 
+    let pass_pipelines: Vec<_> = metadata.passes.iter().map(|pass| {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&pass.name),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some(wgsleng::PASS_VERTEX_ENTRY),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some(&pass.entry_point),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }).collect();
+
     // Render
     let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
         label: Some("Render Encoder"),
     });
 
+    for (i, pipeline) in pass_pipelines.iter().enumerate() {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Offscreen Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &pass_views[i],
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_groups0[i], &[]);
+        render_pass.set_bind_group(1, &bind_group1, &[]);
+        render_pass.set_bind_group(2, &bind_group2, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    let vertex_count = if model_vertex_count > 0 { model_vertex_count as u32 } else { 3 };
     {
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Render Pass"),
@@ -438,10 +418,9 @@ async fn main() {
         });
 
         render_pass.set_pipeline(&render_pipeline);
-        render_pass.set_bind_group(0, &bind_group0, &[]);
+        render_pass.set_bind_group(0, &bind_groups0[metadata.passes.len()], &[]);
         render_pass.set_bind_group(1, &bind_group1, &[]);
         render_pass.set_bind_group(2, &bind_group2, &[]);
-        let vertex_count = if model_vertex_count > 0 { model_vertex_count as u32 } else { 3 };
         render_pass.draw(0..vertex_count, 0..1);
     }
 
@@ -505,3 +484,9 @@ async fn main() {
 
     println!("Rendered to {}", output_path);
 }
+
+/// Print a game loading error (with its source location, if any) and exit
+fn fail(e: wgsleng::Error) -> ! {
+    eprintln!("{}", e);
+    std::process::exit(1);
+}
@@ -0,0 +1,277 @@
+// Offline validation of a game, without a GPU
+//
+// Runs the same preprocessing as the runtime, checks that every referenced
+// asset exists, then parses and validates the shader with naga. Finally the
+// entry points and resource bindings naga sees are compared with the pipelines
+// the runtime builds (see Metadata::binding_plan), which is where most
+// "works on my machine" errors come from: a binding used in the wrong stage,
+// or a hand-written @group/@binding the engine never provides.
+
+use crate::{Error, GameSource, Metadata, PreprocessorOptions, PreprocessorState, SourceLocation};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub location: Option<SourceLocation>,
+    /// Full naga report with source snippet, already mapped to the original files
+    pub detail: Option<String>,
+}
+
+impl Diagnostic {
+    fn error(message: String, location: Option<SourceLocation>) -> Self {
+        Self { severity: Severity::Error, message, location, detail: None }
+    }
+
+    fn warning(message: String, location: Option<SourceLocation>) -> Self {
+        Self { severity: Severity::Warning, message, location, detail: None }
+    }
+}
+
+impl From<&Error> for Diagnostic {
+    fn from(e: &Error) -> Self {
+        let mut diagnostic = Diagnostic::error(e.message(), e.location().cloned());
+        if let Error::Shader { report, .. } = e {
+            diagnostic.detail = Some(report.clone());
+        }
+        diagnostic
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(detail) = &self.detail {
+            return write!(f, "{}", detail.trim_end());
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match &self.location {
+            Some(location) => write!(f, "{}: {}: {}", location, severity, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+/// Entry points the runtime creates pipelines for
+const ENTRY_POINTS: &[(&str, naga::ShaderStage)] = &[
+    ("update", naga::ShaderStage::Compute),
+    ("vs_main", naga::ShaderStage::Vertex),
+    ("fs_render", naga::ShaderStage::Fragment),
+];
+
+/// Check a game (.wgsl file, directory or .zip) the way the runtime would load it,
+/// with the same command-line defines and library paths
+pub fn check_game(game_path: &str, options: &PreprocessorOptions) -> Vec<Diagnostic> {
+    let entry_file = GameSource::entry_file(game_path);
+    let mut game_source = match GameSource::open(game_path) {
+        Ok(source) => source,
+        Err(e) => return vec![Diagnostic::from(&e)],
+    };
+    let source = match game_source.read_text(&entry_file) {
+        Ok(source) => source,
+        Err(e) => return vec![Diagnostic::from(&e)],
+    };
+
+    let mut preprocessor = PreprocessorState::new(game_source).with_entry_file(&entry_file).with_options(options);
+    let (code, metadata) = match preprocessor.preprocess_shader(&source, true) {
+        Ok(result) => result,
+        Err(e) => return vec![Diagnostic::from(&e)],
+    };
+
+    let mut diagnostics = Vec::new();
+    for asset in &metadata.asset_references {
+        if let Err(e) = preprocessor.read_file(&asset.path) {
+            let mut diagnostic = Diagnostic::from(&e);
+            if let Error::AssetNotFound { .. } = e {
+                diagnostic.message = format!("{} {}", asset.kind, diagnostic.message);
+            }
+            diagnostics.push(diagnostic);
+        }
+    }
+    diagnostics.extend(check_shader(&code, &metadata));
+    diagnostics
+}
+
+/// Parse and validate preprocessed WGSL, then compare it with the runtime's pipelines
+pub fn check_shader(code: &str, metadata: &Metadata) -> Vec<Diagnostic> {
+    let map_location = |span: naga::Span| {
+        let loc = span.location(code);
+        metadata.source_map.lookup(loc.line_number as usize, loc.line_position as usize)
+    };
+
+    let module = match naga::front::wgsl::parse_str(code) {
+        Ok(module) => module,
+        Err(e) => return vec![Diagnostic::from(&crate::wgsl_error(&e, code, &metadata.source_map))],
+    };
+
+    let mut validator = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::default());
+    let info = match validator.validate(&module) {
+        Ok(info) => info,
+        Err(e) => {
+            let location = e.spans().next().and_then(|(span, _)| map_location(*span));
+            let mut diagnostic = Diagnostic::error(e.as_inner().to_string(), location);
+            diagnostic.detail = Some(metadata.source_map.rewrite_diagnostic(&e.emit_to_string_with_path(code, "Game Shader")));
+            return vec![diagnostic];
+        }
+    };
+
+    let mut diagnostics = Vec::new();
+    for (name, stage) in ENTRY_POINTS {
+        match module.entry_points.iter().find(|ep| ep.name == *name) {
+            None => diagnostics.push(Diagnostic::error(
+                format!("missing entry point: @{} fn {}", stage_name(*stage), name),
+                None,
+            )),
+            Some(ep) if ep.stage != *stage => diagnostics.push(Diagnostic::error(
+                format!("entry point '{}' must be @{}, found @{}", name, stage_name(*stage), stage_name(ep.stage)),
+                None,
+            )),
+            Some(_) => {}
+        }
+    }
+
+    // Bindings matter for every entry point the runtime builds a pipeline for
+    let runs = |ep: &naga::EntryPoint| {
+        ENTRY_POINTS.iter().any(|(n, _)| *n == ep.name)
+            || metadata.dispatches.iter().any(|d| d.entry_point == ep.name)
+            || (metadata.has_init && ep.name == "init")
+            || metadata.passes.iter().any(|p| p.entry_point == ep.name)
+            || metadata.tests.iter().any(|t| t.entry_point == ep.name)
+    };
+    let plan = metadata.binding_plan();
+    for (handle, var) in module.global_variables.iter() {
+        let Some(binding) = &var.binding else { continue };
+        let name = var.name.as_deref().unwrap_or("<unnamed>");
+        let location = map_location(module.global_variables.get_span(handle));
+
+        // Only bindings an entry point actually touches have to exist in the pipeline layout
+        let users: Vec<&naga::EntryPoint> = module.entry_points.iter().enumerate()
+            .filter(|(i, ep)| runs(ep) && !info.get_entry_point(*i)[handle].is_empty())
+            .map(|(_, ep)| ep)
+            .collect();
+
+        let Some(slot) = plan.iter().find(|s| s.group == binding.group && s.binding == binding.binding) else {
+            let message = format!("'{}' at @group({}) @binding({}) is not bound by the runtime", name, binding.group, binding.binding);
+            match users.first() {
+                Some(ep) => diagnostics.push(Diagnostic::error(format!("{}, but is used by '{}'", message, ep.name), location)),
+                None => diagnostics.push(Diagnostic::warning(format!("{}; it must stay unused", message), location)),
+            }
+            continue;
+        };
+        if users.is_empty() {
+            continue;
+        }
+
+        // Engine-declared bindings have no source position; point at the macro that pulled the asset in
+        let (label, location) = match &slot.asset {
+            Some(asset) => (
+                format!("'{}' ({})", name, asset),
+                location.or_else(|| metadata.asset_references.iter().find(|r| r.path == *asset).map(|r| r.location.clone())),
+            ),
+            // @storage() buffers, @pass() targets and @canvas() textures point at their declaration,
+            // the print buffer at the first @print()
+            None => {
+                let storage = metadata.storage_buffers.iter().enumerate()
+                    .find(|(i, _)| slot.name == format!("_storage_{}", i))
+                    .map(|(_, b)| (format!("@storage(\"{}\")", b.name), &b.location));
+                let pass = metadata.passes.iter().enumerate()
+                    .find(|(i, _)| slot.name == format!("_buffer_{}", i))
+                    .map(|(_, p)| (format!("@buffer(\"{}\")", p.name), &p.location));
+                let canvas = metadata.canvases.iter().enumerate().find_map(|(i, c)| {
+                    if slot.name == format!("_canvas_{}", i) {
+                        Some((format!("@canvas(\"{}\")", c.name), &c.location))
+                    } else if slot.name == format!("_canvas_{}_storage", i) {
+                        Some((format!("@canvas(\"{}\").storage", c.name), &c.location))
+                    } else {
+                        None
+                    }
+                });
+                let print = metadata.prints.first()
+                    .filter(|_| slot.name == "_prints")
+                    .map(|p| ("@print()".to_string(), &p.location));
+                match storage.or(pass).or(canvas).or(print) {
+                    Some((declared, at)) => (format!("'{}' ({})", name, declared), location.or_else(|| Some(at.clone()))),
+                    None => (format!("'{}'", name), location),
+                }
+            }
+        };
+
+        if let Some(expected) = binding_mismatch(&module, var, &slot.ty) {
+            diagnostics.push(Diagnostic::error(
+                format!("{} at @group({}) @binding({}) must be {} to match the runtime", label, binding.group, binding.binding, expected),
+                location.clone(),
+            ));
+        }
+        for ep in users {
+            if !slot.visibility.contains(shader_stages(ep.stage)) {
+                diagnostics.push(Diagnostic::error(
+                    format!("{} is used by '{}', but the runtime only binds it for {}", label, ep.name, visibility_name(slot.visibility)),
+                    location.clone(),
+                ));
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Describe what a global must be declared as if it does not fit the runtime's binding type
+fn binding_mismatch(module: &naga::Module, var: &naga::GlobalVariable, ty: &wgpu::BindingType) -> Option<&'static str> {
+    let inner = &module.types[var.ty].inner;
+    match ty {
+        wgpu::BindingType::Sampler(_) => (!matches!(inner, naga::TypeInner::Sampler { comparison: false })).then_some("a sampler"),
+        wgpu::BindingType::Texture { .. } => {
+            let ok = matches!(inner, naga::TypeInner::Image { dim: naga::ImageDimension::D2, arrayed: false, class: naga::ImageClass::Sampled { kind: naga::ScalarKind::Float, multi: false } });
+            (!ok).then_some("a texture_2d<f32>")
+        }
+        wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only }, .. } => match var.space {
+            naga::AddressSpace::Storage { access } if *read_only && access.contains(naga::StorageAccess::STORE) => Some("var<storage, read>"),
+            naga::AddressSpace::Storage { .. } => None,
+            _ if *read_only => Some("var<storage, read>"),
+            _ => Some("var<storage, read_write>"),
+        },
+        wgpu::BindingType::StorageTexture { format, .. } => {
+            let expected = match format {
+                wgpu::TextureFormat::Rgba8Snorm => ("a texture_storage_2d<rgba8snorm, write>", naga::StorageFormat::Rgba8Snorm),
+                wgpu::TextureFormat::Rgba16Float => ("a texture_storage_2d<rgba16float, write>", naga::StorageFormat::Rgba16Float),
+                _ => ("a texture_storage_2d<rgba8unorm, write>", naga::StorageFormat::Rgba8Unorm),
+            };
+            let ok = matches!(inner, naga::TypeInner::Image { dim: naga::ImageDimension::D2, arrayed: false, class: naga::ImageClass::Storage { format, access } }
+                if *format == expected.1 && *access == naga::StorageAccess::STORE);
+            (!ok).then_some(expected.0)
+        }
+        _ => None,
+    }
+}
+
+fn stage_name(stage: naga::ShaderStage) -> &'static str {
+    match stage {
+        naga::ShaderStage::Vertex => "vertex",
+        naga::ShaderStage::Fragment => "fragment",
+        naga::ShaderStage::Compute => "compute",
+    }
+}
+
+fn shader_stages(stage: naga::ShaderStage) -> wgpu::ShaderStages {
+    match stage {
+        naga::ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+        naga::ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+        naga::ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+    }
+}
+
+fn visibility_name(visibility: wgpu::ShaderStages) -> String {
+    let stages: Vec<&str> = [
+        (wgpu::ShaderStages::COMPUTE, "compute (update)"),
+        (wgpu::ShaderStages::VERTEX, "vertex (vs_main)"),
+        (wgpu::ShaderStages::FRAGMENT, "fragment (fs_render)"),
+    ].iter().filter(|(s, _)| visibility.contains(*s)).map(|(_, n)| *n).collect();
+    stages.join(" and ")
+}
@@ -0,0 +1,190 @@
+// Compute entry points run each frame, from @dispatch()
+//
+// `@dispatch(update_particles, 64)` or `@dispatch(step, @engine.screen_width / 8, @engine.screen_height / 8)`
+// asks the runtime to run a compute entry point with that many workgroups per
+// dimension (missing dimensions are 1). Counts are small arithmetic expressions
+// over numbers, @define()d symbols and a few @engine values, evaluated on the CPU
+// every frame and rounded up, so a shader covering the screen with 8x8 workgroups
+// keeps covering it after a resize.
+
+use std::collections::HashMap;
+
+use crate::lexer::{Token, TokenKind};
+use crate::SourceLocation;
+
+/// @engine members a workgroup count may use; the runtime knows these on the CPU
+pub const DISPATCH_ENGINE_MEMBERS: &[&str] = &["time", "delta_time", "screen_width", "screen_height"];
+
+/// One compute entry point the runtime dispatches each frame, in declaration order
+#[derive(Debug, Clone)]
+pub struct Dispatch {
+    pub entry_point: String,
+    /// Workgroup counts for x, y and z
+    pub workgroups: [DispatchExpr; 3],
+    /// The @dispatch() that declared it (None for the implicit `update`)
+    pub location: Option<SourceLocation>,
+}
+
+impl Dispatch {
+    /// The implicit single-workgroup `update` dispatch games get unless they declare their own
+    pub fn update() -> Self {
+        Self { entry_point: "update".to_string(), workgroups: [DispatchExpr::Number(1.0), DispatchExpr::Number(1.0), DispatchExpr::Number(1.0)], location: None }
+    }
+
+    /// Workgroup counts for this frame. `engine` returns the current value of an @engine member.
+    /// Fractions round up; negative and NaN counts become 0 and nothing runs.
+    pub fn workgroup_counts(&self, engine: impl Fn(&str) -> f32) -> [u32; 3] {
+        self.workgroups.each_ref().map(|expr| {
+            let count = expr.eval(&engine).ceil();
+            if count.is_nan() { 0 } else { count as u32 }
+        })
+    }
+}
+
+/// Arithmetic over numbers and @engine members, parsed from a @dispatch() argument
+#[derive(Debug, Clone, PartialEq)]
+pub enum DispatchExpr {
+    Number(f32),
+    /// One of DISPATCH_ENGINE_MEMBERS
+    Engine(String),
+    Neg(Box<DispatchExpr>),
+    /// `+ - * / %`
+    Binary(char, Box<DispatchExpr>, Box<DispatchExpr>),
+}
+
+impl DispatchExpr {
+    pub fn eval(&self, engine: &dyn Fn(&str) -> f32) -> f32 {
+        match self {
+            DispatchExpr::Number(n) => *n,
+            DispatchExpr::Engine(member) => engine(member),
+            DispatchExpr::Neg(e) => -e.eval(engine),
+            DispatchExpr::Binary(op, a, b) => {
+                let (a, b) = (a.eval(engine), b.eval(engine));
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    '/' => a / b,
+                    _ => a % b,
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for DispatchExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DispatchExpr::Number(n) => write!(f, "{}", n),
+            DispatchExpr::Engine(member) => write!(f, "@engine.{}", member),
+            DispatchExpr::Neg(e) => write!(f, "-{}", e),
+            DispatchExpr::Binary(op, a, b) => write!(f, "({} {} {})", a, op, b),
+        }
+    }
+}
+
+/// Parse a workgroup count from a @dispatch() argument's tokens (trivia already removed).
+/// Identifiers are @define()d symbols and must have numeric values.
+pub(crate) fn parse_dispatch_expr(text: &str, tokens: &[Token], defines: &HashMap<String, String>) -> Result<DispatchExpr, String> {
+    struct Parser<'a> {
+        text: &'a str,
+        tokens: &'a [Token],
+        defines: &'a HashMap<String, String>,
+        pos: usize,
+    }
+
+    impl Parser<'_> {
+        fn peek_punct(&self) -> Option<char> {
+            match self.tokens.get(self.pos)?.kind {
+                TokenKind::Punct(c) => Some(c),
+                _ => None,
+            }
+        }
+
+        fn sum(&mut self) -> Result<DispatchExpr, String> {
+            let mut value = self.product()?;
+            while let Some(op @ ('+' | '-')) = self.peek_punct() {
+                self.pos += 1;
+                value = DispatchExpr::Binary(op, Box::new(value), Box::new(self.product()?));
+            }
+            Ok(value)
+        }
+
+        fn product(&mut self) -> Result<DispatchExpr, String> {
+            let mut value = self.unary()?;
+            while let Some(op @ ('*' | '/' | '%')) = self.peek_punct() {
+                self.pos += 1;
+                value = DispatchExpr::Binary(op, Box::new(value), Box::new(self.unary()?));
+            }
+            Ok(value)
+        }
+
+        fn unary(&mut self) -> Result<DispatchExpr, String> {
+            match self.peek_punct() {
+                Some('-') => {
+                    self.pos += 1;
+                    Ok(DispatchExpr::Neg(Box::new(self.unary()?)))
+                }
+                Some('(') => {
+                    self.pos += 1;
+                    let value = self.sum()?;
+                    if self.peek_punct() != Some(')') {
+                        return Err("expected ')' in @dispatch workgroup count".to_string());
+                    }
+                    self.pos += 1;
+                    Ok(value)
+                }
+                _ => self.operand(),
+            }
+        }
+
+        fn operand(&mut self) -> Result<DispatchExpr, String> {
+            let tok = *self.tokens.get(self.pos).ok_or("unexpected end of @dispatch workgroup count")?;
+            self.pos += 1;
+            let text = tok.span.text(self.text);
+            match tok.kind {
+                TokenKind::Number => number(text).ok_or_else(|| format!("invalid number '{}' in @dispatch", text)),
+                TokenKind::Ident => match self.defines.get(text) {
+                    Some(value) => number(value).ok_or_else(|| format!("'{}' is defined as '{}', which is not a number", text, value)),
+                    None => Err(format!("unknown name '{}' in @dispatch; use a number, a @define()d symbol or @engine.{}", text, DISPATCH_ENGINE_MEMBERS.join("/"))),
+                },
+                TokenKind::At => {
+                    let member = match self.tokens.get(self.pos..self.pos + 3) {
+                        Some([engine, dot, member]) if engine.span.text(self.text) == "engine" && dot.is_punct('.') => member.span.text(self.text),
+                        _ => return Err("expected @engine.<member> in @dispatch".to_string()),
+                    };
+                    self.pos += 3;
+                    if !DISPATCH_ENGINE_MEMBERS.contains(&member) {
+                        return Err(format!("@engine.{} can't be used in @dispatch; available: {}", member, DISPATCH_ENGINE_MEMBERS.join(", ")));
+                    }
+                    Ok(DispatchExpr::Engine(member.to_string()))
+                }
+                _ => Err(format!("unexpected '{}' in @dispatch workgroup count", text)),
+            }
+        }
+    }
+
+    /// Decimal WGSL numeric literal, ignoring the u/i/f suffix
+    fn number(text: &str) -> Option<DispatchExpr> {
+        text.trim_end_matches(['u', 'i', 'f']).parse().ok().map(DispatchExpr::Number)
+    }
+
+    let mut parser = Parser { text, tokens, defines, pos: 0 };
+    let value = parser.sum()?;
+    match tokens.get(parser.pos) {
+        Some(tok) => Err(format!("unexpected '{}' in @dispatch workgroup count", tok.span.text(text))),
+        None => Ok(value),
+    }
+}
+
+/// Parse the arguments of one @dispatch(): the entry point (already resolved by the caller) and up to three counts
+pub(crate) fn parse_workgroups(text: &str, args: &[&[Token]], defines: &HashMap<String, String>) -> Result<[DispatchExpr; 3], String> {
+    if args.len() > 3 {
+        return Err("@dispatch takes an entry point and at most 3 workgroup counts".to_string());
+    }
+    let mut workgroups = [DispatchExpr::Number(1.0), DispatchExpr::Number(1.0), DispatchExpr::Number(1.0)];
+    for (slot, tokens) in workgroups.iter_mut().zip(args) {
+        *slot = parse_dispatch_expr(text, tokens, defines)?;
+    }
+    Ok(workgroups)
+}
@@ -0,0 +1,103 @@
+// Error type shared by the library, the runtime and the tools
+//
+// Errors that come from a line of game code carry its SourceLocation, so a
+// caller can point at it (or test for it) without parsing the message.
+
+use crate::{stdlib, SourceLocation};
+
+#[derive(Debug)]
+pub enum Error {
+    /// A game or library file that doesn't exist; `referenced_from` is the macro that asked for it
+    AssetNotFound { path: String, referenced_from: Option<SourceLocation> },
+    /// A path that would leave the game directory or the library
+    InvalidPath { path: String, referenced_from: Option<SourceLocation> },
+    /// A text file (shader or model) that isn't UTF-8
+    InvalidUtf8 { path: String, referenced_from: Option<SourceLocation> },
+    /// Reading a file failed for a reason other than it not existing
+    Io { path: String, source: std::io::Error },
+    /// The game's zip archive couldn't be opened or read
+    ZipError { path: String, source: zip::result::ZipError },
+    /// Source that can't be tokenized, or a macro call with broken syntax
+    Syntax { location: SourceLocation, message: String },
+    /// A macro with bad arguments or in the wrong place: `@set_size("big")`, `@engine.nope`, a stray `@endif`, ...
+    InvalidDirective { location: SourceLocation, message: String },
+    /// Imports that include each other; `chain` runs from the first file in the cycle back to itself
+    ImportCycle { location: SourceLocation, chain: Vec<String> },
+    /// WGSL that naga or wgpu rejected. `report` is the full diagnostic, mapped back to the game's files.
+    Shader { location: Option<SourceLocation>, message: String, report: String },
+    /// A line of an OBJ model that couldn't be parsed (1-based)
+    ObjParse { path: String, line: usize, message: String },
+    /// A texture that couldn't be decoded
+    Image { path: String, source: image::ImageError },
+}
+
+impl Error {
+    /// Where in the game's source the error was found, if it came from a particular line
+    pub fn location(&self) -> Option<&SourceLocation> {
+        match self {
+            Error::AssetNotFound { referenced_from, .. }
+            | Error::InvalidPath { referenced_from, .. }
+            | Error::InvalidUtf8 { referenced_from, .. } => referenced_from.as_ref(),
+            Error::Syntax { location, .. }
+            | Error::InvalidDirective { location, .. }
+            | Error::ImportCycle { location, .. } => Some(location),
+            Error::Shader { location, .. } => location.as_ref(),
+            Error::Io { .. } | Error::ZipError { .. } | Error::ObjParse { .. } | Error::Image { .. } => None,
+        }
+    }
+
+    /// The error without its location prefix
+    pub fn message(&self) -> String {
+        match self {
+            Error::AssetNotFound { path, .. } if path.starts_with(stdlib::PREFIX) => {
+                format!("{} not found (built in: {})", path, stdlib::files().collect::<Vec<_>>().join(", "))
+            }
+            Error::AssetNotFound { path, .. } => format!("{} not found", path),
+            Error::InvalidPath { path, .. } if path.starts_with(stdlib::PREFIX) => format!("path '{}' leaves the library", path),
+            Error::InvalidPath { path, .. } => format!("path '{}' leaves the game directory", path),
+            Error::InvalidUtf8 { path, .. } => format!("{} is not valid UTF-8", path),
+            Error::Io { path, source } => format!("failed to read {}: {}", path, source),
+            Error::ZipError { path, source } => format!("failed to read zip {}: {}", path, source),
+            Error::Syntax { message, .. } | Error::InvalidDirective { message, .. } | Error::Shader { message, .. } => message.clone(),
+            Error::ImportCycle { chain, .. } => format!("import cycle: {}", chain.join(" -> ")),
+            Error::ObjParse { path, line, message } => format!("{}:{}: {}", path, line, message),
+            Error::Image { path, source } => format!("failed to decode {}: {}", path, source),
+        }
+    }
+
+    /// Fill in the referencing macro for file errors that don't know it yet
+    pub(crate) fn referenced_from(mut self, location: Option<&SourceLocation>) -> Self {
+        if let Error::AssetNotFound { referenced_from, .. }
+        | Error::InvalidPath { referenced_from, .. }
+        | Error::InvalidUtf8 { referenced_from, .. } = &mut self
+        {
+            if referenced_from.is_none() {
+                *referenced_from = location.cloned();
+            }
+        }
+        self
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Error::Shader { report, .. } = self {
+            return write!(f, "{}", report.trim_end());
+        }
+        match self.location() {
+            Some(location) => write!(f, "{}: {}", location, self.message()),
+            None => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::ZipError { source, .. } => Some(source),
+            Error::Image { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
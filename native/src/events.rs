@@ -0,0 +1,214 @@
+// Host events: what the GPU hands the runtime each frame
+//
+// @sound() calls, the @music() command, the @engine.reset()/save()/load()/quit()
+// requests, the @persist struct to save and the @print() buffer all come back
+// from the GPU the same way. Each frame copies them into one slot of a small ring
+// of staging buffers, zeroing the sound count, music command, requests and print
+// count on the GPU right after so each is seen once, and starts mapping the slot. The runtime acts on a slot LATENCY
+// frames later, when the GPU is almost always done with it, so the CPU only
+// waits when the GPU has fallen that far behind instead of on every frame.
+// The lag is fixed rather than "whenever the map lands", so recordings,
+// replays and headless runs see requests take effect on the same frame every
+// time. Anything else the shader needs to tell the host goes in as one more
+// range.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::BufferOffsets;
+
+/// Frames between submitting a frame and acting on its events
+pub const LATENCY: usize = 2;
+
+/// One frame's events
+#[derive(Debug, Default)]
+pub struct FrameEvents {
+    /// Which frame (from 1) they came from
+    pub frame: usize,
+    /// The frame's @sound() calls, in order
+    pub sounds: Vec<wgsleng::SoundTrigger>,
+    /// The frame's last @music() call
+    pub music: Option<wgsleng::MusicCommand>,
+    pub reset: bool,
+    pub save: bool,
+    pub load: bool,
+    pub quit: bool,
+    /// @engine.persist as the frame left it (empty without a @persist struct)
+    pub persist: Vec<u8>,
+    /// The print buffer, for wgsleng::read_prints (empty without a @print)
+    pub prints: Vec<u8>,
+}
+
+/// What a range of each slot holds
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Event {
+    /// The engine buffer's sound queue
+    Sounds,
+    /// Its music command
+    Music,
+    /// Its reset, save, load and quit flags
+    Requests,
+    /// @engine.persist
+    Persist,
+    /// The whole print buffer
+    Prints,
+}
+
+/// Bytes of a GPU buffer copied into every slot, in order
+#[derive(Debug)]
+struct Range {
+    event: Event,
+    offset: u64,
+    size: u64,
+}
+
+impl Range {
+    /// How many of its bytes to zero once copied: the sound and print counts, the music command and the flags
+    fn drain(&self) -> u64 {
+        match self.event {
+            Event::Sounds | Event::Prints => 4,
+            Event::Music | Event::Requests => self.size,
+            Event::Persist => 0,
+        }
+    }
+}
+
+struct Slot {
+    buffer: wgpu::Buffer,
+    /// The submission that copies into it, while its events are on the way
+    submission: Option<wgpu::SubmissionIndex>,
+    /// Set by the map callback once the buffer can be read
+    mapped: Arc<AtomicBool>,
+}
+
+/// The ring of staging buffers a window or headless run reads its events through
+pub struct HostEvents {
+    ranges: Vec<Range>,
+    slots: Vec<Slot>,
+    /// Frames recorded so far; frame N (from 1) copies into slot (N - 1) % slots.len()
+    frame: usize,
+}
+
+impl HostEvents {
+    /// A ring for a game with sounds and `music` to play, reset/save/load/quit `requests` to read, a
+    /// @persist struct to save and a print buffer of `prints_size` bytes (no slots if there's nothing to read)
+    pub fn new(
+        device: &wgpu::Device,
+        offsets: &BufferOffsets,
+        sounds: bool,
+        music: bool,
+        requests: bool,
+        save_file: Option<&wgsleng::save::SaveFile>,
+        prints_size: u64,
+    ) -> Self {
+        let mut ranges = Vec::new();
+        if sounds {
+            let size = 4 + 20 * wgsleng::MAX_SOUND_TRIGGERS as u64;
+            ranges.push(Range { event: Event::Sounds, offset: offsets.sounds, size });
+        }
+        if music {
+            ranges.push(Range { event: Event::Music, offset: offsets.music_command, size: 12 });
+        }
+        if requests {
+            ranges.push(Range { event: Event::Requests, offset: offsets.reset, size: 16 });
+        }
+        if let Some(save) = save_file {
+            ranges.push(Range { event: Event::Persist, offset: save.offset as u64, size: save.size as u64 });
+        }
+        if prints_size > 0 {
+            ranges.push(Range { event: Event::Prints, offset: 0, size: prints_size });
+        }
+
+        let size: u64 = ranges.iter().map(|range| range.size).sum();
+        let slots = if size == 0 { 0 } else { LATENCY + 1 };
+        let slots = (0..slots).map(|_| Slot {
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Host Events"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            submission: None,
+            mapped: Arc::new(AtomicBool::new(false)),
+        }).collect();
+        Self { ranges, slots, frame: 0 }
+    }
+
+    /// Copy this frame's events into its slot, zeroing the ones that are only reported once
+    pub fn record(&self, encoder: &mut wgpu::CommandEncoder, engine_buffer: &wgpu::Buffer, prints_buffer: Option<&wgpu::Buffer>) {
+        let Some(slot) = self.slots.get(self.frame % self.slots.len().max(1)) else {
+            return;
+        };
+        let mut at = 0;
+        for range in &self.ranges {
+            // Prints come last, so without their buffer there's nothing left to copy
+            let source = match range.event {
+                Event::Prints => match prints_buffer {
+                    Some(buffer) => buffer,
+                    None => return,
+                },
+                _ => engine_buffer,
+            };
+            encoder.copy_buffer_to_buffer(source, range.offset, &slot.buffer, at, range.size);
+            if range.drain() > 0 {
+                encoder.clear_buffer(source, range.offset, Some(range.drain()));
+            }
+            at += range.size;
+        }
+    }
+
+    /// Once the frame just recorded is submitted: start mapping its slot and return the events of
+    /// the frame LATENCY before it, waiting for the GPU if it isn't done with them yet
+    pub fn submitted(&mut self, device: &wgpu::Device, submission: wgpu::SubmissionIndex) -> Option<FrameEvents> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let index = self.frame % self.slots.len();
+        let slot = &mut self.slots[index];
+        let mapped = slot.mapped.clone();
+        mapped.store(false, Ordering::Release);
+        slot.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| mapped.store(result.is_ok(), Ordering::Release));
+        slot.submission = Some(submission);
+        self.frame += 1;
+
+        let due = self.frame.checked_sub(LATENCY + 1)?;
+        self.read(device, due)
+    }
+
+    /// Every frame's events still on the way, oldest first, e.g. before a reload replaces the buffers
+    pub fn flush(&mut self, device: &wgpu::Device) -> Vec<FrameEvents> {
+        (self.frame.saturating_sub(LATENCY)..self.frame).filter_map(|frame| self.read(device, frame)).collect()
+    }
+
+    /// The events of a frame (from 0), if its slot still holds them
+    fn read(&mut self, device: &wgpu::Device, frame: usize) -> Option<FrameEvents> {
+        let len = self.slots.len();
+        let slot = &mut self.slots[frame % len];
+        let submission = slot.submission.take()?;
+        device.poll(wgpu::Maintain::wait_for(submission));
+        if !slot.mapped.load(Ordering::Acquire) {
+            return None;
+        }
+        let data = slot.buffer.slice(..).get_mapped_range().to_vec();
+        slot.buffer.unmap();
+
+        let mut events = FrameEvents { frame: frame + 1, ..Default::default() };
+        let mut at = 0;
+        for range in &self.ranges {
+            let bytes = &data[at..at + range.size as usize];
+            at += range.size as usize;
+            let words = || bytes.chunks_exact(4).map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]));
+            match range.event {
+                Event::Sounds => events.sounds = wgsleng::read_sound_triggers(bytes),
+                Event::Music => events.music = wgsleng::read_music_command(bytes),
+                Event::Requests => {
+                    let flags: Vec<bool> = words().map(|flag| flag != 0).collect();
+                    (events.reset, events.save, events.load, events.quit) = (flags[0], flags[1], flags[2], flags[3]);
+                }
+                Event::Persist => events.persist = bytes.to_vec(),
+                Event::Prints => events.prints = bytes.to_vec(),
+            }
+        }
+        Some(events)
+    }
+}
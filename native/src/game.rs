@@ -0,0 +1,985 @@
+// A game loaded onto the GPU
+//
+// The window, a hot reload and a headless run all load a game the same way:
+// preprocess it, load its assets, fill in the engine buffer, lay out the bind
+// groups from the binding plan, then create the pipelines and bind them. What
+// they draw into (a window's surface or an offscreen texture) and where their
+// input comes from (the window, a recording or nothing) is all that differs, so
+// a Game is built here for all three, and runs its frames here too.
+
+use std::collections::HashMap;
+
+use wgpu::util::DeviceExt;
+use wgsleng::{PreprocessorState, KEY_ARRAY_SIZE, OSC_FLOAT_COUNT};
+
+use crate::mixer::{Analyser, Mixer};
+use crate::music::{Music, Track};
+use crate::sound::{Player, Sample};
+use crate::{events, load_video_source, open_camera_source, read_buffer, BufferOffsets, CameraSourceRuntime, Saves, VideoSourceRuntime};
+
+/// What a game's frames are drawn into
+pub struct Target {
+    pub format: wgpu::TextureFormat,
+    /// None for the size the game asks for with @set_size()
+    pub size: Option<winit::dpi::PhysicalSize<u32>>,
+}
+
+/// A game's engine buffer, assets, pipelines and bind groups, and the audio it plays
+pub struct Game {
+    pub metadata: wgsleng::Metadata,
+    /// Hash of the preprocessed shader, tying recordings to the game they were made with
+    pub source_hash: String,
+    /// Size of what it draws into
+    pub size: winit::dpi::PhysicalSize<u32>,
+    pub buffer_offsets: BufferOffsets,
+    pub engine_buffer: wgpu::Buffer,
+    /// Read-only copy of the engine buffer for the vertex stage, refreshed after compute each frame
+    engine_view_buffer: wgpu::Buffer,
+    /// @storage() buffers, bound after the engine buffer in group 1
+    storage_buffers: Vec<(wgpu::Buffer, wgsleng::StorageBuffer)>,
+    /// @canvas() textures, written by compute and sampled by render stages
+    canvases: Vec<(wgpu::Texture, wgsleng::Canvas)>,
+    /// What @print() calls wrote this frame, read back with the other host events
+    pub prints_buffer: Option<wgpu::Buffer>,
+    /// Sound triggers, requests, @persist and prints on their way back from the GPU
+    pub events: events::HostEvents,
+    /// The game's @persist save
+    pub save_file: Option<wgsleng::save::SaveFile>,
+    /// One pipeline per @dispatch(), run in order before rendering
+    compute_pipelines: Vec<(wgpu::ComputePipeline, wgsleng::Dispatch)>,
+    /// The game's `init` entry point, if it has one
+    pub init_pipeline: Option<wgpu::ComputePipeline>,
+    /// Run init before the next frame's dispatches: on load, after a reset or a GameState layout change
+    pub init_pending: bool,
+    /// The @test entry points, for wgsleng test
+    test_pipelines: Vec<(String, wgpu::ComputePipeline)>,
+    render_pipeline: wgpu::RenderPipeline,
+    /// Compute groups 0 (sampler, textures, videos, cameras, canvas storage views) and 1 (engine, @storage())
+    compute_bind_group0: wgpu::BindGroup,
+    compute_bind_group1: wgpu::BindGroup,
+    passes: Passes,
+    render_bind_group1: wgpu::BindGroup,
+    render_bind_group2: Option<wgpu::BindGroup>,
+    depth_view: wgpu::TextureView,
+    model_vertex_count: usize,
+    /// Each @video()'s texture and frames
+    videos: Vec<(wgpu::Texture, VideoSourceRuntime)>,
+    /// Each @camera()'s texture and feed
+    cameras: Vec<(wgpu::Texture, CameraSourceRuntime)>,
+    /// The voices of the game's sounds, timed on the game clock for @sound("x").is_playing
+    pub player: Player,
+    /// The @music() track playing, and any fading out
+    pub music: Music,
+    /// The mix, with an analyser for a game that reads @engine.spectrum, waveform or audio_level
+    analysis: Option<(Mixer, Analyser)>,
+    /// OSC name → osc slot index mapping (populated from @osc("name") in shader)
+    pub osc_name_map: HashMap<String, usize>,
+}
+
+impl Game {
+    /// Load the game `preprocessor` has the entry file of, to draw into `target` and play its sounds
+    /// and music on `mixer` (with None they're only timed). @engine.persist starts from `saves`. On a
+    /// hot reload, `previous` is the game being replaced: its GameState, @persist, @storage() buffers
+    /// and canvases carry over where their layout hasn't changed.
+    pub async fn load(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mut preprocessor: PreprocessorState,
+        target: &Target,
+        mixer: Option<&Mixer>,
+        saves: &Saves,
+        previous: Option<&Game>,
+    ) -> Result<Self, wgsleng::Error> {
+        let entry_file = preprocessor.entry_file.clone();
+        let shader_code = preprocessor.read_text(&entry_file)?;
+        let (processed_code, metadata) = preprocessor.preprocess_shader(&shader_code, true)?;
+        let source_hash = wgsleng::replay::source_hash(&processed_code);
+        if std::env::var("DEBUG_SHADER").is_ok() {
+            println!("\n=== PROCESSED SHADER ===");
+            println!("{}", processed_code);
+            println!("=== END SHADER ===\n");
+        }
+        let size = target.size.unwrap_or(winit::dpi::PhysicalSize::new(metadata.width, metadata.height));
+
+        // Assets
+        let (models, model_vertex_counts) = create_model_buffers(device, &mut preprocessor, &metadata)?;
+        let textures = create_textures(device, queue, &mut preprocessor, &metadata)?;
+        let mut videos = Vec::new();
+        for video_file in &metadata.videos {
+            let (source, _, _) = load_video_source(video_file, preprocessor.read_file(video_file)?);
+            videos.push((create_video_texture(device, queue, &source), source));
+        }
+        let cameras: Vec<_> = metadata.cameras.iter().map(|&cam_idx| {
+            let (source, cam_w, cam_h) = open_camera_source(cam_idx);
+            (create_video_texture(device, queue, &VideoSourceRuntime::Black(cam_w, cam_h)), source)
+        }).collect();
+        let mut samples = Vec::new();
+        for sound_file in &metadata.sounds {
+            samples.push(Sample::load(sound_file, preprocessor.read_file(sound_file)?));
+        }
+        let mut tracks = Vec::new();
+        for (music_file, music_loop) in metadata.music.iter().zip(&metadata.music_loops) {
+            tracks.push(Track::load(music_file, preprocessor.read_file(music_file)?, *music_loop));
+        }
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        // Engine buffer, laid out like the GameEngineHost struct (by naga, in the preprocessor).
+        // GameState starts zeroed for the game's init() to fill in, unless a reload keeps it.
+        // What a reload keeps is copied across on the GPU (old offset, new offset, size), so it
+        // doesn't wait for the frames in flight to read it back.
+        let buffer_offsets = BufferOffsets::new(&metadata);
+        let mut init_data = vec![0u8; metadata.engine_size];
+        let floats = buffer_offsets.floats as usize;
+        init_data[floats + 8..floats + 12].copy_from_slice(&(size.width as f32).to_le_bytes());
+        init_data[floats + 12..floats + 16].copy_from_slice(&(size.height as f32).to_le_bytes());
+        let mut kept = Vec::new();
+        let mut init_pending = true;
+        if let Some(previous) = previous {
+            let old_size = previous.buffer_offsets.state_size;
+            let layout_changed = metadata.state_fields != previous.metadata.state_fields || buffer_offsets.state_size != old_size;
+            if layout_changed {
+                println!("[hot-reload] GameState layout changed ({} -> {} bytes), resetting state", old_size, metadata.state_size);
+            } else if old_size > 0 {
+                kept.push((previous.buffer_offsets.state, buffer_offsets.state, old_size));
+                println!("[hot-reload] GameState preserved ({} bytes)", metadata.state_size);
+            }
+            init_pending = previous.init_pending || layout_changed;
+        }
+
+        // @engine.persist starts from the save file, before init() runs; a reload keeps it while its layout stays the same
+        let save_file = wgsleng::save::SaveFile::new(&metadata);
+        if let Some(save) = &save_file {
+            match previous.and_then(|previous| previous.save_file.as_ref().filter(|old| old.key == save.key)) {
+                Some(old) => kept.push((old.offset as u64, save.offset as u64, save.size as u64)),
+                None => {
+                    if let Some(data) = saves.read(save) {
+                        init_data[save.offset..save.offset + save.size].copy_from_slice(&data);
+                    }
+                }
+            }
+        }
+
+        let engine_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Engine Buffer"),
+            contents: &init_data,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        });
+        if let Some(previous) = previous.filter(|_| !kept.is_empty()) {
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Engine Copy") });
+            for (from, to, size) in kept {
+                encoder.copy_buffer_to_buffer(&previous.engine_buffer, from, &engine_buffer, to, size);
+            }
+            queue.submit(std::iter::once(encoder.finish()));
+        }
+        let engine_view_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Engine View Buffer"),
+            size: metadata.engine_size as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let storage_buffers = create_storage_buffers(device, queue, &metadata, previous.map_or(&[], |previous| &previous.storage_buffers))?;
+        let canvases = create_canvases(device, queue, &metadata, previous.map_or(&[], |previous| &previous.canvases))?;
+        let prints_buffer = create_prints_buffer(device, &metadata);
+        let events = events::HostEvents::new(
+            device,
+            &buffer_offsets,
+            !metadata.sounds.is_empty(),
+            !metadata.music.is_empty(),
+            metadata.uses_reset || metadata.uses_quit || save_file.is_some(),
+            save_file.as_ref(),
+            metadata.prints_size() as u64,
+        );
+
+        // Create the shader and pipelines inside an error scope so shader errors can be
+        // reported against the original files instead of panicking on the flattened source
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Game Shader"),
+            source: wgpu::ShaderSource::Wgsl(processed_code.into()),
+        });
+
+        // Bind group layouts from the binding plan. Render group 0 has the sampler, textures, videos,
+        // cameras, pass targets and canvases; compute group 0 the canvases' storage views instead of
+        // the pass targets. Group 1 has the engine buffer (read-only for vertex code), then the
+        // @storage() buffers and the print buffer. Group 2, the models, is shared.
+        let bindings = metadata.binding_plan();
+        let layout = |group: u32, stages: wgpu::ShaderStages, label: &str| {
+            let entries: Vec<_> = bindings.iter().filter(|b| b.group == group && b.visibility.intersects(stages)).map(|b| b.layout_entry(stages)).collect();
+            (!entries.is_empty() || group < 2).then(|| device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { label: Some(label), entries: &entries }))
+        };
+        let render_layout0 = layout(0, wgpu::ShaderStages::VERTEX_FRAGMENT, "Render Bind Group Layout 0").expect("group 0 always has a layout");
+        let render_layout1 = layout(1, wgpu::ShaderStages::VERTEX_FRAGMENT, "Render Bind Group Layout 1").expect("group 1 always has a layout");
+        let model_layout = layout(2, wgpu::ShaderStages::VERTEX_FRAGMENT | wgpu::ShaderStages::COMPUTE, "Model Bind Group Layout");
+        let compute_layout0 = layout(0, wgpu::ShaderStages::COMPUTE, "Compute Bind Group Layout 0").expect("group 0 always has a layout");
+        let compute_layout1 = layout(1, wgpu::ShaderStages::COMPUTE, "Compute Bind Group Layout 1").expect("group 1 always has a layout");
+        let pipeline_layout = |label: &str, layouts: [&wgpu::BindGroupLayout; 2]| device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &layouts.into_iter().chain(&model_layout).collect::<Vec<_>>(),
+            push_constant_ranges: &[],
+        });
+        let render_pipeline_layout = pipeline_layout("Render Pipeline Layout", [&render_layout0, &render_layout1]);
+        let compute_pipeline_layout = pipeline_layout("Compute Pipeline Layout", [&compute_layout0, &compute_layout1]);
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState { module: &shader, entry_point: Some("vs_main"), buffers: &[], compilation_options: Default::default() },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_render"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleList, ..Default::default() },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let compute_pipelines = create_compute_pipelines(device, &compute_pipeline_layout, &shader, &metadata);
+        let init_pipeline = create_init_pipeline(device, &compute_pipeline_layout, &shader, &metadata);
+        let pass_pipelines = create_pass_pipelines(device, &render_pipeline_layout, &shader, &metadata);
+        let test_pipelines = metadata.tests.iter().map(|test| {
+            let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(&test.entry_point),
+                layout: Some(&compute_pipeline_layout),
+                module: &shader,
+                entry_point: Some(&test.entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            });
+            (test.entry_point.clone(), pipeline)
+        }).collect();
+        if let Some(err) = device.pop_error_scope().await {
+            return Err(shader_error(&err, &metadata));
+        }
+
+        // Bind groups
+        let asset_views: Vec<_> = textures.iter().chain(videos.iter().map(|(t, _)| t)).chain(cameras.iter().map(|(t, _)| t))
+            .map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()))
+            .collect();
+        let canvas_views = canvases.iter().map(|(t, _)| t.create_view(&wgpu::TextureViewDescriptor::default())).collect();
+        let passes = Passes::new(device, render_layout0, sampler, asset_views, canvas_views, pass_pipelines, size);
+        let render_bind_group1 = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Render Bind Group 1"),
+            layout: &render_layout1,
+            entries: &engine_group_entries(&bindings, wgpu::ShaderStages::VERTEX_FRAGMENT, &engine_buffer, &engine_view_buffer, &storage_buffers, prints_buffer.as_ref()),
+        });
+        let render_bind_group2 = model_layout.as_ref().map(|layout| {
+            let entries: Vec<_> = models.iter().flat_map(|(positions, normals)| [positions, normals]).enumerate()
+                .map(|(i, buffer)| wgpu::BindGroupEntry { binding: 1 + i as u32, resource: buffer.as_entire_binding() })
+                .collect();
+            device.create_bind_group(&wgpu::BindGroupDescriptor { label: Some("Render Bind Group 2"), layout, entries: &entries })
+        });
+        let compute_bind_group0 = compute_bind_group0(device, &compute_layout0, &bindings, &passes, &canvases);
+        let compute_bind_group1 = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Compute Bind Group 1"),
+            layout: &compute_layout1,
+            entries: &engine_group_entries(&bindings, wgpu::ShaderStages::COMPUTE, &engine_buffer, &engine_view_buffer, &storage_buffers, prints_buffer.as_ref()),
+        });
+
+        Ok(Self {
+            source_hash,
+            size,
+            buffer_offsets,
+            engine_buffer,
+            engine_view_buffer,
+            storage_buffers,
+            canvases,
+            prints_buffer,
+            events,
+            save_file,
+            compute_pipelines,
+            init_pipeline,
+            init_pending,
+            test_pipelines,
+            render_pipeline,
+            compute_bind_group0,
+            compute_bind_group1,
+            passes,
+            render_bind_group1,
+            render_bind_group2,
+            depth_view: create_depth_view(device, size),
+            model_vertex_count: model_vertex_counts.first().copied().unwrap_or(0),
+            videos,
+            cameras,
+            player: Player::new(mixer.cloned(), samples, metadata.sound_voices.clone()),
+            music: Music::new(mixer.cloned(), tracks),
+            analysis: mixer.filter(|_| metadata.uses_audio_analysis).map(|mixer| (mixer.clone(), Analyser::default())),
+            osc_name_map: metadata.osc_params.iter().cloned().zip(0..).collect(),
+            metadata,
+        })
+    }
+
+    /// Draw into a target of a new size; the pass targets start over as black
+    pub fn resize(&mut self, device: &wgpu::Device, size: winit::dpi::PhysicalSize<u32>) {
+        self.size = size;
+        self.passes.resize(device, size);
+        self.depth_view = create_depth_view(device, size);
+    }
+
+    /// Move the game on by `dt` to `time` with this input: the videos and cameras, the voices and
+    /// music, and what the engine buffer says about them, the input and the screen
+    pub fn update(&mut self, queue: &wgpu::Queue, time: f32, dt: f32, buttons: &[i32; 12], mouse: &[f32; 4], keys: &[u32; KEY_ARRAY_SIZE]) {
+        self.update_dynamic_textures(queue, dt);
+
+        // Input data (buttons + floats), then the raw key state at its offset (after osc)
+        let input_data = input_bytes(buttons, [time, dt, self.size.width as f32, self.size.height as f32], mouse);
+        queue.write_buffer(&self.engine_buffer, self.buffer_offsets.buttons, &input_data);
+        queue.write_buffer(&self.engine_buffer, self.buffer_offsets.keys, bytemuck::cast_slice(keys));
+
+        // The voices still playing after this frame's dt, for @sound("x").is_playing
+        self.player.advance(dt);
+        if !self.player.voices().is_empty() {
+            queue.write_buffer(&self.engine_buffer, self.buffer_offsets.sound_voices, bytemuck::cast_slice(&self.player.voices()));
+        }
+        // Where the music has got to, for @engine.music_time
+        self.music.advance(dt);
+        if !self.music.is_empty() {
+            queue.write_buffer(&self.engine_buffer, self.buffer_offsets.music, bytemuck::cast_slice(&self.music.engine_values()));
+        }
+        // What's playing now, for @engine.spectrum, @engine.waveform and @engine.audio_level
+        if let Some((mixer, analyser)) = &mut self.analysis {
+            let values = analyser.analyse(&mixer.waveform());
+            queue.write_buffer(&self.engine_buffer, self.buffer_offsets.audio_level, bytemuck::cast_slice(&values));
+        }
+    }
+
+    fn update_dynamic_textures(&mut self, queue: &wgpu::Queue, dt_secs: f32) {
+        // Update GIF video frames
+        for (texture, source) in &mut self.videos {
+            if let VideoSourceRuntime::Gif { frames, width, height, current_frame, frame_elapsed_ms } = source {
+                *frame_elapsed_ms += dt_secs * 1000.0;
+                let prev = *current_frame;
+                loop {
+                    let delay = frames[*current_frame].1 as f32;
+                    if *frame_elapsed_ms >= delay {
+                        *frame_elapsed_ms -= delay;
+                        *current_frame = (*current_frame + 1) % frames.len();
+                    } else {
+                        break;
+                    }
+                }
+                // Only upload when the frame actually changed
+                if *current_frame != prev {
+                    write_rgba(queue, texture, &frames[*current_frame].0, *width, *height);
+                }
+            }
+        }
+
+        // Update camera frames
+        for (texture, source) in &self.cameras {
+            let latest: Option<(Vec<u8>, u32, u32)> = match source {
+                #[cfg(feature = "camera")]
+                CameraSourceRuntime::Live { latest_frame, width, height, .. } =>
+                    latest_frame.try_lock().ok().and_then(|mut g| g.take()).map(|data| (data, *width, *height)),
+                CameraSourceRuntime::Black => None,
+            };
+            if let Some((data, width, height)) = latest {
+                write_rgba(queue, texture, &data, width, height);
+            }
+        }
+    }
+
+    /// Jump a @video() to `position` (0 to 1 of the way through); false if the game has no video of that name
+    pub fn seek_video(&mut self, queue: &wgpu::Queue, filename: &str, position: f32) -> bool {
+        let Some(i) = self.metadata.videos.iter().position(|f| f == filename) else {
+            return false;
+        };
+        let (texture, source) = &mut self.videos[i];
+        if let VideoSourceRuntime::Gif { frames, current_frame, frame_elapsed_ms, width, height } = source {
+            *current_frame = ((position * frames.len() as f32) as usize).min(frames.len().saturating_sub(1));
+            *frame_elapsed_ms = 0.0;
+            // Upload the new frame immediately so the seek is visible this frame
+            write_rgba(queue, texture, &frames[*current_frame].0, *width, *height);
+        }
+        true
+    }
+
+    /// Signal the camera threads to stop, e.g. before a reload opens the cameras again
+    pub fn stop_cameras(&self) {
+        #[cfg(feature = "camera")]
+        for (_, source) in &self.cameras {
+            if let CameraSourceRuntime::Live { stop, .. } = source {
+                stop.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn set_osc_float(&self, queue: &wgpu::Queue, slot: usize, value: f32) {
+        if slot < OSC_FLOAT_COUNT {
+            queue.write_buffer(&self.engine_buffer, self.buffer_offsets.osc_floats + (slot * 4) as u64, &value.to_le_bytes());
+        }
+    }
+
+    /// Run a frame into `view` at `time`, `dt` after the last: init() when it's due, the
+    /// @dispatch()es, the @pass()es and fs_render, then the copy of its host events. Returns the
+    /// events of the frame events::LATENCY before it, which the GPU is done with by now.
+    pub fn frame(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, view: &wgpu::TextureView, time: f32, dt: f32) -> Option<events::FrameEvents> {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Frame") });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Compute Pass"), timestamp_writes: None });
+            self.set_compute_bind_groups(&mut compute_pass);
+            let max = device.limits().max_compute_workgroups_per_dimension;
+            for (pipeline, dispatch) in &self.compute_pipelines {
+                let [x, y, z] = dispatch.workgroup_counts(|member| match member {
+                    "time" => time,
+                    "delta_time" => dt,
+                    "screen_width" => self.size.width as f32,
+                    "screen_height" => self.size.height as f32,
+                    _ => 0.0,
+                }).map(|n| n.min(max));
+                if x == 0 || y == 0 || z == 0 {
+                    continue;
+                }
+                compute_pass.set_pipeline(pipeline);
+                compute_pass.dispatch_workgroups(x, y, z);
+            }
+        }
+
+        // The vertex stage reads @engine through a copy, since it can't bind the read_write buffer
+        encoder.copy_buffer_to_buffer(&self.engine_buffer, 0, &self.engine_view_buffer, 0, self.engine_buffer.size());
+
+        // Offscreen passes, in declaration order, each a triangle covering its target
+        for (i, pipeline) in self.passes.pipelines.iter().enumerate() {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Offscreen Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: self.passes.target(i),
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, self.passes.bind_group(i), &[]);
+            render_pass.set_bind_group(1, &self.render_bind_group1, &[]);
+            if let Some(ref bind_group2) = self.render_bind_group2 {
+                render_pass.set_bind_group(2, bind_group2, &[]);
+            }
+            render_pass.draw(0..3, 0..1);
+        }
+
+        // Render: model vertices or a fullscreen triangle
+        let vertex_count = if self.model_vertex_count > 0 { self.model_vertex_count as u32 } else { 3 };
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_view,
+                    depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(1.0), store: wgpu::StoreOp::Store }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_bind_group(0, self.passes.bind_group(self.passes.pipelines.len()), &[]);
+            render_pass.set_bind_group(1, &self.render_bind_group1, &[]);
+            if let Some(ref bind_group2) = self.render_bind_group2 {
+                render_pass.set_bind_group(2, bind_group2, &[]);
+            }
+            render_pass.draw(0..vertex_count, 0..1);
+        }
+
+        // The frame's sounds, requests and prints go back through the host event ring
+        self.events.record(&mut encoder, &self.engine_buffer, self.prints_buffer.as_ref());
+        let submission = queue.submit(std::iter::once(encoder.finish()));
+        self.passes.frame += 1;
+        self.events.submitted(device, submission)
+    }
+
+    /// Dispatch a @test entry point once (a single workgroup), after init() if that's still due
+    pub fn run_test(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, entry_point: &str) -> Result<(), String> {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Test") });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Test Pass"), timestamp_writes: None });
+            self.set_compute_bind_groups(&mut compute_pass);
+            let (_, pipeline) = self.test_pipelines.iter().find(|(name, _)| name == entry_point)
+                .ok_or_else(|| format!("there's no @test fn {}", entry_point))?;
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+        Ok(())
+    }
+
+    /// Bind the compute groups, and run init() first if it's due
+    fn set_compute_bind_groups(&mut self, compute_pass: &mut wgpu::ComputePass) {
+        compute_pass.set_bind_group(0, &self.compute_bind_group0, &[]);
+        compute_pass.set_bind_group(1, &self.compute_bind_group1, &[]);
+        if let Some(ref bind_group2) = self.render_bind_group2 {
+            compute_pass.set_bind_group(2, bind_group2, &[]);
+        }
+        if std::mem::take(&mut self.init_pending) {
+            if let Some(ref init) = self.init_pipeline {
+                compute_pass.set_pipeline(init);
+                compute_pass.dispatch_workgroups(1, 1, 1);
+            }
+        }
+    }
+
+    /// Play a frame's sounds and music and carry out its save and load requests. Its prints and
+    /// reset are left to the caller, which logs them its own way.
+    pub fn handle_events(&mut self, queue: &wgpu::Queue, events: &events::FrameEvents, saves: &mut Saves) {
+        for trigger in &events.sounds {
+            self.player.trigger(trigger);
+        }
+        if let Some(command) = &events.music {
+            self.music.command(command);
+        }
+        if let Some(file) = self.save_file.as_ref().filter(|_| events.save) {
+            saves.write(file, &events.persist);
+        }
+        if let Some(file) = self.save_file.as_ref().filter(|_| events.load) {
+            if let Some(data) = saves.read(file) {
+                queue.write_buffer(&self.engine_buffer, file.offset as u64, &data);
+            }
+        }
+    }
+
+    /// Clear GameState and run init() before the next frame
+    pub fn reset(&mut self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.engine_buffer, self.buffer_offsets.state, &vec![0u8; self.buffer_offsets.state_size as usize]);
+        queue.write_buffer(&self.engine_buffer, self.buffer_offsets.reset, &0u32.to_le_bytes());
+        self.init_pending = true;
+    }
+
+    /// Read part of the engine buffer back from the GPU, waiting for it (@engine.persist for recording, @assert()s for tests)
+    pub fn read_engine_bytes(&self, device: &wgpu::Device, queue: &wgpu::Queue, offset: u64, size: usize) -> Vec<u8> {
+        read_buffer(device, queue, &self.engine_buffer, offset, size)
+    }
+}
+
+/// Depth buffer of the render pass, for 3D games
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24Plus;
+
+fn create_depth_view(device: &wgpu::Device, size: winit::dpi::PhysicalSize<u32>) -> wgpu::TextureView {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Depth Texture"),
+        size: wgpu::Extent3d { width: size.width, height: size.height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    }).create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Replace the whole of an RGBA8 texture
+fn write_rgba(queue: &wgpu::Queue, texture: &wgpu::Texture, rgba: &[u8], width: u32, height: u32) {
+    queue.write_texture(
+        wgpu::ImageCopyTexture { texture, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
+        rgba,
+        wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(4 * width), rows_per_image: Some(height) },
+        wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+    );
+}
+
+/// Format of @pass() render targets; float so feedback effects can accumulate without banding
+const PASS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Group 0 (sampler, textures, videos, cameras, canvases) and the offscreen targets of the game's @pass()es.
+/// Each pass draws into one of two textures, swapping every frame, so it can sample its own
+/// previous frame while passes after it (and fs_render) sample what it drew this frame.
+struct Passes {
+    pipelines: Vec<wgpu::RenderPipeline>,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    /// Texture, video and camera views, bound in that order after the sampler
+    asset_views: Vec<wgpu::TextureView>,
+    /// Sampled views of the @canvas() textures, bound after the pass targets
+    canvas_views: Vec<wgpu::TextureView>,
+    /// Both targets of each pass
+    targets: Vec<[wgpu::TextureView; 2]>,
+    /// Group 0 for each stage (the passes in order, then fs_render) on even and odd frames
+    bind_groups: Vec<[wgpu::BindGroup; 2]>,
+    frame: usize,
+}
+
+impl Passes {
+    fn new(
+        device: &wgpu::Device,
+        layout: wgpu::BindGroupLayout,
+        sampler: wgpu::Sampler,
+        asset_views: Vec<wgpu::TextureView>,
+        canvas_views: Vec<wgpu::TextureView>,
+        pipelines: Vec<wgpu::RenderPipeline>,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> Self {
+        let mut passes = Self { pipelines, layout, sampler, asset_views, canvas_views, targets: Vec::new(), bind_groups: Vec::new(), frame: 0 };
+        passes.resize(device, size);
+        passes
+    }
+
+    /// Recreate the targets at the window size; their contents start over as black
+    fn resize(&mut self, device: &wgpu::Device, size: winit::dpi::PhysicalSize<u32>) {
+        let target = || {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Pass Target"),
+                size: wgpu::Extent3d { width: size.width, height: size.height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: PASS_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            }).create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        self.targets = self.pipelines.iter().map(|_| [target(), target()]).collect();
+
+        let bind_group = |stage: usize, parity: usize| {
+            let mut entries = vec![wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::Sampler(&self.sampler) }];
+            // Passes before this stage were drawn this frame; this one and later ones still hold last frame
+            let buffers = self.targets.iter().enumerate()
+                .map(|(pass, views)| if pass < stage { &views[parity] } else { &views[1 - parity] });
+            for (i, view) in self.asset_views.iter().chain(buffers).chain(&self.canvas_views).enumerate() {
+                entries.push(wgpu::BindGroupEntry { binding: (i + 1) as u32, resource: wgpu::BindingResource::TextureView(view) });
+            }
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Render Bind Group 0"),
+                layout: &self.layout,
+                entries: &entries,
+            })
+        };
+        self.bind_groups = (0..=self.pipelines.len()).map(|stage| [bind_group(stage, 0), bind_group(stage, 1)]).collect();
+    }
+
+    /// Group 0 for a pass this frame; `self.pipelines.len()` is fs_render
+    fn bind_group(&self, stage: usize) -> &wgpu::BindGroup {
+        &self.bind_groups[stage][self.frame % 2]
+    }
+
+    /// Where a pass draws this frame
+    fn target(&self, pass: usize) -> &wgpu::TextureView {
+        &self.targets[pass][self.frame % 2]
+    }
+}
+
+/// The start of GameEngineHost: buttons (48 bytes), then time, delta_time, screen_width and
+/// screen_height, then the mouse (32 bytes)
+fn input_bytes(buttons: &[i32; 12], floats: [f32; 4], mouse: &[f32; 4]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(80);
+    for &button in buttons {
+        data.extend_from_slice(&button.to_le_bytes());
+    }
+    for value in floats.iter().chain(mouse) {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data
+}
+
+/// A model's positions and normals
+type ModelBuffers = (wgpu::Buffer, wgpu::Buffer);
+
+/// Storage buffers with each @model()'s positions and normals, and the models' vertex counts
+fn create_model_buffers(
+    device: &wgpu::Device,
+    preprocessor: &mut PreprocessorState,
+    metadata: &wgsleng::Metadata,
+) -> Result<(Vec<ModelBuffers>, Vec<usize>), wgsleng::Error> {
+    let mut models = Vec::new();
+    let mut vertex_counts = Vec::new();
+    for model_file in &metadata.models {
+        let model = wgsleng::ObjModel::parse(model_file, &preprocessor.read_text(model_file)?)?;
+        vertex_counts.push(model.vertex_count());
+
+        // IMPORTANT: array<vec3f> in WGSL storage buffers has 16-byte alignment (like vec4),
+        // so each position and normal is padded to 4 floats
+        let buffer = |label: &str, data: &[[f32; 3]]| {
+            let padded: Vec<f32> = data.iter().flat_map(|v| [v[0], v[1], v[2], 0.0]).collect();
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::cast_slice(&padded),
+                usage: wgpu::BufferUsages::STORAGE,
+            })
+        };
+        models.push((buffer("Model Positions", &model.positions), buffer("Model Normals", &model.normals)));
+    }
+    Ok((models, vertex_counts))
+}
+
+/// A texture for each @texture() image
+fn create_textures(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    preprocessor: &mut PreprocessorState,
+    metadata: &wgsleng::Metadata,
+) -> Result<Vec<wgpu::Texture>, wgsleng::Error> {
+    let mut textures = Vec::new();
+    for texture_file in &metadata.textures {
+        let img_data = preprocessor.read_file(texture_file)?;
+        let img = image::load_from_memory(&img_data)
+            .map_err(|source| wgsleng::Error::Image { path: texture_file.clone(), source })?
+            .to_rgba8();
+        textures.push(create_rgba_texture(device, queue, "Game Texture", &img, img.width(), img.height()));
+    }
+    Ok(textures)
+}
+
+/// A texture showing a video source's current frame (black for a source without frames, or a camera not yet started)
+fn create_video_texture(device: &wgpu::Device, queue: &wgpu::Queue, source: &VideoSourceRuntime) -> wgpu::Texture {
+    match source {
+        VideoSourceRuntime::Gif { frames, width, height, current_frame, .. } =>
+            create_rgba_texture(device, queue, "Video Texture", &frames[*current_frame].0, *width, *height),
+        VideoSourceRuntime::Black(w, h) =>
+            create_rgba_texture(device, queue, "Video Texture", &vec![0u8; (*w * *h * 4) as usize], *w, *h),
+    }
+}
+
+/// A sampled RGBA8 texture that can be updated with write_texture
+fn create_rgba_texture(device: &wgpu::Device, queue: &wgpu::Queue, label: &str, rgba: &[u8], width: u32, height: u32) -> wgpu::Texture {
+    let size = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    queue.write_texture(
+        wgpu::ImageCopyTexture { texture: &texture, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
+        rgba,
+        wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(4 * width), rows_per_image: Some(height) },
+        size,
+    );
+    texture
+}
+
+/// A render pipeline for each @pass(), drawing into a PASS_FORMAT target without depth
+fn create_pass_pipelines(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    metadata: &wgsleng::Metadata,
+) -> Vec<wgpu::RenderPipeline> {
+    metadata.passes.iter().map(|pass| {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&pass.name),
+            layout: Some(layout),
+            vertex: wgpu::VertexState { module: shader, entry_point: Some(wgsleng::PASS_VERTEX_ENTRY), buffers: &[], compilation_options: Default::default() },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some(&pass.entry_point),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: PASS_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleList, ..Default::default() },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }).collect()
+}
+
+/// Zero-initialized buffers for the game's @storage() declarations. On hot reload, a buffer whose
+/// element type is unchanged takes over the contents of its `previous` namesake (up to the smaller size).
+fn create_storage_buffers(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    metadata: &wgsleng::Metadata,
+    previous: &[(wgpu::Buffer, wgsleng::StorageBuffer)],
+) -> Result<Vec<(wgpu::Buffer, wgsleng::StorageBuffer)>, wgsleng::Error> {
+    let limit = device.limits().max_storage_buffer_binding_size as usize;
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Storage Copy") });
+    let mut buffers = Vec::new();
+    for storage in &metadata.storage_buffers {
+        if storage.size > limit {
+            return Err(wgsleng::Error::InvalidDirective {
+                location: storage.location.clone(),
+                message: format!("@storage(\"{}\") is {} bytes, more than this GPU's limit of {}", storage.name, storage.size, limit),
+            });
+        }
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&storage.name),
+            size: storage.size as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        if let Some((old, old_storage)) = previous.iter().find(|(_, old)| old.name == storage.name) {
+            if old_storage.element == storage.element {
+                encoder.copy_buffer_to_buffer(old, 0, &buffer, 0, old_storage.size.min(storage.size) as u64);
+                println!("[hot-reload] storage \"{}\" preserved", storage.name);
+            } else {
+                println!("[hot-reload] storage \"{}\" element type changed, resetting", storage.name);
+            }
+        }
+        buffers.push((buffer, storage.clone()));
+    }
+    queue.submit(std::iter::once(encoder.finish()));
+    Ok(buffers)
+}
+
+/// The buffer @print() calls write into, emptied after each frame's readback (None when the game has no @print)
+fn create_prints_buffer(device: &wgpu::Device, metadata: &wgsleng::Metadata) -> Option<wgpu::Buffer> {
+    (!metadata.prints.is_empty()).then(|| device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Prints Buffer"),
+        size: metadata.prints_size() as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    }))
+}
+
+/// Group 1 entries for a pipeline running `stages`: the engine buffer, its vertex-stage copy, the
+/// @storage() buffers and the print buffer, as far as the plan makes them visible there
+fn engine_group_entries<'a>(
+    bindings: &[wgsleng::BindingSlot],
+    stages: wgpu::ShaderStages,
+    engine_buffer: &'a wgpu::Buffer,
+    engine_view_buffer: &'a wgpu::Buffer,
+    storage_buffers: &'a [(wgpu::Buffer, wgsleng::StorageBuffer)],
+    prints_buffer: Option<&'a wgpu::Buffer>,
+) -> Vec<wgpu::BindGroupEntry<'a>> {
+    let buffers = [engine_buffer, engine_view_buffer].into_iter()
+        .chain(storage_buffers.iter().map(|(buffer, _)| buffer))
+        .chain(prints_buffer);
+    bindings.iter().filter(|b| b.group == 1).zip(buffers)
+        .filter(|(slot, _)| slot.visibility.intersects(stages))
+        .map(|(slot, buffer)| wgpu::BindGroupEntry { binding: slot.binding, resource: buffer.as_entire_binding() })
+        .collect()
+}
+
+/// Blank textures for the game's @canvas() declarations. On hot reload, a canvas with the same
+/// name, size and format as one of `previous` keeps its pixels.
+fn create_canvases(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    metadata: &wgsleng::Metadata,
+    previous: &[(wgpu::Texture, wgsleng::Canvas)],
+) -> Result<Vec<(wgpu::Texture, wgsleng::Canvas)>, wgsleng::Error> {
+    let limit = device.limits().max_texture_dimension_2d;
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Canvas Copy") });
+    let mut canvases = Vec::new();
+    for canvas in &metadata.canvases {
+        if canvas.width > limit || canvas.height > limit {
+            return Err(wgsleng::Error::InvalidDirective {
+                location: canvas.location.clone(),
+                message: format!("@canvas(\"{}\") is {}x{}, more than this GPU's limit of {}", canvas.name, canvas.width, canvas.height, limit),
+            });
+        }
+        let size = wgpu::Extent3d { width: canvas.width, height: canvas.height, depth_or_array_layers: 1 };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&canvas.name),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: canvas.texture_format(),
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        if let Some((old, old_canvas)) = previous.iter().find(|(_, old)| old.name == canvas.name) {
+            if (old_canvas.width, old_canvas.height, &old_canvas.format) == (canvas.width, canvas.height, &canvas.format) {
+                encoder.copy_texture_to_texture(old.as_image_copy(), texture.as_image_copy(), size);
+                println!("[hot-reload] canvas \"{}\" preserved", canvas.name);
+            } else {
+                println!("[hot-reload] canvas \"{}\" size or format changed, clearing", canvas.name);
+            }
+        }
+        canvases.push((texture, canvas.clone()));
+    }
+    queue.submit(std::iter::once(encoder.finish()));
+    Ok(canvases)
+}
+
+/// Compute group 0: the sampler, the texture/video/camera views and each canvas's storage view,
+/// at the bindings the plan gave them
+fn compute_bind_group0(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    bindings: &[wgsleng::BindingSlot],
+    passes: &Passes,
+    canvases: &[(wgpu::Texture, wgsleng::Canvas)],
+) -> wgpu::BindGroup {
+    let canvas_views: Vec<_> = canvases.iter().map(|(texture, _)| texture.create_view(&wgpu::TextureViewDescriptor::default())).collect();
+    let resources = std::iter::once(wgpu::BindingResource::Sampler(&passes.sampler))
+        .chain(passes.asset_views.iter().chain(&canvas_views).map(wgpu::BindingResource::TextureView));
+    let entries: Vec<_> = bindings.iter()
+        .filter(|b| b.group == 0 && b.visibility.contains(wgpu::ShaderStages::COMPUTE))
+        .zip(resources)
+        .map(|(slot, resource)| wgpu::BindGroupEntry { binding: slot.binding, resource })
+        .collect();
+    device.create_bind_group(&wgpu::BindGroupDescriptor { label: Some("Compute Bind Group 0"), layout, entries: &entries })
+}
+
+/// A compute pipeline for each entry point the game dispatches, in dispatch order
+fn create_compute_pipelines(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    metadata: &wgsleng::Metadata,
+) -> Vec<(wgpu::ComputePipeline, wgsleng::Dispatch)> {
+    metadata.dispatches.iter().map(|dispatch| {
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(&dispatch.entry_point),
+            layout: Some(layout),
+            module: shader,
+            entry_point: Some(&dispatch.entry_point),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        (pipeline, dispatch.clone())
+    }).collect()
+}
+
+/// A compute pipeline for the game's `init` entry point, if it has one
+fn create_init_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    metadata: &wgsleng::Metadata,
+) -> Option<wgpu::ComputePipeline> {
+    metadata.has_init.then(|| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("init"),
+        layout: Some(layout),
+        module: shader,
+        entry_point: Some("init"),
+        compilation_options: Default::default(),
+        cache: None,
+    }))
+}
+
+/// A wgpu shader/pipeline validation error, with its report mapped back to the game's files
+fn shader_error(err: &wgpu::Error, metadata: &wgsleng::Metadata) -> wgsleng::Error {
+    let report = err.to_string();
+    wgsleng::Error::Shader {
+        location: None,
+        message: report.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or("shader error").to_string(),
+        report: format!("shader error:{}", metadata.source_map.rewrite_diagnostic(&report)),
+    }
+}
@@ -0,0 +1,282 @@
+// Golden-image tests for games
+//
+// `wgsleng test` reads tests.json from a game's directory. Each test runs the
+// game headless for some frames, with no input, a --record file or a short
+// script of held buttons, keys and mouse, and compares the last frame to a
+// reference PNG (the "golden") pixel by pixel. A pixel fails if any channel is
+// further than the tolerance from the golden's. `--update` writes the frame as
+// the new golden wherever it's missing or doesn't match.
+//
+//     {
+//       "tolerance": 2,
+//       "tests": [
+//         { "name": "title", "frame": 1 },
+//         { "name": "walk", "frame": 90, "input": [
+//           { "frames": 30 },
+//           { "frames": 60, "buttons": ["RIGHT"], "keys": ["ArrowRight"] }
+//         ] },
+//         { "name": "crash", "frame": 600, "input": "tests/crash.wgrep", "golden": "tests/crash.png" }
+//       ]
+//     }
+//
+// Paths are relative to the manifest, and a golden defaults to tests/NAME.png.
+//
+// The game's @test entry points run too, each once against a fresh GameState
+// (after init()). "setup" can have the game play some frames first:
+//
+//     "setup": {
+//       "test_walks_right": { "frames": 60, "input": [{ "frames": 60, "buttons": ["RIGHT"] }] }
+//     }
+
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::replay::FrameInput;
+use crate::{keycode_index, BTN_A, BTN_B, BTN_DOWN, BTN_L, BTN_LEFT, BTN_R, BTN_RIGHT, BTN_SELECT, BTN_START, BTN_UP, BTN_X, BTN_Y};
+
+/// File name of the manifest in a game's directory
+pub const MANIFEST: &str = "tests.json";
+
+/// How far a channel may be from the golden's without a "tolerance" in the manifest, which
+/// covers rounding differences between GPUs
+pub const DEFAULT_TOLERANCE: u8 = 2;
+
+/// Frame time when there's neither a "dt" in the manifest, a --fixed-dt nor a recording to take it from
+pub const DEFAULT_DT: f32 = 1.0 / 60.0;
+
+/// A game's tests.json
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    /// The directory the manifest is in, that its paths are relative to
+    pub dir: PathBuf,
+    /// Seconds per frame, instead of 1/60 (or a recording's own)
+    pub dt: Option<f32>,
+    pub tests: Vec<Test>,
+    /// Frames to run before @test entry points, by entry point
+    pub setups: Vec<Setup>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Test {
+    pub name: String,
+    /// The frame (from 1) compared with the golden
+    pub frame: usize,
+    pub input: Input,
+    pub golden: PathBuf,
+    /// How far a channel may be from the golden's before the pixel fails
+    pub tolerance: u8,
+    /// How many pixels may fail before the test does
+    pub max_failing_pixels: usize,
+}
+
+/// Frames the game runs before a @test entry point
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setup {
+    pub test: String,
+    /// How many frames; without it, as many as the input has
+    pub frames: Option<usize>,
+    pub input: Input,
+}
+
+/// What a test feeds the game
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    None,
+    /// A session written by --record
+    Recording(PathBuf),
+    /// Frames from the manifest's script; whatever the last step holds stays held
+    Script(Vec<FrameInput>),
+}
+
+impl Manifest {
+    /// Where the manifest for a game directory or .wgsl file is
+    pub fn path(game: &str) -> Result<PathBuf, String> {
+        let game = Path::new(game);
+        if game.extension().is_some_and(|ext| ext == "zip") {
+            return Err(format!("{}: tests need a game directory or .wgsl file, not a zip", game.display()));
+        }
+        let dir = if game.is_dir() { game } else { game.parent().unwrap_or(Path::new(".")) };
+        Ok(dir.join(MANIFEST))
+    }
+
+    /// A game's manifest, or an empty one if it has none (it may only have @test entry points)
+    pub fn load(game: &str) -> Result<Self, String> {
+        let path = Self::path(game)?;
+        if !path.exists() {
+            let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+            return Ok(Self { dir, dt: None, tests: Vec::new(), setups: Vec::new() });
+        }
+        Self::open(&path)
+    }
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        Self::parse(&contents, dir).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(contents: &str, dir: PathBuf) -> Result<Self, String> {
+        let manifest: Value = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+        let dt = match manifest.get("dt") {
+            Some(dt) => Some(dt.as_f64().filter(|dt| *dt >= 0.0).ok_or("dt isn't a number of seconds")? as f32),
+            None => None,
+        };
+        let tolerance = match manifest.get("tolerance") {
+            Some(tolerance) => parse_tolerance(tolerance)?,
+            None => DEFAULT_TOLERANCE,
+        };
+        let max_failing_pixels = match manifest.get("max_failing_pixels") {
+            Some(max) => max.as_u64().ok_or("max_failing_pixels isn't a count")? as usize,
+            None => 0,
+        };
+
+        let mut tests: Vec<Test> = Vec::new();
+        let listed = manifest.get("tests").map(|tests| tests.as_array().ok_or("tests isn't a list")).transpose()?;
+        for test in listed.into_iter().flatten() {
+            let name = test["name"].as_str().ok_or("a test has no name")?.to_string();
+            let error = |message: &str| format!("test '{}': {}", name, message);
+            if tests.iter().any(|t| t.name == name) {
+                return Err(error("there's already a test with this name"));
+            }
+            let frame = test["frame"].as_u64().filter(|&frame| frame > 0).ok_or_else(|| error("frame isn't a frame number (from 1)"))? as usize;
+            let input = parse_input(test.get("input"), &dir, dt).map_err(|e| error(&e))?;
+            let golden = match test.get("golden") {
+                Some(golden) => dir.join(golden.as_str().ok_or_else(|| error("golden isn't a path"))?),
+                None => dir.join("tests").join(format!("{}.png", name)),
+            };
+            let tolerance = match test.get("tolerance") {
+                Some(tolerance) => parse_tolerance(tolerance).map_err(|e| error(&e))?,
+                None => tolerance,
+            };
+            let max_failing_pixels = match test.get("max_failing_pixels") {
+                Some(max) => max.as_u64().ok_or_else(|| error("max_failing_pixels isn't a count"))? as usize,
+                None => max_failing_pixels,
+            };
+            tests.push(Test { name, frame, input, golden, tolerance, max_failing_pixels });
+        }
+
+        let mut setups = Vec::new();
+        match manifest.get("setup") {
+            Some(Value::Object(entries)) => {
+                for (test, setup) in entries {
+                    let error = |message: &str| format!("setup for '{}': {}", test, message);
+                    let frames = match setup.get("frames") {
+                        Some(frames) => Some(frames.as_u64().ok_or_else(|| error("frames isn't a count"))? as usize),
+                        None => None,
+                    };
+                    let input = parse_input(setup.get("input"), &dir, dt).map_err(|e| error(&e))?;
+                    setups.push(Setup { test: test.clone(), frames, input });
+                }
+            }
+            Some(_) => return Err("setup isn't an object of @test entry point names".to_string()),
+            None => {}
+        }
+        Ok(Self { dir, dt, tests, setups })
+    }
+}
+
+fn parse_input(input: Option<&Value>, dir: &Path, dt: Option<f32>) -> Result<Input, String> {
+    match input {
+        None => Ok(Input::None),
+        Some(Value::String(path)) => Ok(Input::Recording(dir.join(path))),
+        Some(Value::Array(steps)) => Ok(Input::Script(parse_script(steps, dt.unwrap_or(DEFAULT_DT))?)),
+        Some(_) => Err("input isn't a recording's path or a list of steps".to_string()),
+    }
+}
+
+fn parse_tolerance(value: &Value) -> Result<u8, String> {
+    value.as_u64().and_then(|t| u8::try_from(t).ok()).ok_or_else(|| "tolerance isn't a channel difference from 0 to 255".to_string())
+}
+
+/// Expand `[{ "frames": 30, "buttons": [...], "keys": [...], "mouse": [x, y, down_x, down_y] }, ...]` into frames
+fn parse_script(steps: &[Value], dt: f32) -> Result<Vec<FrameInput>, String> {
+    let mut frames = Vec::new();
+    for (i, step) in steps.iter().enumerate() {
+        let error = |message: String| format!("input step {}: {}", i + 1, message);
+        let count = step["frames"].as_u64().ok_or_else(|| error("frames isn't a count".into()))? as usize;
+        let mut input = FrameInput { dt, ..Default::default() };
+        for name in names(step, "buttons").map_err(error)? {
+            input.buttons[button_index(name).ok_or_else(|| error(format!("unknown button '{}'", name)))?] = 1;
+        }
+        for name in names(step, "keys").map_err(error)? {
+            input.keys.push(keycode_index(name).ok_or_else(|| error(format!("unknown key '{}' (keys are named like KeyA, Space, ArrowLeft)", name)))?);
+        }
+        input.keys.sort_unstable();
+        if let Some(mouse) = step.get("mouse") {
+            let mouse: Option<Vec<f32>> = mouse.as_array().map(|m| m.iter().filter_map(|v| v.as_f64()).map(|v| v as f32).collect());
+            input.mouse = mouse.and_then(|m| m.try_into().ok()).ok_or_else(|| error("mouse isn't 4 numbers".into()))?;
+        }
+        frames.resize(frames.len() + count, input);
+    }
+    Ok(frames)
+}
+
+fn names<'a>(step: &'a Value, key: &str) -> Result<Vec<&'a str>, String> {
+    match step.get(key) {
+        Some(Value::Array(names)) => names.iter().map(|n| n.as_str().ok_or_else(|| format!("{} isn't a list of names", key))).collect(),
+        Some(_) => Err(format!("{} isn't a list of names", key)),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// A gamepad button by its BTN_* name, with or without the BTN_
+fn button_index(name: &str) -> Option<usize> {
+    Some(match name.strip_prefix("BTN_").unwrap_or(name) {
+        "UP" => BTN_UP,
+        "DOWN" => BTN_DOWN,
+        "LEFT" => BTN_LEFT,
+        "RIGHT" => BTN_RIGHT,
+        "A" => BTN_A,
+        "B" => BTN_B,
+        "X" => BTN_X,
+        "Y" => BTN_Y,
+        "L" => BTN_L,
+        "R" => BTN_R,
+        "START" => BTN_START,
+        "SELECT" => BTN_SELECT,
+        _ => return None,
+    })
+}
+
+/// How a frame differs from its golden
+pub struct Comparison {
+    /// Pixels with a channel further from the golden's than the tolerance
+    pub failing_pixels: usize,
+    /// The largest difference in any channel
+    pub max_difference: u8,
+    /// The golden in dim gray with the failing pixels in red, as RGBA8
+    pub diff: Vec<u8>,
+}
+
+/// Compare two RGBA8 images of the same size
+pub fn compare(actual: &[u8], golden: &[u8], tolerance: u8) -> Comparison {
+    let mut failing_pixels = 0;
+    let mut max_difference = 0;
+    let mut diff = Vec::with_capacity(golden.len());
+    for (a, g) in actual.chunks_exact(4).zip(golden.chunks_exact(4)) {
+        let difference = a.iter().zip(g).map(|(a, g)| a.abs_diff(*g)).max().unwrap_or(0);
+        max_difference = max_difference.max(difference);
+        if difference > tolerance {
+            failing_pixels += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let gray = ((g[0] as u32 * 299 + g[1] as u32 * 587 + g[2] as u32 * 114) / 4000) as u8;
+            diff.extend_from_slice(&[gray, gray, gray, 255]);
+        }
+    }
+    Comparison { failing_pixels, max_difference, diff }
+}
+
+/// A golden PNG as (RGBA8 pixels, width, height)
+pub fn read_golden(path: &Path) -> Result<(Vec<u8>, u32, u32), String> {
+    let image = image::open(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?.to_rgba8();
+    let (width, height) = image.dimensions();
+    Ok((image.into_raw(), width, height))
+}
+
+/// Where a failing test's frame and diff are written: next to the golden, as NAME.actual.png and NAME.diff.png
+pub fn failure_paths(golden: &Path) -> (PathBuf, PathBuf) {
+    let stem = golden.file_stem().unwrap_or_default().to_string_lossy();
+    (golden.with_file_name(format!("{}.actual.png", stem)), golden.with_file_name(format!("{}.diff.png", stem)))
+}
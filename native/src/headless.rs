@@ -0,0 +1,517 @@
+// Running a game without a window
+//
+// `wgsleng run --headless` loads and steps a game with the same Game as the
+// window does, so its frames are the window's: init() when it's due, the
+// @dispatch()es, the @pass()es and fs_render, then the host events, acted on
+// events::LATENCY frames later. It draws into an offscreen texture instead of a
+// surface, takes its input from a --record file (or none at all), advances time
+// by a fixed dt instead of the clock and logs sounds and music instead of
+// playing them, so it runs on CI machines with a software adapter and no display
+// or audio device. `wgsleng test` runs games the same way to compare frames with
+// the goldens in their tests.json.
+
+use std::path::{Path, PathBuf};
+
+use wgsleng::golden::{self, Input, Manifest, Test, DEFAULT_DT};
+use wgsleng::replay::{FrameInput, Replay};
+use wgsleng::{GameSource, PreprocessorOptions, PreprocessorState, KEY_ARRAY_SIZE};
+use winit::dpi::PhysicalSize;
+
+use crate::game::{Game, Target};
+use crate::{events, read_buffer, Saves};
+
+/// What the offscreen target is drawn in, and what PNGs are written from
+const TARGET_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// A game loaded onto the GPU without a window
+pub struct Headless {
+    /// The adapter's name and backend
+    pub adapter: String,
+    /// Print the sounds and music each frame triggers
+    pub log_sounds: bool,
+    pub width: u32,
+    pub height: u32,
+    /// Frames stepped so far
+    pub frame: usize,
+    /// Set once the game calls @engine.quit(), which ends a run
+    pub quit: bool,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    game: Game,
+    saves: Saves,
+    target: wgpu::Texture,
+    time: f32,
+}
+
+impl Headless {
+    /// Load a game. `software` insists on a software adapter (lavapipe, llvmpipe, WARP); `saves` is
+    /// where @engine.persist starts from and where @engine.save() goes; `size` is the one a recording
+    /// was made at, to draw at in place of the game's own.
+    pub async fn new(game_path: &str, options: &PreprocessorOptions, software: bool, saves: Saves, size: Option<(u32, u32)>) -> Result<Self, String> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor { backends: wgpu::Backends::all(), ..Default::default() });
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter: software,
+            })
+            .await
+            .ok_or(if software { "no software GPU adapter found" } else { "no GPU adapter found" })?;
+        let info = adapter.get_info();
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor::default(), None)
+            .await
+            .map_err(|e| format!("failed to create device: {}", e))?;
+
+        let mut headless = Self::load(device, queue, game_path, options, saves, size).await.map_err(|e| e.to_string())?;
+        headless.adapter = format!("{} ({:?})", info.name, info.backend);
+        Ok(headless)
+    }
+
+    async fn load(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        game_path: &str,
+        options: &PreprocessorOptions,
+        saves: Saves,
+        size: Option<(u32, u32)>,
+    ) -> Result<Self, wgsleng::Error> {
+        let entry_file = GameSource::entry_file(game_path);
+        let preprocessor = PreprocessorState::new(GameSource::open(game_path)?).with_entry_file(&entry_file).with_options(options);
+        // Sounds and music are timed on the game clock without being played
+        let target = Target { format: TARGET_FORMAT, size: size.map(|(width, height)| PhysicalSize::new(width, height)) };
+        let game = Game::load(&device, &queue, preprocessor, &target, None, &saves, None).await?;
+        let (width, height) = (game.size.width, game.size.height);
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Headless Target"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: TARGET_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        Ok(Self {
+            adapter: String::new(),
+            log_sounds: true,
+            width,
+            height,
+            frame: 0,
+            quit: false,
+            device,
+            queue,
+            game,
+            saves,
+            target,
+            time: 0.0,
+        })
+    }
+
+    pub fn title(&self) -> &str {
+        &self.game.metadata.title
+    }
+
+    /// Hash of the preprocessed shader, to check recordings against
+    pub fn source_hash(&self) -> &str {
+        &self.game.source_hash
+    }
+
+    /// Run one frame with this input: compute, render, then the sounds and requests it triggered
+    pub fn step(&mut self, input: &FrameInput) {
+        self.frame += 1;
+        self.time += input.dt;
+
+        let mut keys = [0u32; KEY_ARRAY_SIZE];
+        for &key in input.keys.iter().filter(|&&key| key < KEY_ARRAY_SIZE) {
+            keys[key] = 1;
+        }
+        for &(slot, value) in &input.osc {
+            self.game.set_osc_float(&self.queue, slot, value);
+        }
+        if input.reset {
+            self.game.reset(&self.queue);
+        }
+        self.game.update(&self.queue, self.time, input.dt, &input.buttons, &input.mouse, &keys);
+
+        let view = self.target.create_view(&wgpu::TextureViewDescriptor::default());
+        if let Some(events) = self.game.frame(&self.device, &self.queue, &view, self.time, input.dt) {
+            self.handle_events(events);
+        }
+    }
+
+    /// Act on the events of the last few frames, which would otherwise wait for the frames after them
+    pub fn finish(&mut self) {
+        for events in self.game.events.flush(&self.device) {
+            self.handle_events(events);
+        }
+    }
+
+    /// Dispatch a @test entry point once (a single workgroup) against the state as the frames so far
+    /// left it, after init() if that's still due, and return the @assert()s that failed
+    pub fn run_test(&mut self, entry_point: &str) -> Result<Vec<wgsleng::Assertion>, String> {
+        self.finish();
+        let asserts_offset = self.game.metadata.engine_offset("asserts").unwrap_or(0) as u64;
+        let asserts_size = self.game.metadata.asserts.len() * 4;
+        if asserts_size > 0 {
+            self.queue.write_buffer(&self.game.engine_buffer, asserts_offset, &vec![0u8; asserts_size]);
+        }
+        self.game.run_test(&self.device, &self.queue, entry_point)?;
+        self.log_prints();
+        if asserts_size == 0 {
+            return Ok(Vec::new());
+        }
+
+        let failed: Vec<u32> = bytemuck::cast_slice(&self.game.read_engine_bytes(&self.device, &self.queue, asserts_offset, asserts_size)).to_vec();
+        Ok(self.game.metadata.asserts.iter().zip(failed).filter(|(_, failed)| *failed != 0).map(|(assertion, _)| assertion.clone()).collect())
+    }
+
+    /// Log the sounds and music a frame played and what it printed, and carry out its requests, as the window does
+    fn handle_events(&mut self, events: events::FrameEvents) {
+        if self.log_sounds {
+            for trigger in &events.sounds {
+                println!("[audio] frame {}: {}", events.frame, describe_trigger(&self.game.metadata.sounds, trigger));
+            }
+            if let Some(command) = &events.music {
+                println!("[music] frame {}: {}", events.frame, describe_music(&self.game.metadata.music, command));
+            }
+        }
+        for line in wgsleng::read_prints(&self.game.metadata.prints, &events.prints) {
+            println!("[print] frame {}: {}", events.frame, line);
+        }
+        self.game.handle_events(&self.queue, &events, &mut self.saves);
+        if events.reset {
+            self.game.reset(&self.queue);
+        }
+        self.quit |= events.quit;
+    }
+
+    /// Print what a @test's @print()s wrote, and empty the print buffer
+    fn log_prints(&mut self) {
+        let Some(ref prints_buffer) = self.game.prints_buffer else {
+            return;
+        };
+        let data = read_buffer(&self.device, &self.queue, prints_buffer, 0, prints_buffer.size() as usize);
+        for line in wgsleng::read_prints(&self.game.metadata.prints, &data) {
+            println!("[print] frame {}: {}", self.frame, line);
+        }
+        self.queue.write_buffer(prints_buffer, 0, &0u32.to_le_bytes());
+    }
+    /// The last frame drawn, as tightly packed RGBA8 rows
+    pub fn capture(&self) -> Vec<u8> {
+        // Rows of a texture-to-buffer copy are padded to 256 bytes
+        let row = 4 * self.width;
+        let padded_row = row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Buffer"),
+            size: (padded_row * self.height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Capture") });
+        encoder.copy_texture_to_buffer(
+            self.target.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(padded_row), rows_per_image: Some(self.height) },
+            },
+            wgpu::Extent3d { width: self.width, height: self.height, depth_or_array_layers: 1 },
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let padded = read_buffer(&self.device, &self.queue, &buffer, 0, buffer.size() as usize);
+        padded.chunks(padded_row as usize).flat_map(|line| &line[..row as usize]).copied().collect()
+    }
+}
+
+/// A sound trigger as the log shows it: the sound, with what it was played with when that isn't the default
+fn describe_trigger(sounds: &[String], trigger: &wgsleng::SoundTrigger) -> String {
+    let sound = sounds.get(trigger.sound).map_or("?", String::as_str);
+    if trigger.stop {
+        return format!("stop {}", sound);
+    }
+    let mut settings = Vec::new();
+    if trigger.looping {
+        settings.push("loop".to_string());
+    }
+    for (name, value, default) in [("volume", trigger.volume, 1.0), ("pitch", trigger.pitch, 1.0), ("pan", trigger.pan, 0.0)] {
+        if value != default {
+            settings.push(format!("{} {}", name, value));
+        }
+    }
+    if settings.is_empty() {
+        sound.to_string()
+    } else {
+        format!("{} ({})", sound, settings.join(", "))
+    }
+}
+
+/// A music command as the log shows it, e.g. "loop theme.ogg (fade 2s)"
+fn describe_music(files: &[String], command: &wgsleng::MusicCommand) -> String {
+    let file = files.get(command.track).map_or("?", String::as_str);
+    let action = match command.action {
+        wgsleng::MusicAction::Play => "play",
+        wgsleng::MusicAction::Loop => "loop",
+        wgsleng::MusicAction::Pause => "pause",
+        wgsleng::MusicAction::Stop => "stop",
+    };
+    if command.fade > 0.0 {
+        format!("{} {} (fade {}s)", action, file, command.fade)
+    } else {
+        format!("{} {}", action, file)
+    }
+}
+
+/// Write RGBA8 pixels as a PNG
+pub fn write_png(path: &Path, rgba: &[u8], width: u32, height: u32) -> Result<(), String> {
+    let file = std::fs::File::create(path).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(rgba))
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+/// `wgsleng run --headless` settings
+pub struct RunOptions {
+    pub frames: Option<usize>,
+    /// dt for every frame; without it, a recording's own dts (or DEFAULT_DT without one)
+    pub fixed_dt: Option<f32>,
+    pub input: Option<PathBuf>,
+    pub out: PathBuf,
+    /// Frame numbers (from 1) to write; without them, only the last frame
+    pub capture: Vec<usize>,
+    pub every: Option<usize>,
+    pub software: bool,
+}
+
+/// `wgsleng run --headless`: step a game through its frames and write the chosen ones as PNGs, returning the process exit code
+pub fn run(game: &str, options: &PreprocessorOptions, run: &RunOptions) -> i32 {
+    match run_frames(game, options, run) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+fn run_frames(game: &str, options: &PreprocessorOptions, run: &RunOptions) -> Result<(), String> {
+    let replay = run.input.as_deref().map(Replay::open).transpose()?;
+    // Saves stay in memory, starting from the recording's @persist data, and it's drawn at the recording's size
+    let saves = Saves::Replay(replay.as_ref().and_then(|r| r.header.persist.clone()));
+    let size = replay.as_ref().and_then(|r| r.header.size);
+    let mut headless = pollster::block_on(Headless::new(game, options, run.software, saves, size))?;
+    if let Some(replay) = &replay {
+        if replay.header.source_hash != headless.source_hash() {
+            eprintln!(
+                "[headless] warning: {} was recorded with a different build of '{}' (source hash {}, this one is {})",
+                run.input.as_ref().unwrap().display(), replay.header.title, replay.header.source_hash, headless.source_hash()
+            );
+        }
+    }
+
+    let frames = run.frames.or(replay.as_ref().map(|r| r.frames.len())).unwrap_or(1);
+    // The last frame is the one the run stops on, whether it's run them all or the game quit
+    let captured = |frame: usize, last: bool| {
+        run.capture.contains(&frame) || run.every.is_some_and(|every| frame.checked_rem(every) == Some(0))
+            || (run.capture.is_empty() && run.every.is_none() && last)
+    };
+    std::fs::create_dir_all(&run.out).map_err(|e| format!("failed to create {}: {}", run.out.display(), e))?;
+
+    println!("[headless] {}: {} frames at {}x{} on {}", headless.title(), frames, headless.width, headless.height, headless.adapter);
+    let recorded = replay.as_ref().map_or(&[][..], |r| &r.frames);
+    let mut input = FrameInput::default();
+    for frame in 1..=frames {
+        input = next_input(recorded, frame, input, run.fixed_dt);
+        headless.step(&input);
+        if captured(frame, frame == frames || headless.quit) {
+            let path = run.out.join(format!("frame_{:05}.png", frame));
+            write_png(&path, &headless.capture(), headless.width, headless.height)?;
+            println!("[headless] wrote {}", path.display());
+        }
+        if headless.quit {
+            println!("[headless] the game quit at frame {}", frame);
+            break;
+        }
+    }
+    headless.finish();
+    Ok(())
+}
+
+/// Input for a frame (from 1): the recorded or scripted one, or past their end whatever was held
+/// last, with `fixed_dt` in place of the frame's own when given
+fn next_input(frames: &[FrameInput], frame: usize, previous: FrameInput, fixed_dt: Option<f32>) -> FrameInput {
+    let mut input = match frames.get(frame - 1) {
+        Some(input) => input.clone(),
+        None => FrameInput { dt: DEFAULT_DT, osc: Vec::new(), reset: false, ..previous },
+    };
+    if let Some(dt) = fixed_dt {
+        input.dt = dt;
+    }
+    input
+}
+
+/// How a test went
+enum Outcome {
+    Passed,
+    /// --update wrote the frame as the golden, for this reason
+    Updated(String),
+    Failed(String),
+}
+
+/// `wgsleng test`: run each game's @test entry points and the golden-image tests in its
+/// tests.json, returning the process exit code
+pub fn test(games: &[String], options: &PreprocessorOptions, update: bool, software: bool) -> i32 {
+    let (mut passed, mut updated, mut failed) = (0, 0, 0);
+    let mut report = |name: &str, outcome: Result<Outcome, String>| match outcome.unwrap_or_else(Outcome::Failed) {
+        Outcome::Passed => {
+            println!("test {} ... ok", name);
+            passed += 1;
+        }
+        Outcome::Updated(reason) => {
+            println!("test {} ... updated ({})", name, reason);
+            updated += 1;
+        }
+        Outcome::Failed(message) => {
+            println!("test {} ... FAILED\n    {}", name, message);
+            failed += 1;
+        }
+    };
+    for game in games {
+        let found = Manifest::load(game).and_then(|manifest| Ok((unit_tests(game, options, &manifest)?, manifest)));
+        let (units, manifest) = match found {
+            Ok(found) => found,
+            Err(e) => {
+                report(game, Err(e));
+                continue;
+            }
+        };
+        println!("\nrunning {} tests for {}", units.len() + manifest.tests.len(), game);
+        for entry_point in &units {
+            report(entry_point, run_unit(game, options, &manifest, entry_point, software));
+        }
+        for test in &manifest.tests {
+            report(&test.name, run_golden(game, options, &manifest, test, update, software));
+        }
+    }
+    let result = if failed > 0 { "FAILED" } else { "ok" };
+    println!("\ntest result: {}. {} passed; {} updated; {} failed", result, passed, updated, failed);
+    if failed > 0 { 1 } else { 0 }
+}
+
+/// A game's @test entry points, checking that the manifest's setups are for ones it has
+fn unit_tests(game: &str, options: &PreprocessorOptions, manifest: &Manifest) -> Result<Vec<String>, String> {
+    let entry_file = GameSource::entry_file(game);
+    let (_, metadata) = GameSource::open(game)
+        .and_then(|mut game_source| {
+            let source = game_source.read_text(&entry_file)?;
+            PreprocessorState::new(game_source).with_entry_file(&entry_file).with_options(options).preprocess_shader(&source, true)
+        })
+        .map_err(|e| e.to_string())?;
+    let units: Vec<String> = metadata.tests.into_iter().map(|t| t.entry_point).collect();
+    if let Some(setup) = manifest.setups.iter().find(|setup| !units.contains(&setup.test)) {
+        return Err(format!("{}: there's a setup for '{}', but no @test fn {}", game, setup.test, setup.test));
+    }
+    if units.is_empty() && manifest.tests.is_empty() {
+        return Err(format!("{}: nothing to test: no @test entry points and no tests in {}", game, golden::MANIFEST));
+    }
+    Ok(units)
+}
+
+/// Load a game for a test, along with the input it plays
+fn start(game: &str, options: &PreprocessorOptions, input: &Input, software: bool) -> Result<(Headless, Vec<FrameInput>), String> {
+    let replay = match input {
+        Input::Recording(path) => Some(Replay::open(path)?),
+        _ => None,
+    };
+    let saves = Saves::Replay(replay.as_ref().and_then(|r| r.header.persist.clone()));
+    let size = replay.as_ref().and_then(|r| r.header.size);
+    let mut headless = pollster::block_on(Headless::new(game, options, software, saves, size))?;
+    headless.log_sounds = false;
+    let frames = match (input, replay) {
+        (Input::Script(frames), _) => frames.clone(),
+        (_, Some(replay)) if replay.header.source_hash != headless.source_hash() => {
+            return Err(format!("the input was recorded with a different build of '{}'; record it again", replay.header.title));
+        }
+        (_, Some(replay)) => replay.frames,
+        _ => Vec::new(),
+    };
+    Ok((headless, frames))
+}
+
+/// Step through `count` frames of input
+fn play(headless: &mut Headless, frames: &[FrameInput], count: usize, fixed_dt: Option<f32>) {
+    let mut input = FrameInput::default();
+    for frame in 1..=count {
+        input = next_input(frames, frame, input, fixed_dt);
+        headless.step(&input);
+        if headless.quit {
+            break;
+        }
+    }
+}
+
+/// Run a @test entry point on a fresh game, after its setup's frames if it has one
+fn run_unit(game: &str, options: &PreprocessorOptions, manifest: &Manifest, entry_point: &str, software: bool) -> Result<Outcome, String> {
+    let setup = manifest.setups.iter().find(|setup| setup.test == entry_point);
+    let (mut headless, frames) = start(game, options, setup.map_or(&Input::None, |setup| &setup.input), software)?;
+    play(&mut headless, &frames, setup.and_then(|setup| setup.frames).unwrap_or(frames.len()), manifest.dt);
+    let failures: Vec<String> = headless.run_test(entry_point)?.iter()
+        .map(|a| format!("{}: assertion failed: {}: {}", a.location, a.condition, a.message))
+        .collect();
+    Ok(if failures.is_empty() { Outcome::Passed } else { Outcome::Failed(failures.join("\n    ")) })
+}
+
+/// Run a game up to the test's frame and compare it with the golden, or with `update`, replace a
+/// missing or mismatched golden
+fn run_golden(game: &str, options: &PreprocessorOptions, manifest: &Manifest, test: &Test, update: bool, software: bool) -> Result<Outcome, String> {
+    let (mut headless, frames) = start(game, options, &test.input, software)?;
+    play(&mut headless, &frames, test.frame, manifest.dt);
+    let actual = headless.capture();
+    let (width, height) = (headless.width, headless.height);
+
+    let (actual_path, diff_path) = golden::failure_paths(&test.golden);
+    let mismatch = if test.golden.exists() {
+        let (golden, golden_width, golden_height) = golden::read_golden(&test.golden)?;
+        if (golden_width, golden_height) != (width, height) {
+            Some((format!("the frame is {}x{} but the golden is {}x{}", width, height, golden_width, golden_height), None))
+        } else {
+            let comparison = golden::compare(&actual, &golden, test.tolerance);
+            (comparison.failing_pixels > test.max_failing_pixels).then(|| (
+                format!(
+                    "{} pixels differ from the golden by more than {} (up to {})",
+                    comparison.failing_pixels, test.tolerance, comparison.max_difference
+                ),
+                Some(comparison.diff),
+            ))
+        }
+    } else {
+        Some((format!("there's no golden at {}", test.golden.display()), None))
+    };
+
+    // Whatever an earlier failure left behind is stale now
+    for path in [&actual_path, &diff_path] {
+        let _ = std::fs::remove_file(path);
+    }
+    let Some((reason, diff)) = mismatch else {
+        return Ok(Outcome::Passed);
+    };
+    if let Some(dir) = test.golden.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+    }
+    if update {
+        write_png(&test.golden, &actual, width, height)?;
+        return Ok(Outcome::Updated(reason));
+    }
+    write_png(&actual_path, &actual, width, height)?;
+    let mut wrote = actual_path.display().to_string();
+    if let Some(diff) = diff {
+        write_png(&diff_path, &diff, width, height)?;
+        wrote = format!("{} and {}", wrote, diff_path.display());
+    }
+    let hint = if test.golden.exists() { "" } else { "; run with --update to create it" };
+    Ok(Outcome::Failed(format!("{}{} (wrote {})", reason, hint, wrote)))
+}
@@ -0,0 +1,183 @@
+// JSON description of what the preprocessor decided for a game
+//
+// This is what `wgsleng inspect` writes next to the processed WGSL: every
+// asset with the slot or binding it was given, the GameState and engine buffer
+// layouts from naga, and the full binding plan the runtime builds pipelines
+// from. Editors and other runtimes can read it instead of re-implementing the
+// preprocessor's numbering rules.
+
+use serde_json::{json, Value};
+
+use crate::save::SaveFile;
+use crate::{BindingSlot, FieldLayout, Metadata};
+
+/// Metadata as a JSON object
+pub fn metadata_json(metadata: &Metadata) -> Value {
+    let plan = metadata.binding_plan();
+    let binding = |name: &str| {
+        plan.iter().find(|s| s.name == name).map(|s| json!({ "group": s.group, "binding": s.binding }))
+    };
+
+    let textures: Vec<Value> = metadata.textures.iter().enumerate().map(|(i, path)| json!({
+        "path": path,
+        "index": i,
+        "binding": binding(&format!("_texture_{}", i)),
+    })).collect();
+    let sounds: Vec<Value> = metadata.sounds.iter().enumerate().map(|(i, path)| json!({
+        "path": path,
+        "audio_slot": i,
+        "voices": metadata.sound_voices[i],
+    })).collect();
+    let music: Vec<Value> = metadata.music.iter().zip(&metadata.music_loops).enumerate().map(|(i, (path, music_loop))| json!({
+        "path": path,
+        "track": i + 1,
+        "loop_start": music_loop.start,
+        "loop_end": music_loop.end,
+    })).collect();
+    let models: Vec<Value> = metadata.models.iter().enumerate().map(|(i, path)| json!({
+        "path": path,
+        "positions": binding(&format!("_model_{}_positions", i)),
+        "normals": binding(&format!("_model_{}_normals", i)),
+    })).collect();
+    let videos: Vec<Value> = metadata.videos.iter().enumerate().map(|(i, path)| json!({
+        "path": path,
+        "binding": binding(&format!("_video_{}", i)),
+    })).collect();
+    let cameras: Vec<Value> = metadata.cameras.iter().enumerate().map(|(i, camera)| json!({
+        "camera": camera,
+        "binding": binding(&format!("_camera_{}", i)),
+    })).collect();
+    let osc: Vec<Value> = metadata.osc_params.iter().enumerate().map(|(i, name)| json!({
+        "name": name,
+        "slot": i,
+    })).collect();
+    let dispatches: Vec<Value> = metadata.dispatches.iter().map(|d| json!({
+        "entry_point": d.entry_point,
+        "workgroups": d.workgroups.iter().map(ToString::to_string).collect::<Vec<_>>(),
+    })).collect();
+    let passes: Vec<Value> = metadata.passes.iter().enumerate().map(|(i, pass)| json!({
+        "name": pass.name,
+        "entry_point": pass.entry_point,
+        "binding": binding(&format!("_buffer_{}", i)),
+    })).collect();
+    let storage: Vec<Value> = metadata.storage_buffers.iter().enumerate().map(|(i, buffer)| json!({
+        "name": buffer.name,
+        "type": buffer.ty,
+        "size": buffer.size,
+        "element": buffer.element,
+        "binding": binding(&format!("_storage_{}", i)),
+    })).collect();
+    let canvases: Vec<Value> = metadata.canvases.iter().enumerate().map(|(i, canvas)| json!({
+        "name": canvas.name,
+        "width": canvas.width,
+        "height": canvas.height,
+        "format": canvas.format,
+        "binding": binding(&format!("_canvas_{}", i)),
+        "storage_binding": binding(&format!("_canvas_{}_storage", i)),
+    })).collect();
+    let tests: Vec<Value> = metadata.tests.iter().map(|t| json!({
+        "entry_point": t.entry_point,
+        "file": t.location.file,
+        "line": t.location.line,
+        "column": t.location.column,
+    })).collect();
+    let asserts: Vec<Value> = metadata.asserts.iter().enumerate().map(|(i, a)| json!({
+        "id": i,
+        "condition": a.condition,
+        "message": a.message,
+        "file": a.location.file,
+        "line": a.location.line,
+        "column": a.location.column,
+    })).collect();
+    let prints: Vec<Value> = metadata.prints.iter().enumerate().map(|(i, p)| json!({
+        "id": i,
+        "format": p.format,
+        "values": p.values,
+        "file": p.location.file,
+        "line": p.location.line,
+        "column": p.location.column,
+    })).collect();
+    let assets: Vec<Value> = metadata.asset_references.iter().map(|r| json!({
+        "kind": r.kind,
+        "path": r.path,
+        "file": r.location.file,
+        "line": r.location.line,
+        "column": r.location.column,
+    })).collect();
+
+    json!({
+        "title": metadata.title,
+        "width": metadata.width,
+        "height": metadata.height,
+        "textures": textures,
+        "sounds": sounds,
+        "music": music,
+        "models": models,
+        "videos": videos,
+        "cameras": cameras,
+        "osc": osc,
+        "dispatches": dispatches,
+        "init": metadata.has_init,
+        "passes": passes,
+        "storage": storage,
+        "canvases": canvases,
+        "tests": tests,
+        "asserts": asserts,
+        "prints": prints,
+        "state": {
+            "offset": metadata.engine_offset("state"),
+            "size": metadata.state_size,
+            "fields": fields_json(&metadata.state_fields),
+        },
+        "persist": persist_json(metadata),
+        "engine": {
+            "size": metadata.engine_size,
+            "fields": fields_json(&metadata.engine_fields),
+        },
+        "bindings": plan.iter().map(binding_json).collect::<Vec<_>>(),
+        "assets": assets,
+        "files": metadata.source_map.files(),
+    })
+}
+
+/// The @persist struct's layout and the key its save file is stored under
+fn persist_json(metadata: &Metadata) -> Value {
+    match SaveFile::new(metadata) {
+        Some(save) => json!({
+            "struct": metadata.persist_struct,
+            "offset": save.offset,
+            "size": save.size,
+            "fields": fields_json(&metadata.persist_fields),
+            "key": save.key,
+        }),
+        None => Value::Null,
+    }
+}
+
+fn fields_json(fields: &[FieldLayout]) -> Vec<Value> {
+    fields.iter().map(|f| json!({ "name": f.name, "offset": f.offset, "size": f.size })).collect()
+}
+
+fn binding_json(slot: &BindingSlot) -> Value {
+    let ty = match slot.ty {
+        wgpu::BindingType::Sampler(_) => "sampler",
+        wgpu::BindingType::Texture { .. } => "texture_2d<f32>",
+        wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: true }, .. } => "storage, read",
+        wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: false }, .. } => "storage, read_write",
+        wgpu::BindingType::StorageTexture { .. } => "texture_storage_2d, write",
+        _ => "other",
+    };
+    let stages: Vec<&str> = [
+        (wgpu::ShaderStages::VERTEX, "vertex"),
+        (wgpu::ShaderStages::FRAGMENT, "fragment"),
+        (wgpu::ShaderStages::COMPUTE, "compute"),
+    ].iter().filter(|(s, _)| slot.visibility.contains(*s)).map(|(_, n)| *n).collect();
+    json!({
+        "group": slot.group,
+        "binding": slot.binding,
+        "name": slot.name,
+        "asset": slot.asset,
+        "type": ty,
+        "visibility": stages,
+    })
+}
@@ -0,0 +1,188 @@
+// Tokenizer for WGSL source with engine @-macros
+//
+// The preprocessor only needs a coarse view of WGSL: identifiers, numbers,
+// punctuation, comments and whitespace. String literals are not part of WGSL,
+// but macro arguments like @texture("file.png") use them, so they get their
+// own token kind. Every token keeps its byte span so callers can copy the
+// original text through untouched and report precise locations.
+
+/// Byte range into the source text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.start..self.end]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Ident,
+    Number,
+    /// "..." with backslash escapes (only valid inside macro arguments)
+    Str,
+    /// A single punctuation character
+    Punct(char),
+    /// The `@` that starts an attribute or engine macro
+    At,
+    Whitespace,
+    LineComment,
+    /// /* ... */ (WGSL block comments nest)
+    BlockComment,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn is_trivia(&self) -> bool {
+        matches!(self.kind, TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment)
+    }
+
+    pub fn is_punct(&self, c: char) -> bool {
+        self.kind == TokenKind::Punct(c)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LexError {
+    pub message: String,
+    pub offset: usize,
+}
+
+/// 1-based line and column (in characters) of a byte offset
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let col = before[line_start..].chars().count() + 1;
+    (line, col)
+}
+
+fn is_ident_start(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+/// Split WGSL source into tokens. The concatenated spans always cover the whole input.
+pub fn tokenize(source: &str) -> Result<Vec<Token>, LexError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        let kind = if c.is_whitespace() {
+            while chars.peek().is_some_and(|&(_, c)| c.is_whitespace()) {
+                chars.next();
+            }
+            TokenKind::Whitespace
+        } else if source[start..].starts_with("//") {
+            while chars.peek().is_some_and(|&(_, c)| c != '\n') {
+                chars.next();
+            }
+            TokenKind::LineComment
+        } else if source[start..].starts_with("/*") {
+            chars.next();
+            chars.next();
+            let mut depth = 1;
+            while depth > 0 {
+                match chars.next() {
+                    Some((i, '*')) if source[i..].starts_with("*/") => {
+                        chars.next();
+                        depth -= 1;
+                    }
+                    Some((i, '/')) if source[i..].starts_with("/*") => {
+                        chars.next();
+                        depth += 1;
+                    }
+                    Some(_) => {}
+                    None => {
+                        return Err(LexError { message: "unterminated block comment".to_string(), offset: start });
+                    }
+                }
+            }
+            TokenKind::BlockComment
+        } else if c == '"' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some((_, '\\')) => {
+                        chars.next();
+                    }
+                    Some((_, '"')) => break,
+                    Some((_, '\n')) | None => {
+                        return Err(LexError { message: "unterminated string literal".to_string(), offset: start });
+                    }
+                    Some(_) => {}
+                }
+            }
+            TokenKind::Str
+        } else if is_ident_start(c) {
+            while chars.peek().is_some_and(|&(_, c)| is_ident_continue(c)) {
+                chars.next();
+            }
+            TokenKind::Ident
+        } else if c.is_ascii_digit() || (c == '.' && source[start + 1..].starts_with(|c: char| c.is_ascii_digit())) {
+            // Numbers are only passed through, so a loose match is enough:
+            // digits, letters (hex, suffixes, exponents), dots and exponent signs
+            let mut prev = c;
+            chars.next();
+            while let Some(&(_, c)) = chars.peek() {
+                let exponent_sign = (c == '+' || c == '-') && matches!(prev, 'e' | 'E' | 'p' | 'P');
+                if c.is_ascii_alphanumeric() || c == '.' || c == '_' || exponent_sign {
+                    prev = c;
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            TokenKind::Number
+        } else if c == '@' {
+            chars.next();
+            TokenKind::At
+        } else {
+            chars.next();
+            TokenKind::Punct(c)
+        };
+
+        let end = chars.peek().map(|&(i, _)| i).unwrap_or(source.len());
+        tokens.push(Token { kind, span: Span::new(start, end) });
+    }
+
+    Ok(tokens)
+}
+
+/// Decode the contents of a string literal token, including its quotes
+pub fn unescape_str(literal: &str) -> String {
+    let inner = &literal[1..literal.len() - 1];
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('0') => out.push('\0'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
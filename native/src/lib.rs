@@ -1,12 +1,27 @@
 // Shared library for WGSL game engine processing
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::Read;
-use regex::Regex;
 use zip::ZipArchive;
 
+pub mod check;
+pub mod dispatch;
+pub mod error;
+pub mod golden;
+pub mod inspect;
+pub mod lexer;
 pub mod obj_loader;
+pub mod replay;
+pub mod save;
+pub mod source_map;
+pub mod stdlib;
+pub use dispatch::{Dispatch, DispatchExpr};
+pub use error::Error;
 pub use obj_loader::ObjModel;
+pub use source_map::{SourceLocation, SourceMap};
+
+use lexer::{Span, Token, TokenKind};
+use source_map::{LineIndex, MappedWriter};
 
 /// Number of named OSC float slots accessible via @osc("name") or @engine.osc[N]
 pub const OSC_FLOAT_COUNT: usize = 64;
@@ -235,7 +250,7 @@ pub enum GameSource {
 }
 
 impl GameSource {
-    pub fn open(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn open(path: &str) -> Result<Self, Error> {
         // Check if it's a .wgsl file
         if path.ends_with(".wgsl") {
             let path_obj = std::path::Path::new(path);
@@ -247,8 +262,8 @@ impl GameSource {
 
         // Check if it's a zip file
         if path.ends_with(".zip") {
-            let file = std::fs::File::open(path)?;
-            let archive = ZipArchive::new(file)?;
+            let file = std::fs::File::open(path).map_err(|e| io_error(path, e))?;
+            let archive = ZipArchive::new(file).map_err(|source| Error::ZipError { path: path.to_string(), source })?;
             return Ok(GameSource::Zip(archive));
         }
 
@@ -256,32 +271,54 @@ impl GameSource {
         Ok(GameSource::Directory(std::path::PathBuf::from(path)))
     }
 
-    pub fn read_file(&mut self, file_path: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    /// Name of the top-level shader: the file itself for a .wgsl path, otherwise main.wgsl
+    pub fn entry_file(path: &str) -> String {
+        if path.ends_with(".wgsl") {
+            if let Some(name) = std::path::Path::new(path).file_name() {
+                return name.to_string_lossy().to_string();
+            }
+        }
+        "main.wgsl".to_string()
+    }
+
+    pub fn read_file(&mut self, file_path: &str) -> Result<Vec<u8>, Error> {
         match self {
             GameSource::Directory(base_path) => {
                 let requested = std::path::Path::new(file_path);
                 if requested.components().any(|c| matches!(c, std::path::Component::ParentDir)) {
-                    return Err("Directory traversal not allowed".into());
+                    return Err(Error::InvalidPath { path: file_path.to_string(), referenced_from: None });
                 }
-                Ok(fs::read(base_path.join(file_path))?)
+                fs::read(base_path.join(file_path)).map_err(|e| io_error(file_path, e))
             }
             GameSource::Zip(archive) => {
                 let stripped = file_path.strip_prefix("./").unwrap_or(file_path);
                 match archive.by_name(stripped) {
                     Ok(mut file) => {
                         let mut contents = Vec::new();
-                        file.read_to_end(&mut contents)?;
+                        file.read_to_end(&mut contents).map_err(|e| io_error(file_path, e))?;
                         Ok(contents)
                     }
-                    Err(_) => Err(format!("File not found in zip: {}", file_path).into())
+                    Err(zip::result::ZipError::FileNotFound) => {
+                        Err(Error::AssetNotFound { path: file_path.to_string(), referenced_from: None })
+                    }
+                    Err(source) => Err(Error::ZipError { path: file_path.to_string(), source }),
                 }
             }
         }
     }
 
-    pub fn read_text(&mut self, file_path: &str) -> Result<String, Box<dyn std::error::Error>> {
+    /// Read a file as UTF-8 text
+    pub fn read_text(&mut self, file_path: &str) -> Result<String, Error> {
         let bytes = self.read_file(file_path)?;
-        Ok(String::from_utf8(bytes)?)
+        String::from_utf8(bytes).map_err(|_| Error::InvalidUtf8 { path: file_path.to_string(), referenced_from: None })
+    }
+}
+
+/// A missing file is AssetNotFound; anything else is an I/O error
+pub(crate) fn io_error(path: &str, e: std::io::Error) -> Error {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => Error::AssetNotFound { path: path.to_string(), referenced_from: None },
+        _ => Error::Io { path: path.to_string(), source: e },
     }
 }
 
@@ -292,498 +329,2299 @@ pub struct Metadata {
     pub height: u32,
     pub textures: Vec<String>,
     pub sounds: Vec<String>,
+    /// How many voices of each sound can play at once, from @set_voices() or DEFAULT_SOUND_VOICES
+    pub sound_voices: Vec<usize>,
+    /// @music() tracks, in the order they're first referenced
+    pub music: Vec<String>,
+    /// Where each track loops, from @set_music_loop() or the whole track
+    pub music_loops: Vec<MusicLoop>,
     pub models: Vec<String>,
+    /// Byte size of GameState including trailing padding (0 when the game has none)
     pub state_size: usize,
+    /// Top-level GameState fields; offsets are relative to the start of the state
+    pub state_fields: Vec<FieldLayout>,
+    /// GameEngineHost members; offsets are into the engine buffer
+    pub engine_fields: Vec<FieldLayout>,
+    /// Byte size of the engine buffer
+    pub engine_size: usize,
     /// Ordered list of @osc("name") parameters; index in this vec = osc slot index
     pub osc_params: Vec<String>,
     /// Ordered list of @video("file") filenames; index = video binding slot
     pub videos: Vec<String>,
     /// Sorted list of @camera(N) indices; index = camera binding slot
     pub cameras: Vec<u32>,
+    /// Compute entry points to run each frame, in order: the game's @dispatch()es,
+    /// after a single-workgroup `update` unless the game dispatches `update` itself
+    pub dispatches: Vec<Dispatch>,
+    /// Whether the game has a `@compute fn init()`, run once on load, on reset and when a reload changes GameState's layout
+    pub has_init: bool,
+    /// Whether game code calls @engine.reset(), so the runtime has to read the request back each frame
+    pub uses_reset: bool,
+    /// Whether game code calls @engine.quit(), so the runtime has to read the request back each frame
+    pub uses_quit: bool,
+    /// Whether game code reads @engine.spectrum, @engine.waveform or @engine.audio_level, so the
+    /// engine buffer has room for them and the runtime analyses what's playing each frame
+    pub uses_audio_analysis: bool,
+    /// Name of the struct marked @persist, kept in the game's save file as @engine.persist
+    pub persist_struct: Option<String>,
+    /// Byte size of the @persist struct including trailing padding (0 when the game has none)
+    pub persist_size: usize,
+    /// Top-level fields of the @persist struct; offsets are relative to its start
+    pub persist_fields: Vec<FieldLayout>,
+    /// Compute entry points marked @test, in order, for `wgsleng test` to run
+    pub tests: Vec<TestEntry>,
+    /// Every @assert(), indexed by the id it writes into @engine's asserts array
+    pub asserts: Vec<Assertion>,
+    /// Every @print(), indexed by the id it writes into the print buffer
+    pub prints: Vec<Print>,
+    /// Offscreen render targets from @pass(), drawn in this order before fs_render; index = buffer binding slot
+    pub passes: Vec<Pass>,
+    /// Buffers from @storage("name", type), outside the engine buffer; index = binding slot after `_engine`
+    pub storage_buffers: Vec<StorageBuffer>,
+    /// Compute-writable textures from @canvas(); index = canvas binding slot
+    pub canvases: Vec<Canvas>,
+    /// Every asset file the game refers to, at its first reference
+    pub asset_references: Vec<AssetReference>,
+    /// Maps lines of the preprocessed shader back to the original files
+    pub source_map: SourceMap,
 }
 
-pub struct PreprocessorState {
-    pub game_source: GameSource,
-    imported_files: HashSet<String>,
+/// Byte layout of one struct member, as computed by naga's layouter
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldLayout {
+    pub name: String,
+    pub offset: usize,
+    pub size: usize,
 }
 
-impl PreprocessorState {
-    pub fn new(game_source: GameSource) -> Self {
-        Self {
-            game_source,
-            imported_files: HashSet::new(),
+/// An asset file named by a macro such as @texture("player.png")
+#[derive(Debug, Clone)]
+pub struct AssetReference {
+    /// Macro that referenced it: texture, sound, model or video
+    pub kind: &'static str,
+    pub path: String,
+    pub location: SourceLocation,
+}
+
+/// An offscreen render target declared with @pass("name", fs_entry) and sampled as @buffer("name")
+#[derive(Debug, Clone)]
+pub struct Pass {
+    pub name: String,
+    /// Fragment entry point that draws it, with PASS_VERTEX_ENTRY as the vertex stage
+    pub entry_point: String,
+    /// The @pass() that declared it
+    pub location: SourceLocation,
+}
+
+/// Vertex entry point the header adds for @pass() pipelines: one triangle covering the whole target,
+/// with the position and `@location(0) uv` (0,0 at the top left) for the fragment stage
+pub const PASS_VERTEX_ENTRY: &str = "_vs_pass";
+
+/// A compute entry point marked @test
+#[derive(Debug, Clone)]
+pub struct TestEntry {
+    pub entry_point: String,
+    /// The @test that marked it
+    pub location: SourceLocation,
+}
+
+/// An @assert(condition, "message"); a failing one sets its slot in @engine's asserts array
+#[derive(Debug, Clone)]
+pub struct Assertion {
+    /// The condition as written
+    pub condition: String,
+    pub message: String,
+    pub location: SourceLocation,
+}
+
+/// Slots in the engine buffer's sound queue: @sound() calls past this many in a frame are dropped
+pub const MAX_SOUND_TRIGGERS: usize = 32;
+
+/// Voices of a sound that can play at once without a @set_voices(); another play() stops the oldest
+pub const DEFAULT_SOUND_VOICES: usize = 8;
+
+/// Volume, pitch and pan for the ones a @sound(...).play() leaves out
+const SOUND_DEFAULTS: [&str; 3] = ["1.0", "1.0", "0.0"];
+
+/// _SoundTrigger flags
+const SOUND_LOOP: u32 = 1;
+const SOUND_STOP: u32 = 2;
+
+/// A @sound("x").play(volume, pitch, pan), .loop(...) or .stop() call, as the runtime reads it back
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundTrigger {
+    /// Index into Metadata::sounds
+    pub sound: usize,
+    /// .loop(): play until stopped
+    pub looping: bool,
+    /// .stop(): stop every voice of the sound
+    pub stop: bool,
+    pub volume: f32,
+    /// Playback speed, which shifts the pitch with it: 2.0 is an octave up
+    pub pitch: f32,
+    /// -1.0 (left) to 1.0 (right)
+    pub pan: f32,
+}
+
+/// The sound queue as a frame left it (sound_count, then sound_triggers): its calls in the order they ran
+pub fn read_sound_triggers(data: &[u8]) -> Vec<SoundTrigger> {
+    let words: Vec<u32> = data.chunks_exact(4).map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]])).collect();
+    let Some((&count, triggers)) = words.split_first() else {
+        return Vec::new();
+    };
+    triggers.chunks_exact(5).take((count as usize).min(MAX_SOUND_TRIGGERS))
+        .map(|trigger| SoundTrigger {
+            sound: trigger[0] as usize,
+            looping: trigger[1] & SOUND_LOOP != 0,
+            stop: trigger[1] & SOUND_STOP != 0,
+            volume: f32::from_bits(trigger[2]),
+            pitch: f32::from_bits(trigger[3]),
+            pan: f32::from_bits(trigger[4]),
+        })
+        .collect()
+}
+
+/// The section of a @music() track that .loop() repeats, in seconds
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MusicLoop {
+    pub start: f32,
+    /// None for the end of the track
+    pub end: Option<f32>,
+}
+
+/// What a @music("x") call asks for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MusicAction {
+    /// .play(): play it once, or resume it if it's paused
+    Play,
+    /// .loop(): play it over and over between its loop points
+    Loop,
+    Pause,
+    Stop,
+}
+
+/// The last @music("x") call of a frame, as the runtime reads it back
+#[derive(Debug, Clone, PartialEq)]
+pub struct MusicCommand {
+    /// Index into Metadata::music
+    pub track: usize,
+    pub action: MusicAction,
+    /// Seconds to fade over: a play() or loop() fades out whatever is playing and crossfades to
+    /// the track, a stop() fades it out
+    pub fade: f32,
+}
+
+/// The music command as a frame left it (music_command, music_command_track, music_command_fade)
+pub fn read_music_command(data: &[u8]) -> Option<MusicCommand> {
+    let words: Vec<u32> = data.chunks_exact(4).map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]])).collect();
+    let action = match words.first()? {
+        1 => MusicAction::Play,
+        2 => MusicAction::Loop,
+        3 => MusicAction::Pause,
+        4 => MusicAction::Stop,
+        _ => return None,
+    };
+    Some(MusicCommand { track: *words.get(1)? as usize, action, fade: f32::from_bits(*words.get(2)?) })
+}
+
+/// Samples of the mix in @engine.waveform, which the spectrum is taken over
+pub const WAVEFORM_SIZE: usize = 1024;
+
+/// Bins in @engine.spectrum, from 0 Hz up to half the sample rate
+pub const SPECTRUM_SIZE: usize = WAVEFORM_SIZE / 2;
+
+/// Slots in the print buffer: @print()s past this many in a frame are counted but not logged
+pub const MAX_PRINTS_PER_FRAME: usize = 32;
+
+/// A @print("format {}", values...); each call claims a slot in the print buffer for the runtime to log
+#[derive(Debug, Clone)]
+pub struct Print {
+    /// The format string, with one `{}` per value (`{{` and `}}` for literal braces)
+    pub format: String,
+    /// The values as written
+    pub values: Vec<String>,
+    pub location: SourceLocation,
+}
+
+impl Print {
+    /// The format string with `values` in place of its `{}`s
+    pub fn format(&self, values: &[f32]) -> String {
+        let mut out = String::new();
+        let mut values = values.iter();
+        let mut chars = self.format.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    out.push(c);
+                    chars.next();
+                }
+                ('{', Some('}')) => {
+                    chars.next();
+                    if let Some(value) = values.next() {
+                        out.push_str(&value.to_string());
+                    }
+                }
+                _ => out.push(c),
+            }
         }
+        out
     }
+}
 
-    pub fn preprocess_shader(&mut self, source: &str, is_top_level: bool) -> Result<(String, Metadata), Box<dyn std::error::Error>> {
-        let mut source = source.to_string();
+/// How many `{}`s a @print() format string has, or what's wrong with it
+fn count_placeholders(format: &str) -> Result<usize, String> {
+    let mut count = 0;
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        match (c, if matches!(c, '{' | '}') { chars.next() } else { None }) {
+            ('{', Some('}')) => count += 1,
+            ('{', Some('{')) | ('}', Some('}')) => {}
+            ('{' | '}', _) => return Err("use {} for a value, and {{ or }} for a literal brace".to_string()),
+            _ => {}
+        }
+    }
+    Ok(count)
+}
 
-        // Process @import directives first (recursive, like C #include)
-        let import_re = Regex::new(r#"@import\("([^"]+)"\)"#)?;
-        loop {
-            // Collect captures into owned strings to avoid borrow issues
-            let captures: Vec<(String, String)> = import_re.captures_iter(&source)
-                .map(|cap| (cap.get(0).unwrap().as_str().to_string(), cap[1].to_string()))
-                .collect();
+/// Values in each print buffer slot: as many as the @print() with the most
+fn print_values(prints: &[Print]) -> usize {
+    prints.iter().map(|p| p.values.len()).max().unwrap_or(0).max(1)
+}
 
-            if captures.is_empty() {
-                break;
+/// The lines a frame's print buffer holds: each @print() with its location, in the order
+/// they ran, then how many didn't fit
+pub fn read_prints(prints: &[Print], data: &[u8]) -> Vec<String> {
+    let words: Vec<u32> = data.chunks_exact(4).map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]])).collect();
+    let Some((&count, slots)) = words.split_first() else {
+        return Vec::new();
+    };
+    let mut lines: Vec<String> = slots.chunks_exact(1 + print_values(prints)).take((count as usize).min(MAX_PRINTS_PER_FRAME))
+        .filter_map(|slot| {
+            let print = prints.get(slot[0] as usize)?;
+            let values: Vec<f32> = slot[1..=print.values.len()].iter().map(|&bits| f32::from_bits(bits)).collect();
+            Some(format!("{}: {}", print.location, print.format(&values)))
+        })
+        .collect();
+    if count as usize > MAX_PRINTS_PER_FRAME {
+        lines.push(format!("{} more @print()s this frame were dropped", count as usize - MAX_PRINTS_PER_FRAME));
+    }
+    lines
+}
+
+/// A zero-initialized storage buffer declared with @storage("name", type) and used as @storage("name")
+#[derive(Debug, Clone)]
+pub struct StorageBuffer {
+    pub name: String,
+    /// WGSL type as written (after namespace renames), e.g. `array<Particle, 100000>`
+    pub ty: String,
+    /// Byte size, from naga's layout
+    pub size: usize,
+    /// Array element type with struct members spelled out; contents survive a hot reload while it stays the same
+    pub element: String,
+    /// The @storage() that declared it
+    pub location: SourceLocation,
+}
+
+/// Texel formats a @canvas() may use: writable from compute and filterable when sampled
+pub const CANVAS_FORMATS: &[(&str, wgpu::TextureFormat)] = &[
+    ("rgba8unorm", wgpu::TextureFormat::Rgba8Unorm),
+    ("rgba8snorm", wgpu::TextureFormat::Rgba8Snorm),
+    ("rgba16float", wgpu::TextureFormat::Rgba16Float),
+];
+
+/// A texture declared with @canvas("name", width, height, format). Compute entry points
+/// write it through `@canvas("name").storage`; render stages sample `@canvas("name")`.
+#[derive(Debug, Clone)]
+pub struct Canvas {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// WGSL texel format, one of CANVAS_FORMATS
+    pub format: String,
+    /// The @canvas() that declared it
+    pub location: SourceLocation,
+}
+
+impl Canvas {
+    pub fn texture_format(&self) -> wgpu::TextureFormat {
+        CANVAS_FORMATS.iter().find(|(name, _)| *name == self.format).map_or(wgpu::TextureFormat::Rgba8Unorm, |(_, f)| *f)
+    }
+}
+
+/// A resource binding the runtime provides to the game shader
+#[derive(Debug, Clone)]
+pub struct BindingSlot {
+    pub group: u32,
+    pub binding: u32,
+    /// Variable the generated header declares for it
+    pub name: String,
+    /// Asset file that backs it (texture, video or model path)
+    pub asset: Option<String>,
+    pub ty: wgpu::BindingType,
+    /// Every stage the runtime's pipeline layouts expose this binding to
+    pub visibility: wgpu::ShaderStages,
+}
+
+impl BindingSlot {
+    /// Layout entry for a pipeline that only runs `stages`
+    pub fn layout_entry(&self, stages: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding: self.binding,
+            visibility: self.visibility & stages,
+            ty: self.ty,
+            count: None,
+        }
+    }
+}
+
+impl Metadata {
+    /// Byte offset of a GameEngineHost member (buttons, time, state, sound_count, osc, keys, ...)
+    pub fn engine_offset(&self, member: &str) -> Option<usize> {
+        self.engine_fields.iter().find(|f| f.name == member).map(|f| f.offset)
+    }
+
+    /// Values in each print buffer slot
+    pub fn print_values(&self) -> usize {
+        print_values(&self.prints)
+    }
+
+    /// Byte size of the print buffer: a count of the frame's @print() calls, then
+    /// MAX_PRINTS_PER_FRAME slots of an id and its values (0 when the game has no @print)
+    pub fn prints_size(&self) -> usize {
+        if self.prints.is_empty() {
+            return 0;
+        }
+        4 + MAX_PRINTS_PER_FRAME * (4 + 4 * self.print_values())
+    }
+
+    /// Bindings the runtime creates for this game, in group/binding order.
+    /// Group 0 holds the sampler, textures, videos, cameras, pass buffers and canvases, then the
+    /// canvases' storage views (compute only); group 1 the engine buffer, its read-only copy for
+    /// the vertex stage, the @storage() buffers and the print buffer; group 2 the model buffers. The sampler,
+    /// textures, videos, cameras and models are visible to every stage.
+    pub fn binding_plan(&self) -> Vec<BindingSlot> {
+        let texture = wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        };
+        let storage = |read_only| wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+        let slot = |group, binding: usize, name: String, asset: Option<&String>, ty, visibility| BindingSlot {
+            group,
+            binding: binding as u32,
+            name,
+            asset: asset.cloned(),
+            ty,
+            visibility,
+        };
+
+        let all = wgpu::ShaderStages::VERTEX_FRAGMENT | wgpu::ShaderStages::COMPUTE;
+
+        let mut plan = vec![slot(0, 0, "_engine_sampler".to_string(), None,
+            wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering), all)];
+        let textures = self.textures.iter().enumerate().map(|(i, path)| (format!("_texture_{}", i), Some(path), all));
+        let videos = self.videos.iter().enumerate().map(|(i, path)| (format!("_video_{}", i), Some(path), all));
+        let cameras = self.cameras.iter().enumerate().map(|(i, _)| (format!("_camera_{}", i), None, all));
+        // Pass targets and canvases are written during the frame, so compute never samples them
+        let buffers = self.passes.iter().enumerate().map(|(i, _)| (format!("_buffer_{}", i), None, wgpu::ShaderStages::FRAGMENT));
+        let canvases = self.canvases.iter().enumerate().map(|(i, _)| (format!("_canvas_{}", i), None, wgpu::ShaderStages::FRAGMENT));
+        for (i, (name, asset, visibility)) in textures.chain(videos).chain(cameras).chain(buffers).chain(canvases).enumerate() {
+            plan.push(slot(0, i + 1, name, asset, texture, visibility));
+        }
+        let storage_base = plan.len();
+        for (i, canvas) in self.canvases.iter().enumerate() {
+            let ty = wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: canvas.texture_format(),
+                view_dimension: wgpu::TextureViewDimension::D2,
+            };
+            plan.push(slot(0, storage_base + i, format!("_canvas_{}_storage", i), None, ty, wgpu::ShaderStages::COMPUTE));
+        }
+
+        plan.push(slot(1, 0, "_engine".to_string(), None, storage(false), wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE));
+        plan.push(slot(1, 1, "_engine_view".to_string(), None, storage(true), wgpu::ShaderStages::VERTEX_FRAGMENT));
+        for i in 0..self.storage_buffers.len() {
+            plan.push(slot(1, 2 + i, format!("_storage_{}", i), None, storage(false), wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE));
+        }
+        if !self.prints.is_empty() {
+            let binding = 2 + self.storage_buffers.len();
+            plan.push(slot(1, binding, "_prints".to_string(), None, storage(false), wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE));
+        }
+
+        for (i, model) in self.models.iter().enumerate() {
+            plan.push(slot(2, 1 + i * 2, format!("_model_{}_positions", i), Some(model), storage(true), all));
+            plan.push(slot(2, 2 + i * 2, format!("_model_{}_normals", i), Some(model), storage(true), all));
+        }
+        plan
+    }
+}
+
+/// Engine macros the preprocessor expands. Any other @name (e.g. @compute, @builtin) is a WGSL attribute.
+const MACRO_NAMES: &[&str] = &[
+    "import", "set_title", "set_size", "engine", "sound", "texture", "texture_index",
+    "video", "camera", "model", "osc", "str", "define", "if", "else", "endif", "dispatch",
+    "pass", "buffer", "storage", "canvas", "persist", "test", "assert", "print", "set_voices",
+    "music", "set_music_loop",
+];
+
+/// Directives written without an argument list
+const BARE_MACROS: &[&str] = &["engine", "else", "endif", "persist", "test"];
+
+/// Fields of GameEngineHost reachable through @engine.<member>
+const ENGINE_MEMBERS: &[&str] = &[
+    "buttons", "time", "delta_time", "screen_width", "screen_height", "mouse", "keys", "sampler", "state", "osc", "reset",
+    "persist", "save", "load", "quit", "music_time", "music_duration", "spectrum", "waveform", "audio_level",
+];
+
+/// One comma-separated argument of a macro call
+#[derive(Debug, Clone)]
+struct MacroArg {
+    span: Span,
+    /// Non-trivia tokens making up the argument
+    tokens: Vec<Token>,
+}
+
+/// A macro invocation in code position, e.g. `@sound("x.ogg").play()`
+#[derive(Debug, Clone)]
+struct MacroCall {
+    name: String,
+    args: Vec<MacroArg>,
+    /// Trailing `.member` for macros that take one (@engine, @sound, @model)
+    member: Option<String>,
+    /// Namespace from `@import("file.wgsl") as name`
+    alias: Option<String>,
+    span: Span,
+}
+
+#[derive(Debug)]
+enum Item {
+    /// Source text copied through unchanged (code, comments, whitespace, WGSL attributes)
+    Text(Span),
+    Macro(MacroCall),
+    /// @import resolved to an index into the parsed file list, or None if it was already imported
+    Import { name: String, file: Option<usize>, span: Span },
+}
+
+struct ParsedFile {
+    name: String,
+    text: String,
+    lines: LineIndex,
+    items: Vec<Item>,
+    /// Span of `struct GameState { ... }`, if this file declares it
+    game_state: Option<Span>,
+    /// Identifiers to replace when the file is imported under a namespace, sorted by position
+    renames: Vec<(Span, String)>,
+}
+
+/// Location of a byte offset in a source file
+fn location(file: &str, text: &str, offset: usize) -> SourceLocation {
+    let (line, column) = lexer::line_col(text, offset);
+    SourceLocation { file: file.to_string(), line, column }
+}
+
+/// An InvalidDirective error at a byte offset in a source file
+fn directive_error(file: &str, text: &str, offset: usize, message: String) -> Error {
+    Error::InvalidDirective { location: location(file, text, offset), message }
+}
+
+fn next_code_token(tokens: &[Token], mut i: usize) -> usize {
+    while i < tokens.len() && tokens[i].is_trivia() {
+        i += 1;
+    }
+    i
+}
+
+/// Parse `( arg, arg, ... )` starting at the opening paren. Returns the args and the index after `)`.
+fn parse_macro_args(text: &str, tokens: &[Token], open: usize) -> Result<(Vec<MacroArg>, usize), String> {
+    let mut args = Vec::new();
+    let mut current: Vec<Token> = Vec::new();
+    let mut depth = 0;
+    let mut i = open + 1;
+
+    let push_arg = |current: &mut Vec<Token>, args: &mut Vec<MacroArg>| {
+        if let (Some(first), Some(last)) = (current.first(), current.last()) {
+            args.push(MacroArg { span: Span::new(first.span.start, last.span.end), tokens: std::mem::take(current) });
+        }
+    };
+
+    while i < tokens.len() {
+        let tok = tokens[i];
+        match tok.kind {
+            TokenKind::Punct('(') | TokenKind::Punct('[') | TokenKind::Punct('{') => depth += 1,
+            TokenKind::Punct(')') if depth == 0 => {
+                push_arg(&mut current, &mut args);
+                return Ok((args, i + 1));
             }
+            TokenKind::Punct(')') | TokenKind::Punct(']') | TokenKind::Punct('}') => depth -= 1,
+            TokenKind::Punct(',') if depth == 0 => {
+                push_arg(&mut current, &mut args);
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        if !tok.is_trivia() {
+            current.push(tok);
+        }
+        i += 1;
+    }
 
-            for (full_match, filename) in captures {
-                if self.imported_files.contains(&filename) {
-                    source = source.replace(&full_match, &format!("// Already imported: {}", filename));
-                    continue;
+    Err(format!("unclosed '(' in macro arguments starting with {:?}", &text[tokens[open].span.start..]
+        .chars().take(20).collect::<String>()))
+}
+
+/// Split a tokenized file into pass-through text and macro calls
+fn parse_items(text: &str, tokens: &[Token]) -> Result<Vec<Item>, (usize, String)> {
+    let mut items = Vec::new();
+    let mut text_start = 0;
+    let mut i = 0;
+    // The rest of the @assert(), @print() or @sound(...).play(...) being read, last piece first:
+    // each is cut out once the code before it has been
+    let mut pieces: Vec<MacroCall> = Vec::new();
+
+    while i < tokens.len() {
+        if pieces.last().is_some_and(|piece| tokens[i].span.start >= piece.span.start) {
+            let piece = pieces.pop().expect("there's a last piece");
+            if text_start < piece.span.start {
+                items.push(Item::Text(Span::new(text_start, piece.span.start)));
+            }
+            text_start = piece.span.end;
+            i = tokens.partition_point(|t| t.span.start < piece.span.end);
+            items.push(Item::Macro(piece));
+            continue;
+        }
+        let is_macro = tokens[i].kind == TokenKind::At
+            && tokens.get(i + 1).is_some_and(|t| t.kind == TokenKind::Ident && MACRO_NAMES.contains(&t.span.text(text)));
+        if !is_macro {
+            i += 1;
+            continue;
+        }
+
+        let start = tokens[i].span.start;
+        let name = tokens[i + 1].span.text(text).to_string();
+        let mut end = tokens[i + 1].span.end;
+        let mut j = i + 2;
+        let mut args = Vec::new();
+
+        if !BARE_MACROS.contains(&name.as_str()) {
+            if !tokens.get(j).is_some_and(|t| t.is_punct('(')) {
+                return Err((start, format!("expected '(' after @{}", name)));
+            }
+            let (parsed, next) = parse_macro_args(text, tokens, j).map_err(|e| (start, e))?;
+            args = parsed;
+            end = tokens[next - 1].span.end;
+            j = next;
+        }
+
+        // Member access directly following the call: @engine.time, @sound("x").play(), @music("x").loop(),
+        // @model("x").positions, @canvas("x").storage
+        let mut member = None;
+        if matches!(name.as_str(), "engine" | "sound" | "music" | "model" | "canvas") && tokens.get(j).is_some_and(|t| t.is_punct('.')) {
+            match tokens.get(j + 1) {
+                Some(t) if t.kind == TokenKind::Ident => {
+                    member = Some(t.span.text(text).to_string());
+                    end = t.span.end;
+                    j += 2;
+                    // Methods: @sound(...).play(volume, pitch, pan), .loop(...) and .stop(), @music(...).play(fade),
+                    // .loop(fade), .pause() and .stop(fade), whose arguments stay as text like @print()'s values,
+                    // and @engine.reset(), @engine.save(), @engine.load(), @engine.quit()
+                    if matches!(name.as_str(), "sound" | "music") && member.as_deref() != Some("is_playing") {
+                        let method = member.as_deref().unwrap_or_default();
+                        if !tokens.get(j).is_some_and(|t| t.is_punct('(')) {
+                            return Err((start, format!("expected '(' after @{}(...).{}", name, method)));
+                        }
+                        let (method_args, next) = parse_macro_args(text, tokens, j).map_err(|e| (start, e))?;
+                        if (name == "sound" && method == "stop" || name == "music" && method == "pause") && !method_args.is_empty() {
+                            return Err((start, format!("@{}(...).{}() takes no arguments", name, method)));
+                        }
+                        if name == "sound" && method_args.len() > 3 {
+                            return Err((start, format!("@sound(...).{}() takes up to 3 arguments: volume, pitch and pan", method)));
+                        }
+                        if name == "music" && method_args.len() > 1 {
+                            return Err((start, format!("@music(...).{}() takes at most 1 argument: the seconds to fade over", method)));
+                        }
+                        end = tokens[next - 1].span.end;
+                        j = next;
+                        if let (Some(first), Some(last)) = (method_args.first(), method_args.last()) {
+                            let span = Span::new(last.span.end, end);
+                            pieces.push(MacroCall { name: format!("{}_end", name), args: method_args.clone(), member: None, alias: None, span });
+                            end = first.span.start;
+                            j = tokens.partition_point(|t| t.span.start < first.span.start);
+                        }
+                        // The method's arguments follow the file's
+                        args.extend(method_args);
+                    } else if name == "engine" && matches!(member.as_deref(), Some("reset" | "save" | "load" | "quit")) {
+                        if tokens.get(j).is_some_and(|t| t.is_punct('(')) && tokens.get(j + 1).is_some_and(|t| t.is_punct(')')) {
+                            end = tokens[j + 1].span.end;
+                            j += 2;
+                        } else {
+                            return Err((start, format!("expected '()' after @engine.{}", member.as_deref().unwrap_or(""))));
+                        }
+                    }
                 }
+                _ => return Err((start, format!("expected a member name after @{}.", name))),
+            }
+        } else if name == "engine" {
+            return Err((start, "expected '.<member>' after @engine".to_string()));
+        } else if name == "sound" && tokens.get(j).is_some_and(|t| t.is_punct('+')) && tokens.get(j + 1).is_some_and(|t| t.is_punct('+')) {
+            // Legacy @sound("x")++ plays it
+            member = Some("play".to_string());
+            end = tokens[j + 1].span.end;
+            j += 2;
+        }
 
-                self.imported_files.insert(filename.clone());
-                let imported_code = self.game_source.read_text(&filename)?;
-                let (processed, _) = self.preprocess_shader(&imported_code, false)?;
+        // @persist marks the struct declared right after it; its name is the only argument
+        if name == "persist" {
+            let keyword = next_code_token(tokens, j);
+            let struct_name = next_code_token(tokens, keyword + 1);
+            match (tokens.get(keyword), tokens.get(struct_name)) {
+                (Some(k), Some(n)) if k.span.text(text) == "struct" && n.kind == TokenKind::Ident => {
+                    args.push(MacroArg { span: n.span, tokens: vec![*n] });
+                }
+                _ => return Err((start, "@persist must come right before a struct declaration, e.g. @persist struct SaveData { ... }".to_string())),
+            }
+        }
 
-                source = source.replace(&full_match, &format!("// Imported from {}\n{}\n", filename, processed));
+        // @test marks the function declared right after it (past its attributes); its name is the only argument
+        if name == "test" {
+            let mut keyword = next_code_token(tokens, j);
+            while tokens.get(keyword).is_some_and(|t| !(t.is_punct('{') || t.is_punct(';') || (t.kind == TokenKind::Ident && t.span.text(text) == "fn"))) {
+                keyword = next_code_token(tokens, keyword + 1);
+            }
+            let fn_name = next_code_token(tokens, keyword + 1);
+            match (tokens.get(keyword), tokens.get(fn_name)) {
+                (Some(k), Some(n)) if k.span.text(text) == "fn" && n.kind == TokenKind::Ident => {
+                    args.push(MacroArg { span: n.span, tokens: vec![*n] });
+                }
+                _ => return Err((start, "@test must come right before a compute function, e.g. @test @compute @workgroup_size(1) fn test_score() { ... }".to_string())),
             }
         }
 
-        // Extract metadata
-        let mut metadata = Metadata {
-            title: "WGSL Game".to_string(),
-            width: 800,
-            height: 600,
-            textures: Vec::new(),
-            sounds: Vec::new(),
-            models: Vec::new(),
-            state_size: 0, // set to 0 so no buffer space is reserved unless GameState is found
-            osc_params: Vec::new(),
-            videos: Vec::new(),
-            cameras: Vec::new(),
-        };
+        // @assert(condition, "message") and @print("format", values...) only replace their own
+        // parts: the code they're given stays as text so the macros in it are expanded like any others
+        if name == "assert" || name == "print" {
+            if !pieces.is_empty() {
+                return Err((start, format!("@{} can't be nested", name)));
+            }
+            let piece = |name: &str, span: Span, args: Vec<MacroArg>| MacroCall { name: name.to_string(), args, member: None, alias: None, span };
+            let code = if name == "assert" {
+                let [condition, _message] = args.as_slice() else {
+                    return Err((start, "@assert expects a condition and a message, e.g. @assert(score == 10.0, \"eating food scores 10\")".to_string()));
+                };
+                pieces.push(piece("assert_end", Span::new(condition.span.end, end), Vec::new()));
+                Some(condition.span)
+            } else {
+                // The commas between values, then the closing paren
+                let values = args.get(1..).unwrap_or_default();
+                if let Some(last) = values.last() {
+                    pieces.push(piece("print_end", Span::new(last.span.end, end), args.clone()));
+                }
+                for pair in values.windows(2).rev() {
+                    pieces.push(piece("print_next", Span::new(pair[0].span.end, pair[1].span.start), Vec::new()));
+                }
+                values.first().map(|first| first.span)
+            };
+            if let Some(code) = code {
+                end = code.start;
+                j = tokens.partition_point(|t| t.span.start < code.start);
+            }
+        }
+
+        // Namespaced import: @import("lib/draw2d.wgsl") as draw
+        let mut alias = None;
+        let next = next_code_token(tokens, j);
+        if name == "import" && tokens.get(next).is_some_and(|t| t.kind == TokenKind::Ident && t.span.text(text) == "as") {
+            let alias_token = next_code_token(tokens, next + 1);
+            match tokens.get(alias_token) {
+                Some(t) if t.kind == TokenKind::Ident => {
+                    alias = Some(t.span.text(text).to_string());
+                    end = t.span.end;
+                    j = alias_token + 1;
+                }
+                _ => return Err((start, "expected a namespace name after 'as'".to_string())),
+            }
+        }
+
+        if text_start < start {
+            items.push(Item::Text(Span::new(text_start, start)));
+        }
+        items.push(Item::Macro(MacroCall { name, args, member, alias, span: Span::new(start, end) }));
+        text_start = end;
+        i = j;
+    }
+
+    if text_start < text.len() {
+        items.push(Item::Text(Span::new(text_start, text.len())));
+    }
+    Ok(items)
+}
+
+/// Find every `struct GameState { ... }` at token level, matching braces.
+/// There can be several when @if/@else select between variants.
+fn find_game_states(text: &str, tokens: &[Token]) -> Vec<Span> {
+    let mut found = Vec::new();
+    for i in 0..tokens.len() {
+        if tokens[i].kind != TokenKind::Ident || tokens[i].span.text(text) != "struct" {
+            continue;
+        }
+        let name = next_code_token(tokens, i + 1);
+        if !tokens.get(name).is_some_and(|t| t.kind == TokenKind::Ident && t.span.text(text) == "GameState") {
+            continue;
+        }
+        let open = next_code_token(tokens, name + 1);
+        if !tokens.get(open).is_some_and(|t| t.is_punct('{')) {
+            continue;
+        }
+        let mut depth = 0;
+        for tok in &tokens[open..] {
+            if tok.is_punct('{') {
+                depth += 1;
+            } else if tok.is_punct('}') {
+                depth -= 1;
+                if depth == 0 {
+                    found.push(Span::new(tokens[i].span.start, tok.span.end));
+                    break;
+                }
+            }
+        }
+    }
+    found
+}
 
-        // Extract @set_title
-        if let Some(cap) = Regex::new(r#"@set_title\("([^"]+)"\)"#)?.captures(&source) {
-            metadata.title = cap[1].to_string();
+/// Names declared at module scope (fn, struct, const, var, override, alias), except GameState
+fn top_level_names(text: &str, tokens: &[Token]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < tokens.len() {
+        let tok = tokens[i];
+        match tok.kind {
+            TokenKind::Punct('{') => depth += 1,
+            TokenKind::Punct('}') => depth -= 1,
+            TokenKind::Ident if depth == 0 && matches!(tok.span.text(text), "fn" | "struct" | "const" | "var" | "override" | "alias") => {
+                let mut next = next_code_token(tokens, i + 1);
+                // var<storage, read> name
+                if tokens.get(next).is_some_and(|t| t.is_punct('<')) {
+                    while next < tokens.len() && !tokens[next].is_punct('>') {
+                        next += 1;
+                    }
+                    next = next_code_token(tokens, next + 1);
+                }
+                if let Some(name) = tokens.get(next).filter(|t| t.kind == TokenKind::Ident) {
+                    if name.span.text(text) != "GameState" {
+                        names.insert(name.span.text(text).to_string());
+                    }
+                    i = next;
+                }
+            }
+            _ => {}
         }
+        i += 1;
+    }
+    names
+}
 
-        // Extract @set_size
-        if let Some(cap) = Regex::new(r#"@set_size\((\d+),\s*(\d+)\)"#)?.captures(&source) {
-            metadata.width = cap[1].parse()?;
-            metadata.height = cap[2].parse()?;
+/// Every use of a top-level name in a file imported `as prefix`, paired with its prefixed replacement.
+/// Member accesses (`.name`), attributes (`@name`) and struct field declarations keep their names.
+fn namespace_renames(text: &str, tokens: &[Token], prefix: &str) -> Vec<(Span, String)> {
+    let names = top_level_names(text, tokens);
+    let code: Vec<Token> = tokens.iter().copied().filter(|t| !t.is_trivia()).collect();
+    let mut renames = Vec::new();
+    let mut depth = 0;
+    // Brace depth of the struct body we are in, if any
+    let mut struct_body = None;
+    for (k, tok) in code.iter().enumerate() {
+        match tok.kind {
+            TokenKind::Punct('{') => {
+                depth += 1;
+                if k >= 2 && code[k - 2].span.text(text) == "struct" {
+                    struct_body = Some(depth);
+                }
+            }
+            TokenKind::Punct('}') => {
+                if struct_body == Some(depth) {
+                    struct_body = None;
+                }
+                depth -= 1;
+            }
+            TokenKind::Ident if names.contains(tok.span.text(text)) => {
+                let prev = k.checked_sub(1).map(|p| code[p]);
+                let is_member = prev.is_some_and(|p| p.is_punct('.') || p.kind == TokenKind::At);
+                let is_field = struct_body == Some(depth) && code.get(k + 1).is_some_and(|n| n.is_punct(':'));
+                if !is_member && !is_field {
+                    renames.push((tok.span, format!("{}_{}", prefix, tok.span.text(text))));
+                }
+            }
+            _ => {}
         }
+    }
+    renames
+}
 
-        // Find all @sound() references
-        let sound_re = Regex::new(r#"@sound\("([^"]+)"\)(?:\.(?:play|stop)\(\))?"#)?;
-        for cap in sound_re.captures_iter(&source) {
-            let sound_file = cap[1].to_string();
-            if !metadata.sounds.contains(&sound_file) {
-                metadata.sounds.push(sound_file);
+/// Resolve an @import path against the importing file's directory.
+/// A leading `/` starts from the game root; `..` may not leave the game root (None).
+/// `std:` paths name the library instead; the `.wgsl` extension is optional there, and relative
+/// imports inside a library module stay in the library.
+fn resolve_import(importer: &str, path: &str) -> Option<String> {
+    if let Some(module) = path.strip_prefix(stdlib::PREFIX) {
+        let module = resolve_import("", module)?;
+        let extension = if std::path::Path::new(&module).extension().is_none() { ".wgsl" } else { "" };
+        return Some(format!("{}{}{}", stdlib::PREFIX, module, extension));
+    }
+    if let Some(importer) = importer.strip_prefix(stdlib::PREFIX) {
+        if !path.starts_with('/') {
+            return Some(format!("{}{}", stdlib::PREFIX, resolve_import(importer, path)?));
+        }
+    }
+    let mut parts: Vec<&str> = match path.strip_prefix('/') {
+        Some(_) => Vec::new(),
+        None => importer.split('/').collect(),
+    };
+    // Drop the importer's own file name
+    parts.pop();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
             }
+            part => parts.push(part),
         }
+    }
+    Some(parts.join("/"))
+}
+
+/// An open @if block while loading a file
+struct Conditional {
+    /// Whether the enclosing code is active
+    parent: bool,
+    /// Whether the @if branch was taken
+    taken: bool,
+    seen_else: bool,
+    offset: usize,
+}
+
+impl Conditional {
+    /// Whether code at this point is compiled
+    fn active(&self) -> bool {
+        self.parent && self.taken != self.seen_else
+    }
+}
 
-        // Find all @texture() references
-        let texture_re = Regex::new(r#"@texture\("([^"]+)"\)"#)?;
-        for cap in texture_re.captures_iter(&source) {
-            let texture_file = cap[1].to_string();
-            if !metadata.textures.contains(&texture_file) {
-                metadata.textures.push(texture_file);
+/// Evaluate an @if condition. Names are looked up in `defines` (undefined names are "0");
+/// a value is true unless it is "0", "false" or empty.
+///
+///   @if(DEBUG)  @if(!HAS_CAMERA)  @if(LEVEL == 2 && !WGSLENG_NATIVE)  @if(MODE != "easy" || (A && B))
+fn eval_condition(text: &str, tokens: &[Token], defines: &HashMap<String, String>) -> Result<bool, String> {
+    struct Parser<'a> {
+        text: &'a str,
+        tokens: &'a [Token],
+        defines: &'a HashMap<String, String>,
+        pos: usize,
+    }
+
+    fn truthy(value: &str) -> bool {
+        !matches!(value, "" | "0" | "false")
+    }
+
+    impl Parser<'_> {
+        fn punct_at(&self, offset: usize, c: char) -> bool {
+            self.tokens.get(self.pos + offset).is_some_and(|t| t.is_punct(c))
+        }
+
+        /// Consume an operator made of single-character punctuation tokens
+        fn eat(&mut self, op: &str) -> bool {
+            if !op.chars().enumerate().all(|(k, c)| self.punct_at(k, c)) {
+                return false;
             }
+            // `!` followed by `=` is the != operator, not a negation
+            if op == "!" && self.punct_at(1, '=') {
+                return false;
+            }
+            self.pos += op.len();
+            true
         }
 
-        // Find all @texture_index() references (also load these textures)
-        let texture_index_re = Regex::new(r#"@texture_index\("([^"]+)"\)"#)?;
-        for cap in texture_index_re.captures_iter(&source) {
-            let texture_file = cap[1].to_string();
-            if !metadata.textures.contains(&texture_file) {
-                metadata.textures.push(texture_file);
+        fn or(&mut self) -> Result<bool, String> {
+            let mut value = self.and()?;
+            while self.eat("||") {
+                value |= self.and()?;
             }
+            Ok(value)
         }
 
-        // Find all @video() references
-        let video_re = Regex::new(r#"@video\("([^"]+)"\)"#)?;
-        for cap in video_re.captures_iter(&source) {
-            let f = cap[1].to_string();
-            if !metadata.videos.contains(&f) {
-                metadata.videos.push(f);
+        fn and(&mut self) -> Result<bool, String> {
+            let mut value = self.unary()?;
+            while self.eat("&&") {
+                value &= self.unary()?;
             }
+            Ok(value)
         }
 
-        // Find all @camera() references
-        let camera_re = Regex::new(r#"@camera\((\d+)\)"#)?;
-        for cap in camera_re.captures_iter(&source) {
-            let idx: u32 = cap[1].parse()?;
-            if !metadata.cameras.contains(&idx) {
-                metadata.cameras.push(idx);
+        fn unary(&mut self) -> Result<bool, String> {
+            if self.eat("!") {
+                return Ok(!self.unary()?);
             }
+            if self.eat("(") {
+                let value = self.or()?;
+                if !self.eat(")") {
+                    return Err("expected ')' in @if condition".to_string());
+                }
+                return Ok(value);
+            }
+            let left = self.operand()?;
+            if self.eat("==") {
+                return Ok(left == self.operand()?);
+            }
+            if self.eat("!=") {
+                return Ok(left != self.operand()?);
+            }
+            Ok(truthy(&left))
         }
-        metadata.cameras.sort();
 
-        // Find all @model() references
-        let model_re = Regex::new(r#"@model\("([^"]+)"\)"#)?;
-        for cap in model_re.captures_iter(&source) {
-            let model_file = cap[1].to_string();
-            if !metadata.models.contains(&model_file) {
-                metadata.models.push(model_file);
+        fn operand(&mut self) -> Result<String, String> {
+            let tok = self.tokens.get(self.pos).ok_or("unexpected end of @if condition")?;
+            self.pos += 1;
+            let text = tok.span.text(self.text);
+            match tok.kind {
+                TokenKind::Ident => Ok(self.defines.get(text).cloned().unwrap_or_else(|| "0".to_string())),
+                TokenKind::Number => Ok(text.to_string()),
+                TokenKind::Str => Ok(lexer::unescape_str(text)),
+                _ => Err(format!("unexpected '{}' in @if condition", text)),
             }
         }
+    }
 
-        // Find all @osc() references and assign sequential slot indices
-        let osc_ref_re = Regex::new(r#"@osc\("([^"]+)"\)"#)?;
-        for cap in osc_ref_re.captures_iter(&source) {
-            let param_name = cap[1].to_string();
-            if !metadata.osc_params.contains(&param_name) {
-                metadata.osc_params.push(param_name);
+    let mut parser = Parser { text, tokens, defines, pos: 0 };
+    let value = parser.or()?;
+    match tokens.get(parser.pos) {
+        Some(tok) => Err(format!("unexpected '{}' in @if condition", tok.span.text(text))),
+        None => Ok(value),
+    }
+}
+
+/// Remove `skip` from `span`, returning the (up to two) remaining pieces
+fn subtract_span(span: Span, skip: Option<Span>) -> Vec<Span> {
+    match skip {
+        Some(skip) if skip.start < span.end && skip.end > span.start => {
+            let mut pieces = Vec::new();
+            if span.start < skip.start {
+                pieces.push(Span::new(span.start, skip.start));
+            }
+            if skip.end < span.end {
+                pieces.push(Span::new(skip.end, span.end));
             }
+            pieces
+        }
+        _ => vec![span],
+    }
+}
+
+/// Preprocessor settings that come from the command line rather than the game
+#[derive(Debug, Clone, Default)]
+pub struct PreprocessorOptions {
+    /// Extra @if symbols (--define)
+    pub defines: Vec<(String, String)>,
+    /// Directories searched for `std:` files (--lib-path)
+    pub lib_paths: Vec<std::path::PathBuf>,
+}
+
+pub struct PreprocessorState {
+    pub game_source: GameSource,
+    /// Name of the top-level shader file, used in error locations
+    pub entry_file: String,
+    /// Symbols visible to @if before the game's own @define()s (predefined and --define)
+    pub defines: HashMap<String, String>,
+    /// Directories searched for `std:` files before the embedded library (--lib-path)
+    pub lib_paths: Vec<std::path::PathBuf>,
+    /// Files loaded so far in this run, with the namespace they were imported under
+    imported_files: HashMap<String, Option<String>>,
+    /// Files currently being loaded, outermost first, for reporting import cycles
+    import_stack: Vec<String>,
+    /// `defines` plus the game's @define()s during a preprocess_shader run
+    active_defines: HashMap<String, String>,
+    /// Assets found by the last run, so read_file errors can say where they were referenced
+    asset_references: Vec<AssetReference>,
+}
+
+impl PreprocessorState {
+    pub fn new(game_source: GameSource) -> Self {
+        let flag = |on: bool| if on { "1" } else { "0" }.to_string();
+        let defines = HashMap::from([
+            ("WGSLENG_NATIVE".to_string(), "1".to_string()),
+            ("HAS_CAMERA".to_string(), flag(cfg!(feature = "camera"))),
+            ("DEBUG".to_string(), flag(cfg!(debug_assertions))),
+        ]);
+        Self {
+            game_source,
+            entry_file: "main.wgsl".to_string(),
+            defines,
+            lib_paths: Vec::new(),
+            imported_files: HashMap::new(),
+            import_stack: Vec::new(),
+            active_defines: HashMap::new(),
+            asset_references: Vec::new(),
         }
+    }
 
-        // Remove @set_* directives
-        source = Regex::new(r#"@set_title\([^)]+\)[^\n]*"#)?.replace_all(&source, "").to_string();
-        source = Regex::new(r#"@set_size\([^)]+\)[^\n]*"#)?.replace_all(&source, "").to_string();
+    /// Set the file name reported for errors in the top-level source
+    pub fn with_entry_file(mut self, entry_file: &str) -> Self {
+        self.entry_file = entry_file.to_string();
+        self
+    }
 
-        // Find GameState struct
-        let game_state_re = Regex::new(r"struct GameState\s*\{[^}]+\}")?;
-        let game_state_struct = game_state_re.find(&source).map(|m| m.as_str().to_string());
+    /// Define (or override) a symbol for @if, like `--define NAME=VALUE`
+    pub fn with_define(mut self, name: &str, value: &str) -> Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
 
-        // Calculate GameState size
-        if let Some(ref gs) = game_state_struct {
-            // Match field types, including arrays with angle brackets
-            let field_re = Regex::new(r":\s*(?:array<[^>]+>|[^,;\n]+)")?;
-            let array_re = Regex::new(r"array<([^,>]+),\s*(\d+)>")?;
-            let mut size = 0;
-            let mut alignment = 4; // Track the largest member alignment
+    /// Apply command-line defines and library search directories
+    pub fn with_options(self, options: &PreprocessorOptions) -> Self {
+        let state = options.defines.iter().fold(self, |p, (name, value)| p.with_define(name, value));
+        options.lib_paths.iter().fold(state, |p, path| p.with_lib_path(path))
+    }
 
-            for cap in field_re.captures_iter(gs) {
-                let field = cap.get(0).unwrap().as_str();
+    /// Add a directory to search for `std:` files, ahead of the embedded library
+    pub fn with_lib_path(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.lib_paths.push(path.into());
+        self
+    }
 
-                // Check if it's an array
-                if let Some(array_cap) = array_re.captures(field) {
-                    let element_type = &array_cap[1];
-                    let count: usize = array_cap[2].parse().unwrap_or(1);
+    /// Read a file referenced by the game: `std:` paths come from the library, everything else from the GameSource.
+    /// Errors for assets of the last preprocess_shader run point at the macro that first referenced them.
+    pub fn read_file(&mut self, file_path: &str) -> Result<Vec<u8>, Error> {
+        let result = match file_path.strip_prefix(stdlib::PREFIX) {
+            Some(name) => stdlib::read(&self.lib_paths, name),
+            None => self.game_source.read_file(file_path),
+        };
+        result.map_err(|e| e.referenced_from(self.asset_references.iter().find(|r| r.path == file_path).map(|r| &r.location)))
+    }
+
+    /// Read a file as UTF-8 text, resolved like read_file
+    pub fn read_text(&mut self, file_path: &str) -> Result<String, Error> {
+        let bytes = self.read_file(file_path)?;
+        String::from_utf8(bytes).map_err(|_| Error::InvalidUtf8 { path: file_path.to_string(), referenced_from: None })
+    }
 
-                    let (element_size, element_align) = if element_type.contains("vec4f") {
-                        (16, 16)
-                    } else if element_type.contains("vec3f") {
-                        (16, 16) // vec3 aligns to 16 in arrays
-                    } else if element_type.contains("vec2f") {
-                        (8, 8)
-                    } else {
-                        (4, 4) // u32, i32, f32
+    /// Tokenize a file and recursively load its imports. Returns the index of the file in `files`.
+    /// @define/@if/@else/@endif are resolved here, in order, so inactive code never loads its imports.
+    fn load_file(&mut self, files: &mut Vec<ParsedFile>, name: &str, text: String, alias: Option<&str>) -> Result<usize, Error> {
+        let tokens = lexer::tokenize(&text)
+            .map_err(|e| Error::Syntax { location: location(name, &text, e.offset), message: e.message })?;
+        let parsed = parse_items(&text, &tokens)
+            .map_err(|(offset, message)| Error::Syntax { location: location(name, &text, offset), message })?;
+        self.import_stack.push(name.to_string());
+
+        let mut items = Vec::with_capacity(parsed.len());
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        for item in parsed {
+            let active = conditionals.last().map_or(true, Conditional::active);
+            match item {
+                Item::Macro(call) if call.name == "if" => {
+                    let condition = call.args.first()
+                        .ok_or_else(|| directive_error(name, &text, call.span.start, "@if is missing a condition".to_string()))?;
+                    let taken = eval_condition(&text, &condition.tokens, &self.active_defines)
+                        .map_err(|e| directive_error(name, &text, condition.span.start, e))?;
+                    conditionals.push(Conditional { parent: active, taken, seen_else: false, offset: call.span.start });
+                }
+                Item::Macro(call) if call.name == "else" => match conditionals.last_mut() {
+                    Some(c) if !c.seen_else => c.seen_else = true,
+                    Some(_) => return Err(directive_error(name, &text, call.span.start, "duplicate @else".to_string())),
+                    None => return Err(directive_error(name, &text, call.span.start, "@else without @if".to_string())),
+                },
+                Item::Macro(call) if call.name == "endif" => {
+                    if conditionals.pop().is_none() {
+                        return Err(directive_error(name, &text, call.span.start, "@endif without @if".to_string()));
+                    }
+                }
+                _ if !active => {}
+                Item::Macro(call) if call.name == "define" => {
+                    let symbol = match call.args.first() {
+                        Some(arg) if arg.tokens.len() == 1 && arg.tokens[0].kind == TokenKind::Ident => arg.span.text(&text).to_string(),
+                        _ => return Err(directive_error(name, &text, call.span.start, "@define expects a name, e.g. @define(DEBUG, 1)".to_string())),
                     };
+                    let value = match call.args.get(1) {
+                        Some(arg) if arg.tokens.len() == 1 && arg.tokens[0].kind == TokenKind::Str => lexer::unescape_str(arg.span.text(&text)),
+                        Some(arg) => arg.span.text(&text).to_string(),
+                        None => "1".to_string(),
+                    };
+                    self.active_defines.insert(symbol, value);
+                }
+                Item::Macro(call) if call.name == "import" => {
+                    let at = location(name, &text, call.span.start);
+                    let path = str_arg(&call, 0, name, &text)?;
+                    let filename = resolve_import(name, &path)
+                        .ok_or_else(|| Error::InvalidPath { path, referenced_from: Some(at.clone()) })?;
+                    if let Some(start) = self.import_stack.iter().position(|f| *f == filename) {
+                        let chain = [&self.import_stack[start..], &[filename]].concat();
+                        return Err(Error::ImportCycle { location: at, chain });
+                    }
+                    if let Some(previous) = self.imported_files.get(&filename) {
+                        if *previous != call.alias {
+                            let describe = |alias: &Option<String>| alias.as_ref()
+                                .map_or("without a namespace".to_string(), |a| format!("as {}", a));
+                            let message = format!("{} is imported {} here but {} elsewhere", filename, describe(&call.alias), describe(previous));
+                            return Err(Error::InvalidDirective { location: at, message });
+                        }
+                        items.push(Item::Import { name: filename, file: None, span: call.span });
+                        continue;
+                    }
+                    self.imported_files.insert(filename.clone(), call.alias.clone());
+                    let imported_code = self.read_text(&filename).map_err(|e| e.referenced_from(Some(&at)))?;
+                    let index = self.load_file(files, &filename, imported_code, call.alias.as_deref())?;
+                    items.push(Item::Import { name: filename, file: Some(index), span: call.span });
+                }
+                other => items.push(other),
+            }
+        }
+        if let Some(open) = conditionals.last() {
+            return Err(directive_error(name, &text, open.offset, "@if without matching @endif".to_string()));
+        }
 
-                    alignment = alignment.max(element_align);
-                    size += element_size * count;
-                } else {
-                    // Regular field
-                    if field.contains("vec4f") {
-                        size += 16;
-                        alignment = alignment.max(16);
-                    } else if field.contains("vec3f") {
-                        size += 12;
-                        alignment = alignment.max(16);
-                    } else if field.contains("vec2f") {
-                        size += 8;
-                        alignment = alignment.max(8);
-                    } else if field.contains("u32") || field.contains("i32") || field.contains("f32") {
-                        size += 4;
-                        alignment = alignment.max(4);
+        // The first GameState that survived conditional compilation
+        let game_state = find_game_states(&text, &tokens).into_iter().find(|gs| {
+            items.iter().any(|item| matches!(item, Item::Text(span) if span.start <= gs.start && gs.start < span.end))
+        });
+        let renames = alias.map(|prefix| namespace_renames(&text, &tokens, prefix)).unwrap_or_default();
+        let lines = LineIndex::new(&text);
+        self.import_stack.pop();
+        files.push(ParsedFile { name: name.to_string(), text, lines, items, game_state, renames });
+        Ok(files.len() - 1)
+    }
+
+    pub fn preprocess_shader(&mut self, source: &str, is_top_level: bool) -> Result<(String, Metadata), Error> {
+        // Parse the entry file and everything it imports (recursive, like C #include)
+        self.imported_files.clear();
+        self.import_stack.clear();
+        self.active_defines = self.defines.clone();
+        let mut files = Vec::new();
+        let entry_file = self.entry_file.clone();
+        self.imported_files.insert(entry_file.clone(), None);
+        let root = self.load_file(&mut files, &entry_file, source.to_string(), None)?;
+
+        // Extract metadata
+        let mut metadata = Metadata {
+            title: "WGSL Game".to_string(),
+            width: 800,
+            height: 600,
+            textures: Vec::new(),
+            sounds: Vec::new(),
+            sound_voices: Vec::new(),
+            music: Vec::new(),
+            music_loops: Vec::new(),
+            models: Vec::new(),
+            state_size: 0, // set to 0 so no buffer space is reserved unless GameState is found
+            state_fields: Vec::new(),
+            engine_fields: Vec::new(),
+            engine_size: 0,
+            osc_params: Vec::new(),
+            videos: Vec::new(),
+            cameras: Vec::new(),
+            dispatches: Vec::new(),
+            has_init: false,
+            uses_reset: false,
+            uses_quit: false,
+            uses_audio_analysis: false,
+            persist_struct: None,
+            persist_size: 0,
+            persist_fields: Vec::new(),
+            tests: Vec::new(),
+            asserts: Vec::new(),
+            prints: Vec::new(),
+            passes: Vec::new(),
+            storage_buffers: Vec::new(),
+            canvases: Vec::new(),
+            asset_references: Vec::new(),
+            source_map: SourceMap::default(),
+        };
+        let mut title_set = false;
+        let mut size_set = false;
+        let mut texture_indices = Vec::new();
+        let mut voice_limits: Vec<(String, usize)> = Vec::new();
+        let mut music_loops: Vec<(String, MusicLoop)> = Vec::new();
+        let mut game_state: Option<(usize, Span)> = None;
+
+        // Walk the files in include order so slot indices follow first appearance
+        let mut order = Vec::new();
+        flatten_order(&files, root, &mut order);
+        for &(f, i) in &order {
+            let file = &files[f];
+            let call = match &file.items[i] {
+                Item::Text(span) => {
+                    if let Some(gs) = file.game_state {
+                        if game_state.is_none() && gs.start >= span.start && gs.start < span.end {
+                            game_state = Some((f, gs));
+                        }
                     }
+                    continue;
+                }
+                Item::Import { .. } => continue,
+                Item::Macro(call) => call,
+            };
+            let (name, text) = (file.name.as_str(), file.text.as_str());
+
+            let asset_kind = match call.name.as_str() {
+                "texture" | "texture_index" => Some("texture"),
+                "sound" | "set_voices" => Some("sound"),
+                "music" | "set_music_loop" => Some("music"),
+                "model" => Some("model"),
+                "video" => Some("video"),
+                _ => None,
+            };
+            if let Some(kind) = asset_kind {
+                let path = str_arg(call, 0, name, text)?;
+                if !metadata.asset_references.iter().any(|r| r.path == path) {
+                    let (line, column) = file.lines.position(call.span.start);
+                    let location = SourceLocation { file: name.to_string(), line: line + 1, column: column + 1 };
+                    metadata.asset_references.push(AssetReference { kind, path, location });
                 }
             }
 
-            // Round up to struct's alignment (largest member)
-            metadata.state_size = ((size + alignment - 1) / alignment) * alignment;
+            match call.name.as_str() {
+                "set_title" => {
+                    let title = str_arg(call, 0, name, text)?;
+                    if !title_set {
+                        metadata.title = title;
+                        title_set = true;
+                    }
+                }
+                "set_size" => {
+                    let width = int_arg(call, 0, name, text)?;
+                    let height = int_arg(call, 1, name, text)?;
+                    if !size_set {
+                        metadata.width = width;
+                        metadata.height = height;
+                        size_set = true;
+                    }
+                }
+                "sound" => push_unique(&mut metadata.sounds, str_arg(call, 0, name, text)?),
+                "set_voices" => {
+                    let sound = str_arg(call, 0, name, text)?;
+                    let voices = int_arg(call, 1, name, text)? as usize;
+                    if voices == 0 {
+                        return Err(directive_error(name, text, call.span.start, "@set_voices needs at least 1 voice".to_string()));
+                    }
+                    if voice_limits.iter().any(|(s, v)| *s == sound && *v != voices) {
+                        return Err(directive_error(name, text, call.span.start, format!("@set_voices(\"{}\") is set twice", sound)));
+                    }
+                    voice_limits.push((sound.clone(), voices));
+                    push_unique(&mut metadata.sounds, sound);
+                }
+                "music" => push_unique(&mut metadata.music, str_arg(call, 0, name, text)?),
+                "set_music_loop" => {
+                    let track = str_arg(call, 0, name, text)?;
+                    let start = float_arg(call, 1, name, text)?;
+                    let end = if call.args.len() > 2 { Some(float_arg(call, 2, name, text)?) } else { None };
+                    if end.is_some_and(|end| end <= start) {
+                        return Err(directive_error(name, text, call.span.start, "@set_music_loop's end must come after its start".to_string()));
+                    }
+                    let music_loop = MusicLoop { start, end };
+                    if music_loops.iter().any(|(t, l)| *t == track && *l != music_loop) {
+                        return Err(directive_error(name, text, call.span.start, format!("@set_music_loop(\"{}\") is set twice", track)));
+                    }
+                    music_loops.push((track.clone(), music_loop));
+                    push_unique(&mut metadata.music, track);
+                }
+                "texture" => push_unique(&mut metadata.textures, str_arg(call, 0, name, text)?),
+                // @texture_index() also loads the texture, after all @texture() references
+                "texture_index" => push_unique(&mut texture_indices, str_arg(call, 0, name, text)?),
+                "video" => push_unique(&mut metadata.videos, str_arg(call, 0, name, text)?),
+                "camera" => push_unique(&mut metadata.cameras, int_arg(call, 0, name, text)?),
+                "model" => push_unique(&mut metadata.models, str_arg(call, 0, name, text)?),
+                // Assign sequential osc slot indices
+                "osc" => push_unique(&mut metadata.osc_params, str_arg(call, 0, name, text)?),
+                "str" => {
+                    str_arg(call, 0, name, text)?;
+                }
+                "dispatch" => {
+                    let entry_point = entry_point_arg(file, call, 0)
+                        .ok_or_else(|| directive_error(name, text, call.span.start, "@dispatch expects a compute entry point name, e.g. @dispatch(update_particles, 64)".to_string()))?;
+                    if entry_point == "init" {
+                        return Err(directive_error(name, text, call.span.start, "init runs on load and on reset, not every frame; it can't be @dispatch()ed".to_string()));
+                    }
+                    let counts: Vec<&[Token]> = call.args[1..].iter().map(|arg| arg.tokens.as_slice()).collect();
+                    let workgroups = dispatch::parse_workgroups(text, &counts, &self.active_defines)
+                        .map_err(|e| directive_error(name, text, call.args.get(1).map_or(call.span.start, |arg| arg.span.start), e))?;
+                    metadata.dispatches.push(Dispatch { entry_point, workgroups, location: Some(location(name, text, call.span.start)) });
+                }
+                "pass" => {
+                    let pass_name = str_arg(call, 0, name, text)?;
+                    let entry_point = entry_point_arg(file, call, 1)
+                        .filter(|_| call.args.len() == 2)
+                        .ok_or_else(|| directive_error(name, text, call.span.start, "@pass expects a name and a fragment entry point, e.g. @pass(\"bufferA\", fs_buffer_a)".to_string()))?;
+                    if metadata.passes.iter().any(|p| p.name == pass_name) {
+                        return Err(directive_error(name, text, call.span.start, format!("@pass(\"{}\") is declared twice", pass_name)));
+                    }
+                    metadata.passes.push(Pass { name: pass_name, entry_point, location: location(name, text, call.span.start) });
+                }
+                // @storage("name", type) declares a buffer; @storage("name") uses it
+                "storage" if call.args.len() > 1 => {
+                    let buffer_name = str_arg(call, 0, name, text)?;
+                    if metadata.storage_buffers.iter().any(|b| b.name == buffer_name) {
+                        return Err(directive_error(name, text, call.span.start, format!("@storage(\"{}\") is declared twice", buffer_name)));
+                    }
+                    // The type's own commas split it into several arguments
+                    let ty = renamed_text(file, Span::new(call.args[1].span.start, call.args[call.args.len() - 1].span.end));
+                    let location = location(name, text, call.span.start);
+                    metadata.storage_buffers.push(StorageBuffer { name: buffer_name, ty, size: 0, element: String::new(), location });
+                }
+                "storage" => {
+                    str_arg(call, 0, name, text)?;
+                }
+                // @canvas("name", width, height[, format]) declares a canvas; @canvas("name") uses it
+                "canvas" if call.args.len() > 1 => {
+                    let canvas_name = str_arg(call, 0, name, text)?;
+                    let width = int_arg(call, 1, name, text)?;
+                    let height = int_arg(call, 2, name, text)?;
+                    let format = match call.args.get(3) {
+                        Some(arg) => arg.span.text(text).to_string(),
+                        None => "rgba8unorm".to_string(),
+                    };
+                    let formats: Vec<&str> = CANVAS_FORMATS.iter().map(|(f, _)| *f).collect();
+                    if call.args.len() > 4 || !formats.contains(&format.as_str()) {
+                        return Err(directive_error(name, text, call.span.start, format!("@canvas format must be one of {}", formats.join(", "))));
+                    }
+                    if width == 0 || height == 0 {
+                        return Err(directive_error(name, text, call.span.start, "@canvas needs a width and height above 0".to_string()));
+                    }
+                    if metadata.canvases.iter().any(|c| c.name == canvas_name) {
+                        return Err(directive_error(name, text, call.span.start, format!("@canvas(\"{}\") is declared twice", canvas_name)));
+                    }
+                    let location = location(name, text, call.span.start);
+                    metadata.canvases.push(Canvas { name: canvas_name, width, height, format, location });
+                }
+                "canvas" => {
+                    str_arg(call, 0, name, text)?;
+                }
+                "engine" => {
+                    let member = call.member.as_deref().unwrap_or_default();
+                    if !ENGINE_MEMBERS.contains(&member) {
+                        return Err(directive_error(name, text, call.span.start, format!("unknown engine member '@engine.{}'", member)));
+                    }
+                    metadata.uses_reset |= member == "reset";
+                    metadata.uses_quit |= member == "quit";
+                    metadata.uses_audio_analysis |= matches!(member, "spectrum" | "waveform" | "audio_level");
+                }
+                "persist" => {
+                    if metadata.persist_struct.is_some() {
+                        return Err(directive_error(name, text, call.span.start, "only one struct can be @persist".to_string()));
+                    }
+                    metadata.persist_struct = Some(renamed_text(file, call.args[0].span));
+                }
+                "test" => {
+                    let location = location(name, text, call.span.start);
+                    metadata.tests.push(TestEntry { entry_point: renamed_text(file, call.args[0].span), location });
+                }
+                "assert" => {
+                    let message = str_arg(call, 1, name, text)?;
+                    let condition = call.args[0].span.text(text).to_string();
+                    metadata.asserts.push(Assertion { condition, message, location: location(name, text, call.span.start) });
+                }
+                "print" => {
+                    let format = str_arg(call, 0, name, text)?;
+                    let placeholders = count_placeholders(&format)
+                        .map_err(|e| directive_error(name, text, call.args[0].span.start, format!("bad @print format string: {}", e)))?;
+                    let values: Vec<String> = call.args[1..].iter().map(|arg| arg.span.text(text).to_string()).collect();
+                    if placeholders != values.len() {
+                        return Err(directive_error(name, text, call.span.start, format!(
+                            "@print format string has {} {{}} but {} value{} given, e.g. @print(\"x = {{}}\", @engine.state.x)",
+                            placeholders, values.len(), if values.len() == 1 { " is" } else { "s are" }
+                        )));
+                    }
+                    metadata.prints.push(Print { format, values, location: location(name, text, call.span.start) });
+                }
+                _ => {}
+            }
+        }
+        for texture in texture_indices {
+            push_unique(&mut metadata.textures, texture);
+        }
+        metadata.sound_voices = metadata.sounds.iter()
+            .map(|sound| voice_limits.iter().find(|(s, _)| s == sound).map_or(DEFAULT_SOUND_VOICES, |(_, voices)| *voices))
+            .collect();
+        metadata.music_loops = metadata.music.iter()
+            .map(|track| music_loops.iter().find(|(t, _)| t == track).map_or_else(MusicLoop::default, |(_, l)| *l))
+            .collect();
+        metadata.cameras.sort();
+        if !metadata.dispatches.iter().any(|d| d.entry_point == "update") {
+            metadata.dispatches.insert(0, Dispatch::update());
         }
 
-        // Build header (only for top-level)
-        let mut header = String::new();
+        let game_state_struct = game_state.map(|(f, span)| active_text(&files[f], span));
+        self.asset_references = metadata.asset_references.clone();
+
+        // Build header (only for top-level), which also takes over the GameState struct
+        let mut writer = MappedWriter::new(files.iter().map(|f| f.name.clone()).collect());
         if is_top_level {
-            header.push_str("// Preprocessed WGSL - generated from macros\n\n");
-
-            // Add GameState first
-            if let Some(ref gs) = game_state_struct {
-                header.push_str(gs);
-                header.push_str("\n\n");
-            }
-
-            // Add GameEngineHost struct
-            header.push_str("// Engine host struct that contains all engine state\n");
-            header.push_str("struct GameEngineHost {\n");
-            header.push_str("    buttons: array<i32, 12>, // the current state of virtual SNES gamepad (BTN_*)\n");
-            header.push_str("    time: f32, // clock time\n");
-            header.push_str("    delta_time: f32, // time since last frame\n");
-            header.push_str("    screen_width: f32, // current screensize\n");
-            header.push_str("    screen_height: f32, // current screensize\n");
-            header.push_str("    mouse: vec4f, // mouse state (iMouse): xy=pos, z=click_x (neg if not pressed), w=click_y\n");
-            if game_state_struct.is_some() {
-                header.push_str("    state: GameState, // user's game state that persists across frames\n");
-            }
-            if !metadata.sounds.is_empty() {
-                header.push_str(&format!("    audio: array<u32, {}>, // audio trigger counters\n", metadata.sounds.len()));
-            }
-            header.push_str(&format!("    osc: array<f32, {}>, // OSC float uniforms: /u/name or /u/N\n", OSC_FLOAT_COUNT));
-            header.push_str(&format!("    keys: array<u32, {}>, // raw key state: 1=down, 0=up, indexed by KEY_* constants\n", KEY_ARRAY_SIZE));
-            header.push_str("}\n\n");
-
-            // Add button constants
-            header.push_str("// Button constants for input\n");
-            header.push_str("const BTN_UP: u32 = 0u;\n");
-            header.push_str("const BTN_DOWN: u32 = 1u;\n");
-            header.push_str("const BTN_LEFT: u32 = 2u;\n");
-            header.push_str("const BTN_RIGHT: u32 = 3u;\n");
-            header.push_str("const BTN_A: u32 = 4u;\n");
-            header.push_str("const BTN_B: u32 = 5u;\n");
-            header.push_str("const BTN_X: u32 = 6u;\n");
-            header.push_str("const BTN_Y: u32 = 7u;\n");
-            header.push_str("const BTN_L: u32 = 8u;\n");
-            header.push_str("const BTN_R: u32 = 9u;\n");
-            header.push_str("const BTN_START: u32 = 10u;\n");
-            header.push_str("const BTN_SELECT: u32 = 11u;\n\n");
-
-            // Key constants — indices match winit KeyCode enum order / web e.code strings
-            header.push_str("// Key constants for @engine.keys[] — same on native and web\n");
-            header.push_str("const KEY_BACKQUOTE: u32 = 0u;\n");
-            header.push_str("const KEY_BACKSLASH: u32 = 1u;\n");
-            header.push_str("const KEY_BRACKET_LEFT: u32 = 2u;\n");
-            header.push_str("const KEY_BRACKET_RIGHT: u32 = 3u;\n");
-            header.push_str("const KEY_COMMA: u32 = 4u;\n");
-            header.push_str("const KEY_0: u32 = 5u;\n");
-            header.push_str("const KEY_1: u32 = 6u;\n");
-            header.push_str("const KEY_2: u32 = 7u;\n");
-            header.push_str("const KEY_3: u32 = 8u;\n");
-            header.push_str("const KEY_4: u32 = 9u;\n");
-            header.push_str("const KEY_5: u32 = 10u;\n");
-            header.push_str("const KEY_6: u32 = 11u;\n");
-            header.push_str("const KEY_7: u32 = 12u;\n");
-            header.push_str("const KEY_8: u32 = 13u;\n");
-            header.push_str("const KEY_9: u32 = 14u;\n");
-            header.push_str("const KEY_EQUAL: u32 = 15u;\n");
-            header.push_str("const KEY_INTL_BACKSLASH: u32 = 16u;\n");
-            header.push_str("const KEY_INTL_RO: u32 = 17u;\n");
-            header.push_str("const KEY_INTL_YEN: u32 = 18u;\n");
-            header.push_str("const KEY_A: u32 = 19u;\n");
-            header.push_str("const KEY_B: u32 = 20u;\n");
-            header.push_str("const KEY_C: u32 = 21u;\n");
-            header.push_str("const KEY_D: u32 = 22u;\n");
-            header.push_str("const KEY_E: u32 = 23u;\n");
-            header.push_str("const KEY_F: u32 = 24u;\n");
-            header.push_str("const KEY_G: u32 = 25u;\n");
-            header.push_str("const KEY_H: u32 = 26u;\n");
-            header.push_str("const KEY_I: u32 = 27u;\n");
-            header.push_str("const KEY_J: u32 = 28u;\n");
-            header.push_str("const KEY_K: u32 = 29u;\n");
-            header.push_str("const KEY_L: u32 = 30u;\n");
-            header.push_str("const KEY_M: u32 = 31u;\n");
-            header.push_str("const KEY_N: u32 = 32u;\n");
-            header.push_str("const KEY_O: u32 = 33u;\n");
-            header.push_str("const KEY_P: u32 = 34u;\n");
-            header.push_str("const KEY_Q: u32 = 35u;\n");
-            header.push_str("const KEY_R: u32 = 36u;\n");
-            header.push_str("const KEY_S: u32 = 37u;\n");
-            header.push_str("const KEY_T: u32 = 38u;\n");
-            header.push_str("const KEY_U: u32 = 39u;\n");
-            header.push_str("const KEY_V: u32 = 40u;\n");
-            header.push_str("const KEY_W: u32 = 41u;\n");
-            header.push_str("const KEY_X: u32 = 42u;\n");
-            header.push_str("const KEY_Y: u32 = 43u;\n");
-            header.push_str("const KEY_Z: u32 = 44u;\n");
-            header.push_str("const KEY_MINUS: u32 = 45u;\n");
-            header.push_str("const KEY_PERIOD: u32 = 46u;\n");
-            header.push_str("const KEY_QUOTE: u32 = 47u;\n");
-            header.push_str("const KEY_SEMICOLON: u32 = 48u;\n");
-            header.push_str("const KEY_SLASH: u32 = 49u;\n");
-            header.push_str("const KEY_ALT_LEFT: u32 = 50u;\n");
-            header.push_str("const KEY_ALT_RIGHT: u32 = 51u;\n");
-            header.push_str("const KEY_BACKSPACE: u32 = 52u;\n");
-            header.push_str("const KEY_CAPS_LOCK: u32 = 53u;\n");
-            header.push_str("const KEY_CONTEXT_MENU: u32 = 54u;\n");
-            header.push_str("const KEY_CTRL_LEFT: u32 = 55u;\n");
-            header.push_str("const KEY_CTRL_RIGHT: u32 = 56u;\n");
-            header.push_str("const KEY_ENTER: u32 = 57u;\n");
-            header.push_str("const KEY_SUPER_LEFT: u32 = 58u;\n");
-            header.push_str("const KEY_SUPER_RIGHT: u32 = 59u;\n");
-            header.push_str("const KEY_SHIFT_LEFT: u32 = 60u;\n");
-            header.push_str("const KEY_SHIFT_RIGHT: u32 = 61u;\n");
-            header.push_str("const KEY_SPACE: u32 = 62u;\n");
-            header.push_str("const KEY_TAB: u32 = 63u;\n");
-            header.push_str("const KEY_DELETE: u32 = 72u;\n");
-            header.push_str("const KEY_END: u32 = 73u;\n");
-            header.push_str("const KEY_HOME: u32 = 75u;\n");
-            header.push_str("const KEY_INSERT: u32 = 76u;\n");
-            header.push_str("const KEY_PAGE_DOWN: u32 = 77u;\n");
-            header.push_str("const KEY_PAGE_UP: u32 = 78u;\n");
-            header.push_str("const KEY_DOWN: u32 = 79u;\n");
-            header.push_str("const KEY_LEFT: u32 = 80u;\n");
-            header.push_str("const KEY_RIGHT: u32 = 81u;\n");
-            header.push_str("const KEY_UP: u32 = 82u;\n");
-            header.push_str("const KEY_ESCAPE: u32 = 114u;\n");
-            header.push_str("const KEY_F1: u32 = 159u;\n");
-            header.push_str("const KEY_F2: u32 = 160u;\n");
-            header.push_str("const KEY_F3: u32 = 161u;\n");
-            header.push_str("const KEY_F4: u32 = 162u;\n");
-            header.push_str("const KEY_F5: u32 = 163u;\n");
-            header.push_str("const KEY_F6: u32 = 164u;\n");
-            header.push_str("const KEY_F7: u32 = 165u;\n");
-            header.push_str("const KEY_F8: u32 = 166u;\n");
-            header.push_str("const KEY_F9: u32 = 167u;\n");
-            header.push_str("const KEY_F10: u32 = 168u;\n");
-            header.push_str("const KEY_F11: u32 = 169u;\n");
-            header.push_str("const KEY_F12: u32 = 170u;\n");
-            header.push_str("\n");
-
-            // Add bindings
-            header.push_str("// Bindings: group 0 = textures, group 1 = engine state\n\n");
-            header.push_str("@group(0) @binding(0) var _engine_sampler: sampler;\n");
-
-            for (i, tex) in metadata.textures.iter().enumerate() {
-                header.push_str(&format!("@group(0) @binding({}) var _texture_{}: texture_2d<f32>; // {}\n", i + 1, i, tex));
-            }
-
-            let video_base = metadata.textures.len() + 1;
-            for (i, vid) in metadata.videos.iter().enumerate() {
-                header.push_str(&format!(
-                    "@group(0) @binding({}) var _video_{}: texture_2d<f32>; // {}\n",
-                    video_base + i, i, vid
-                ));
-            }
-
-            let camera_base = metadata.textures.len() + metadata.videos.len() + 1;
-            for (i, cam) in metadata.cameras.iter().enumerate() {
-                header.push_str(&format!(
-                    "@group(0) @binding({}) var _camera_{}: texture_2d<f32>; // camera {}\n",
-                    camera_base + i, i, cam
-                ));
-            }
-
-            header.push_str("\n@group(1) @binding(0) var<storage, read_write> _engine: GameEngineHost;\n");
-
-            // Add model buffers
-            if !metadata.models.is_empty() {
-                header.push_str("\n// Model data buffers\n");
-                for (i, model) in metadata.models.iter().enumerate() {
-                    let binding_base = 1 + i * 2;
-                    header.push_str(&format!("struct Model{}Positions {{ data: array<vec3f> }}\n", i));
-                    header.push_str(&format!("@group(2) @binding({}) var<storage, read> _model_{}_positions: Model{}Positions; // {}\n", binding_base, i, i, model));
-
-                    header.push_str(&format!("struct Model{}Normals {{ data: array<vec3f> }}\n", i));
-                    header.push_str(&format!("@group(2) @binding({}) var<storage, read> _model_{}_normals: Model{}Normals;\n", binding_base + 1, i, i));
-                }
-            }
-
-            header.push_str("\n");
-
-            // Remove GameState from source
-            if game_state_struct.is_some() {
-                source = game_state_re.replace(&source, "").to_string();
-            }
-        }
-
-        // Replace macros
-        source = source.replace("@engine.buttons", "_engine.buttons");
-        source = source.replace("@engine.time", "_engine.time");
-        source = source.replace("@engine.delta_time", "_engine.delta_time");
-        source = source.replace("@engine.screen_width", "_engine.screen_width");
-        source = source.replace("@engine.screen_height", "_engine.screen_height");
-        source = source.replace("@engine.mouse", "_engine.mouse");
-        source = source.replace("@engine.keys", "_engine.keys");
-        source = source.replace("@engine.sampler", "_engine_sampler");
-        source = source.replace("@engine.state", "_engine.state");
-        source = source.replace("@engine.osc", "_engine.osc");
-
-        // Replace @osc("name") with indexed slot access
-        for (i, name) in metadata.osc_params.iter().enumerate() {
-            let escaped = regex::escape(name);
-            let osc_name_re = Regex::new(&format!(r#"@osc\("{}"\)"#, escaped))?;
-            source = osc_name_re.replace_all(&source, &format!("_engine.osc[{}]", i)).to_string();
-        }
-
-        // Replace @sound().play() and @sound().stop()
-        for (i, sound) in metadata.sounds.iter().enumerate() {
-            let escaped = sound.replace(".", "\\.");
-            let play_re = Regex::new(&format!(r#"@sound\("{}"\)\.play\(\)"#, escaped))?;
-            source = play_re.replace_all(&source, &format!("_engine.audio[{}]++", i)).to_string();
-
-            let stop_re = Regex::new(&format!(r#"@sound\("{}"\)\.stop\(\)"#, escaped))?;
-            source = stop_re.replace_all(&source, &format!("/* stop sound {} - not implemented */", i)).to_string();
-
-            // Legacy @sound() syntax
-            let legacy_re = Regex::new(&format!(r#"@sound\("{}"\)"#, escaped))?;
-            source = legacy_re.replace_all(&source, &format!("_engine.audio[{}]", i)).to_string();
-        }
-
-        // Replace @texture()
-        for (i, texture) in metadata.textures.iter().enumerate() {
-            let escaped = texture.replace(".", "\\.");
-            let texture_re = Regex::new(&format!(r#"@texture\("{}"\)"#, escaped))?;
-            source = texture_re.replace_all(&source, &format!("_texture_{}", i)).to_string();
+            writer.generated(&build_header(&metadata, game_state_struct.as_deref()));
         }
+        let skip = if is_top_level { game_state } else { None };
+        emit_file(&files, root, &metadata, skip, &mut writer)?;
+
+        let (mut output, source_map) = writer.finish();
+        metadata.source_map = source_map;
 
-        // Replace @texture_index() with texture binding number
-        for (i, texture) in metadata.textures.iter().enumerate() {
-            let escaped = texture.replace(".", "\\.");
-            let texture_index_re = Regex::new(&format!(r#"@texture_index\("{}"\)"#, escaped))?;
-            source = texture_index_re.replace_all(&source, &format!("{}u", i)).to_string();
+        // Buffer layout comes from the shader naga will compile, so it matches the GPU exactly
+        if is_top_level {
+            let module = compute_layout(&output, &mut metadata)?;
+            route_vertex_engine_reads(&mut output, &module, &mut metadata.source_map)?;
         }
+        Ok((output, metadata))
+    }
+}
+
+/// Text of `span` in a file, leaving out code removed by @if/@else
+fn active_text(file: &ParsedFile, span: Span) -> String {
+    file.items.iter()
+        .filter_map(|item| match item {
+            Item::Text(text) => Some(Span::new(text.start.max(span.start), text.end.min(span.end))),
+            _ => None,
+        })
+        .filter(|piece| piece.start < piece.end)
+        .map(|piece| piece.text(&file.text))
+        .collect()
+}
 
-        // Replace @video()
-        for (i, video) in metadata.videos.iter().enumerate() {
-            let escaped = video.replace(".", "\\.");
-            let re = Regex::new(&format!(r#"@video\("{}"\)"#, escaped))?;
-            source = re.replace_all(&source, &format!("_video_{}", i)).to_string();
+/// A macro argument naming an entry point, renamed like the rest of the module when it comes from a namespaced import
+fn entry_point_arg(file: &ParsedFile, call: &MacroCall, index: usize) -> Option<String> {
+    let arg = call.args.get(index).filter(|arg| arg.tokens.len() == 1 && arg.tokens[0].kind == TokenKind::Ident)?;
+    Some(renamed_text(file, arg.span))
+}
+
+/// Source text of a span with namespace renames applied
+fn renamed_text(file: &ParsedFile, span: Span) -> String {
+    let mut out = String::new();
+    let mut pos = span.start;
+    let first = file.renames.partition_point(|(s, _)| s.start < span.start);
+    for (ident, replacement) in file.renames[first..].iter().take_while(|(s, _)| s.end <= span.end) {
+        out.push_str(&file.text[pos..ident.start]);
+        out.push_str(replacement);
+        pos = ident.end;
+    }
+    out.push_str(&file.text[pos..span.end]);
+    out
+}
+
+fn push_unique<T: PartialEq>(list: &mut Vec<T>, value: T) {
+    if !list.contains(&value) {
+        list.push(value);
+    }
+}
+
+/// (file, item) pairs in the order they appear once imports are inlined
+fn flatten_order(files: &[ParsedFile], file: usize, order: &mut Vec<(usize, usize)>) {
+    for (i, item) in files[file].items.iter().enumerate() {
+        if let Item::Import { file: Some(imported), .. } = item {
+            flatten_order(files, *imported, order);
+        } else {
+            order.push((file, i));
         }
+    }
+}
 
-        // Replace @camera()
-        for (i, cam_idx) in metadata.cameras.iter().enumerate() {
-            let re = Regex::new(&format!(r#"@camera\({}\)"#, cam_idx))?;
-            source = re.replace_all(&source, &format!("_camera_{}", i)).to_string();
+/// Read a macro argument that must be a single string literal
+fn str_arg(call: &MacroCall, index: usize, file: &str, text: &str) -> Result<String, Error> {
+    match call.args.get(index) {
+        Some(arg) if arg.tokens.len() == 1 && arg.tokens[0].kind == TokenKind::Str => {
+            Ok(lexer::unescape_str(arg.span.text(text)))
         }
+        Some(arg) => Err(directive_error(file, text, arg.span.start, format!("@{} expects a string literal, found '{}'", call.name, arg.span.text(text)))),
+        None => Err(directive_error(file, text, call.span.start, format!("@{} is missing argument {}", call.name, index + 1))),
+    }
+}
 
-        // Replace @str() with fixed-size array of character codes (padded with zeros)
-        let str_re = Regex::new(r#"@str\("((?:[^"\\]|\\.)*)"\)"#)?;
-        let str_matches: Vec<(String, String)> = str_re.captures_iter(&source)
-            .map(|cap| (cap.get(0).unwrap().as_str().to_string(), cap[1].to_string()))
+/// Read a macro argument that must be a non-negative integer literal
+fn int_arg(call: &MacroCall, index: usize, file: &str, text: &str) -> Result<u32, Error> {
+    match call.args.get(index) {
+        Some(arg) => arg.span.text(text).trim_end_matches('u').parse().map_err(|_| {
+            directive_error(file, text, arg.span.start, format!("@{} expects an integer, found '{}'", call.name, arg.span.text(text)))
+        }),
+        None => Err(directive_error(file, text, call.span.start, format!("@{} is missing argument {}", call.name, index + 1))),
+    }
+}
+
+/// Read a macro argument that must be a non-negative number literal, e.g. 12.5
+fn float_arg(call: &MacroCall, index: usize, file: &str, text: &str) -> Result<f32, Error> {
+    match call.args.get(index) {
+        Some(arg) => arg.span.text(text).trim_end_matches('f').parse().ok().filter(|x: &f32| *x >= 0.0).ok_or_else(|| {
+            directive_error(file, text, arg.span.start, format!("@{} expects a number of seconds, found '{}'", call.name, arg.span.text(text)))
+        }),
+        None => Err(directive_error(file, text, call.span.start, format!("@{} is missing argument {}", call.name, index + 1))),
+    }
+}
+
+/// Fill in GameState and GameEngineHost layouts by running naga's layouter over the final shader
+fn compute_layout(code: &str, metadata: &mut Metadata) -> Result<naga::Module, Error> {
+    let module = naga::front::wgsl::parse_str(code).map_err(|e| wgsl_error(&e, code, &metadata.source_map))?;
+    let mut layouter = naga::proc::Layouter::default();
+    layouter.update(module.to_ctx()).map_err(|e| {
+        let message = format!("failed to compute buffer layout: {}", e);
+        Error::Shader { location: None, report: message.clone(), message }
+    })?;
+
+    let struct_layout = |name: &str| {
+        module.types.iter().find_map(|(_, ty)| match &ty.inner {
+            naga::TypeInner::Struct { members, span } if ty.name.as_deref() == Some(name) => {
+                let fields = members.iter().map(|m| FieldLayout {
+                    name: m.name.clone().unwrap_or_default(),
+                    offset: m.offset as usize,
+                    size: layouter[m.ty].size as usize,
+                }).collect::<Vec<_>>();
+                Some((fields, *span as usize))
+            }
+            _ => None,
+        })
+    };
+
+    if let Some((fields, size)) = struct_layout("GameState") {
+        metadata.state_fields = fields;
+        metadata.state_size = size;
+    }
+    if let Some((fields, size)) = metadata.persist_struct.as_deref().and_then(struct_layout) {
+        metadata.persist_fields = fields;
+        metadata.persist_size = size;
+    }
+    let (fields, size) = struct_layout("GameEngineHost").ok_or_else(|| {
+        let message = "GameEngineHost struct missing from generated header".to_string();
+        Error::Shader { location: None, report: message.clone(), message }
+    })?;
+    metadata.engine_fields = fields;
+    metadata.engine_size = size;
+
+    for (i, buffer) in metadata.storage_buffers.iter_mut().enumerate() {
+        let var_name = format!("_storage_{}", i);
+        let Some((_, var)) = module.global_variables.iter().find(|(_, v)| v.name.as_deref() == Some(var_name.as_str())) else { continue };
+        let element = match module.types[var.ty].inner {
+            naga::TypeInner::Array { size: naga::ArraySize::Constant(_), base, .. } => base,
+            naga::TypeInner::Array { .. } => {
+                return Err(Error::InvalidDirective {
+                    location: buffer.location.clone(),
+                    message: format!("@storage(\"{}\") needs a fixed size, e.g. array<{}, 1024>", buffer.name, buffer.ty.trim_start_matches("array<").trim_end_matches('>')),
+                });
+            }
+            _ => var.ty,
+        };
+        buffer.size = layouter[var.ty].size as usize;
+        buffer.element = match &module.types[element].inner {
+            naga::TypeInner::Struct { members, .. } => {
+                let members: Vec<String> = members.iter()
+                    .map(|m| format!("{}: {} @ {}", m.name.as_deref().unwrap_or_default(), m.ty.to_wgsl(&module.to_ctx()), m.offset))
+                    .collect();
+                format!("{} {{ {} }}", element.to_wgsl(&module.to_ctx()), members.join(", "))
+            }
+            _ => element.to_wgsl(&module.to_ctx()),
+        };
+    }
+
+    // A pipeline for a missing entry point would fail with no hint of which @dispatch or @pass asked for it
+    let dispatches = metadata.dispatches.iter()
+        .filter_map(|d| Some(("dispatch", &d.entry_point, naga::ShaderStage::Compute, d.location.as_ref()?)));
+    let passes = metadata.passes.iter().map(|p| ("pass", &p.entry_point, naga::ShaderStage::Fragment, &p.location));
+    let tests = metadata.tests.iter().map(|t| ("test", &t.entry_point, naga::ShaderStage::Compute, &t.location));
+    for (directive, entry_point, stage, location) in dispatches.chain(passes).chain(tests) {
+        let stage_name = if stage == naga::ShaderStage::Compute { "compute" } else { "fragment" };
+        let message = match module.entry_points.iter().find(|ep| ep.name == *entry_point) {
+            None => format!("@{} names '{}', but there is no @{} fn {}", directive, entry_point, stage_name, entry_point),
+            Some(ep) if ep.stage != stage => format!("@{} entry point '{}' must be @{}", directive, ep.name, stage_name),
+            Some(_) => continue,
+        };
+        return Err(Error::InvalidDirective { location: location.clone(), message });
+    }
+    // A @pass() fragment stage only gets what PASS_VERTEX_ENTRY outputs
+    for pass in &metadata.passes {
+        let Some(ep) = module.entry_points.iter().find(|ep| ep.name == pass.entry_point) else { continue };
+        let provided = |binding: Option<&naga::Binding>, ty: naga::Handle<naga::Type>| match binding {
+            Some(naga::Binding::Location { location, .. }) => {
+                *location == 0 && module.types[ty].inner == naga::TypeInner::Vector { size: naga::VectorSize::Bi, scalar: naga::Scalar::F32 }
+            }
+            _ => true,
+        };
+        let inputs_provided = ep.function.arguments.iter().all(|arg| match &module.types[arg.ty].inner {
+            naga::TypeInner::Struct { members, .. } if arg.binding.is_none() => members.iter().all(|m| provided(m.binding.as_ref(), m.ty)),
+            _ => provided(arg.binding.as_ref(), arg.ty),
+        });
+        if !inputs_provided {
+            let message = format!("@pass entry point '{}' is drawn over the whole target and only gets @builtin(position) and @location(0) uv: vec2f", ep.name);
+            return Err(Error::InvalidDirective { location: pass.location.clone(), message });
+        }
+    }
+    if let Some(ep) = module.entry_points.iter().find(|ep| ep.name == "init") {
+        if ep.stage != naga::ShaderStage::Compute {
+            let message = "'init' is the entry point the engine runs on load and on reset, so it must be @compute".to_string();
+            return Err(Error::Shader { location: None, report: message.clone(), message });
+        }
+        metadata.has_init = true;
+    }
+    Ok(module)
+}
+
+/// Point @engine in every function the vertex stage runs at `_engine_view`. Vertex shaders can't
+/// bind the read_write engine buffer, so the runtime gives them a read-only copy taken after compute.
+fn route_vertex_engine_reads(code: &mut String, module: &naga::Module, source_map: &mut SourceMap) -> Result<(), Error> {
+    fn calls(block: &naga::Block, out: &mut Vec<naga::Handle<naga::Function>>) {
+        for statement in block.iter() {
+            match statement {
+                naga::Statement::Call { function, .. } => out.push(*function),
+                naga::Statement::Block(body) => calls(body, out),
+                naga::Statement::If { accept, reject, .. } => {
+                    calls(accept, out);
+                    calls(reject, out);
+                }
+                naga::Statement::Switch { cases, .. } => cases.iter().for_each(|case| calls(&case.body, out)),
+                naga::Statement::Loop { body, continuing, .. } => {
+                    calls(body, out);
+                    calls(continuing, out);
+                }
+                _ => {}
+            }
+        }
+    }
+    // Functions an entry point calls, directly or through other functions
+    let reachable = |function: &naga::Function| {
+        let mut seen = HashSet::new();
+        let mut pending = Vec::new();
+        calls(&function.body, &mut pending);
+        while let Some(handle) = pending.pop() {
+            if seen.insert(handle) {
+                calls(&module.functions[handle].body, &mut pending);
+            }
+        }
+        seen
+    };
+
+    let mut vertex = HashSet::new();
+    let mut compute = HashSet::new();
+    for ep in &module.entry_points {
+        match ep.stage {
+            naga::ShaderStage::Vertex => {
+                vertex.insert(ep.name.as_str());
+                vertex.extend(reachable(&ep.function).into_iter().filter_map(|h| module.functions[h].name.as_deref()));
+            }
+            naga::ShaderStage::Compute => {
+                compute.extend(reachable(&ep.function).into_iter().filter_map(|h| module.functions[h].name.as_deref()));
+            }
+            naga::ShaderStage::Fragment => {}
+        }
+    }
+
+    // Nothing the vertex stage runs may write to @engine, in the game's own code or in the helpers
+    // behind @sound(), @music() and the rest; it's reported where the game's code does it
+    let lines = LineIndex::new(code);
+    if let Some(engine) = module.global_variables.iter().find(|(_, g)| g.name.as_deref() == Some("_engine")).map(|(h, _)| h) {
+        let writers = engine_writers(module, engine);
+        for ep in module.entry_points.iter().filter(|ep| ep.stage == naga::ShaderStage::Vertex) {
+            let reached = reachable(&ep.function);
+            // Callees come before their callers in the arena, so the innermost write is reported
+            let functions = module.functions.iter()
+                .filter(|(h, _)| reached.contains(h))
+                .map(|(_, f)| (f.name.as_deref().unwrap_or("?"), f))
+                .chain(std::iter::once((ep.name.as_str(), &ep.function)));
+            let mut unmapped = None;
+            for (name, span) in functions.filter_map(|(name, f)| Some((name, engine_write(&f.body, f, engine, &writers)?))) {
+                let message = format!(
+                    "'{}' runs in the vertex stage, which only gets a read-only copy of @engine, so it can't change @engine or play @sound()s and @music(); do that from a compute entry point",
+                    name
+                );
+                let (line, column) = lines.position(span.to_range().map_or(0, |range| range.start));
+                match source_map.lookup(line + 1, column + 1) {
+                    Some(location) => return Err(Error::InvalidDirective { location, message }),
+                    None => unmapped = unmapped.or(Some(message)),
+                }
+            }
+            if let Some(message) = unmapped {
+                return Err(Error::Shader { location: None, report: message.clone(), message });
+            }
+        }
+    }
+
+    let tokens: Vec<Token> = lexer::tokenize(code)
+        .map_err(|e| Error::Shader { location: None, report: e.message.clone(), message: e.message })?
+        .into_iter()
+        .filter(|t| !t.is_trivia())
+        .collect();
+    let mut engine_reads = Vec::new();
+    let mut i = 0;
+    while i + 1 < tokens.len() {
+        let (keyword, name) = (tokens[i].span.text(code), tokens[i + 1].span.text(code));
+        i += 1;
+        if keyword != "fn" || !vertex.contains(name) {
+            continue;
+        }
+        let Some(open) = tokens[i..].iter().position(|t| t.is_punct('{')).map(|p| p + i) else { break };
+        let mut depth = 0;
+        for (j, tok) in tokens.iter().enumerate().skip(open) {
+            if tok.is_punct('{') {
+                depth += 1;
+            } else if tok.is_punct('}') {
+                depth -= 1;
+            } else if tok.kind == TokenKind::Ident && tok.span.text(code) == "_engine" {
+                if compute.contains(name) {
+                    let message = format!(
+                        "'{}' uses @engine and is called from both vertex and compute entry points; the vertex stage only gets a read-only copy, so give it its own function",
+                        name
+                    );
+                    let (line, column) = lines.position(tok.span.start);
+                    return Err(match source_map.lookup(line + 1, column + 1) {
+                        Some(location) => Error::InvalidDirective { location, message },
+                        None => Error::Shader { location: None, report: message.clone(), message },
+                    });
+                }
+                engine_reads.push(tok.span);
+            }
+            if depth == 0 {
+                i = j;
+                break;
+            }
+        }
+    }
+
+    for span in engine_reads.into_iter().rev() {
+        let (line, column) = lines.position(span.start);
+        code.replace_range(span.start..span.end, "_engine_view");
+        source_map.insert(line, column, "_view".len());
+    }
+    Ok(())
+}
+
+/// Functions that write to the engine buffer, themselves or through the functions they call
+fn engine_writers(module: &naga::Module, engine: naga::Handle<naga::GlobalVariable>) -> HashSet<naga::Handle<naga::Function>> {
+    let mut writers = HashSet::new();
+    loop {
+        let found: Vec<_> = module.functions.iter()
+            .filter(|(h, f)| !writers.contains(h) && engine_write(&f.body, f, engine, &writers).is_some())
+            .map(|(h, _)| h)
             .collect();
+        if found.is_empty() {
+            return writers;
+        }
+        writers.extend(found);
+    }
+}
 
-        for (full_match, string) in str_matches {
-            // Unescape the string
-            let unescaped = string
-                .replace("\\n", "\n")
-                .replace("\\r", "\r")
-                .replace("\\t", "\t")
-                .replace("\\\"", "\"")
-                .replace("\\\\", "\\");
+/// Where a block first writes to the engine buffer: a store or atomic through a pointer into it,
+/// or a call to one of `writers`
+fn engine_write(
+    block: &naga::Block,
+    function: &naga::Function,
+    engine: naga::Handle<naga::GlobalVariable>,
+    writers: &HashSet<naga::Handle<naga::Function>>,
+) -> Option<naga::Span> {
+    let into_engine = |mut pointer: naga::Handle<naga::Expression>| loop {
+        match function.expressions[pointer] {
+            naga::Expression::Access { base, .. } | naga::Expression::AccessIndex { base, .. } => pointer = base,
+            naga::Expression::GlobalVariable(global) => return global == engine,
+            _ => return false,
+        }
+    };
+    block.span_iter().find_map(|(statement, span)| match statement {
+        naga::Statement::Store { pointer, .. } | naga::Statement::Atomic { pointer, .. } if into_engine(*pointer) => Some(*span),
+        naga::Statement::Call { function, .. } if writers.contains(function) => Some(*span),
+        naga::Statement::Block(body) => engine_write(body, function, engine, writers),
+        naga::Statement::If { accept, reject, .. } => {
+            engine_write(accept, function, engine, writers).or_else(|| engine_write(reject, function, engine, writers))
+        }
+        naga::Statement::Switch { cases, .. } => cases.iter().find_map(|case| engine_write(&case.body, function, engine, writers)),
+        naga::Statement::Loop { body, continuing, .. } => {
+            engine_write(body, function, engine, writers).or_else(|| engine_write(continuing, function, engine, writers))
+        }
+        _ => None,
+    })
+}
 
-            // Convert to character codes
-            let mut char_codes: Vec<u32> = unescaped.chars().map(|c| c as u32).collect();
+/// A naga WGSL parse error as Error::Shader, with locations mapped back to the game's files
+pub(crate) fn wgsl_error(e: &naga::front::wgsl::ParseError, code: &str, source_map: &SourceMap) -> Error {
+    Error::Shader {
+        location: e.location(code).and_then(|loc| source_map.lookup(loc.line_number as usize, loc.line_position as usize)),
+        message: e.message().to_string(),
+        report: source_map.rewrite_diagnostic(&e.emit_to_string(code)),
+    }
+}
 
-            // Pad with zeros to 128
-            while char_codes.len() < 128 {
-                char_codes.push(0);
+/// Copy source text through, applying namespace renames
+fn copy_text(file: &ParsedFile, index: usize, span: Span, out: &mut MappedWriter) {
+    let mut pos = span.start;
+    let first = file.renames.partition_point(|(s, _)| s.start < span.start);
+    for (ident, replacement) in file.renames[first..].iter().take_while(|(s, _)| s.end <= span.end) {
+        out.copied(&file.text[pos..ident.start], index, file.lines.position(pos));
+        out.expanded(replacement, index, file.lines.position(ident.start));
+        pos = ident.end;
+    }
+    out.copied(&file.text[pos..span.end], index, file.lines.position(pos));
+}
+
+/// Write a file's text with macros expanded and imports inlined
+fn emit_file(files: &[ParsedFile], index: usize, metadata: &Metadata, skip: Option<(usize, Span)>, out: &mut MappedWriter) -> Result<(), Error> {
+    let file = &files[index];
+    let (name, text) = (file.name.as_str(), file.text.as_str());
+    let local_skip = skip.filter(|(f, _)| *f == index).map(|(_, span)| span);
+
+    for item in &file.items {
+        match item {
+            Item::Text(span) => {
+                for piece in subtract_span(*span, local_skip) {
+                    copy_text(file, index, piece, out);
+                }
+            }
+            Item::Import { name, file: None, span } => {
+                out.expanded(&format!("// Already imported: {}", name), index, file.lines.position(span.start));
+            }
+            Item::Import { name, file: Some(imported), span } => {
+                out.expanded(&format!("// Imported from {}\n", name), index, file.lines.position(span.start));
+                emit_file(files, *imported, metadata, skip, out)?;
+                out.generated("\n");
+            }
+            Item::Macro(call) => {
+                if local_skip.is_some_and(|s| call.span.start >= s.start && call.span.end <= s.end) {
+                    continue;
+                }
+                out.expanded(&expand_macro(call, metadata, name, text)?, index, file.lines.position(call.span.start));
             }
+        }
+    }
+    Ok(())
+}
 
-            // Create array literal
+/// Expansion text for a single macro call
+fn expand_macro(call: &MacroCall, metadata: &Metadata, file: &str, text: &str) -> Result<String, Error> {
+    let index_of = |list: &[String], value: String| list.iter().position(|v| *v == value).unwrap_or(0);
+
+    Ok(match call.name.as_str() {
+        // Directives only contribute metadata
+        "set_title" | "set_size" | "set_voices" | "set_music_loop" | "dispatch" | "pass" | "persist" | "test" => String::new(),
+        // @assert(condition, "message") becomes _assert(id, condition)
+        "assert" => {
+            let at = location(file, text, call.span.start);
+            format!("_assert({}u, ", metadata.asserts.iter().position(|a| a.location == at).unwrap_or(0))
+        }
+        "assert_end" => ")".to_string(),
+        // @print("format", a, b) becomes _print(id, array<f32, N>(f32(a), f32(b), 0.0, ...))
+        "print" => {
+            let at = location(file, text, call.span.start);
+            let id = metadata.prints.iter().position(|p| p.location == at).unwrap_or(0);
+            let head = format!("_print({}u, array<f32, {}>(", id, metadata.print_values());
+            if call.args.len() > 1 {
+                format!("{}f32(", head)
+            } else {
+                format!("{}{}))", head, vec!["0.0"; metadata.print_values()].join(", "))
+            }
+        }
+        "print_next" => "), f32(".to_string(),
+        "print_end" => {
+            let padding = metadata.print_values() - (call.args.len() - 1);
+            format!("){}))", ", 0.0".repeat(padding))
+        }
+        "storage" | "canvas" if call.args.len() > 1 => String::new(),
+        "canvas" => {
+            let canvas = str_arg(call, 0, file, text)?;
+            let Some(i) = metadata.canvases.iter().position(|c| c.name == canvas) else {
+                return Err(directive_error(file, text, call.span.start, format!("no @canvas(\"{}\", width, height) declared", canvas)));
+            };
+            match call.member.as_deref() {
+                None => format!("_canvas_{}", i),
+                Some("storage") => format!("_canvas_{}_storage", i),
+                Some(other) => return Err(directive_error(file, text, call.span.start, format!("unknown canvas field '.{}', expected .storage", other))),
+            }
+        }
+        "storage" => {
+            let buffer = str_arg(call, 0, file, text)?;
+            match metadata.storage_buffers.iter().position(|b| b.name == buffer) {
+                Some(i) => format!("_storage_{}", i),
+                None => return Err(directive_error(file, text, call.span.start, format!("no @storage(\"{}\", type) declared", buffer))),
+            }
+        }
+        "engine" => match call.member.as_deref() {
+            Some("sampler") => "_engine_sampler".to_string(),
+            Some("persist" | "save" | "load") if metadata.persist_struct.is_none() => {
+                let member = call.member.as_deref().unwrap_or_default();
+                return Err(directive_error(file, text, call.span.start, format!("@engine.{} needs a @persist struct to hold the saved data", member)));
+            }
+            Some(member @ ("music_time" | "music_duration")) if metadata.music.is_empty() => {
+                return Err(directive_error(file, text, call.span.start, format!("@engine.{} needs a @music() track", member)));
+            }
+            Some(method @ ("reset" | "save" | "load" | "quit")) => format!("_engine.{} = 1u", method),
+            Some(member) => format!("_engine.{}", member),
+            None => unreachable!("@engine is always parsed with a member"),
+        },
+        // @sound("x").play(volume, pitch, pan) becomes _sound(i, flags, volume, pitch, pan), the
+        // arguments it wasn't given filled in by sound_end (or here, when it has none)
+        "sound" => {
+            let i = index_of(&metadata.sounds, str_arg(call, 0, file, text)?);
+            let flags = match call.member.as_deref() {
+                Some("play") => 0,
+                Some("loop") => SOUND_LOOP,
+                Some("stop") => SOUND_STOP,
+                Some("is_playing") => return Ok(format!("(_engine.sound_voices[{}] > 0u)", i)),
+                None => return Err(directive_error(file, text, call.span.start, "expected .play(), .loop(), .stop() or .is_playing after @sound(...)".to_string())),
+                Some(other) => return Err(directive_error(file, text, call.span.start, format!(
+                    "unknown sound method '.{}()', expected .play(), .loop(), .stop() or .is_playing", other
+                ))),
+            };
+            if call.args.len() > 1 {
+                format!("_sound({}u, {}u, ", i, flags)
+            } else {
+                format!("_sound({}u, {}u, {})", i, flags, SOUND_DEFAULTS.join(", "))
+            }
+        }
+        "sound_end" => format!("{})", SOUND_DEFAULTS[call.args.len()..].iter().map(|d| format!(", {}", d)).collect::<String>()),
+        // @music("x").loop(fade) becomes _music(i, command, fade)
+        "music" => {
+            let i = index_of(&metadata.music, str_arg(call, 0, file, text)?);
+            let command = match call.member.as_deref() {
+                Some("play") => 1,
+                Some("loop") => 2,
+                Some("pause") => 3,
+                Some("stop") => 4,
+                Some("is_playing") => return Ok(format!("(_engine.music_track == {}u)", i + 1)),
+                None => return Err(directive_error(file, text, call.span.start, "expected .play(), .loop(), .pause(), .stop() or .is_playing after @music(...)".to_string())),
+                Some(other) => return Err(directive_error(file, text, call.span.start, format!(
+                    "unknown music method '.{}()', expected .play(), .loop(), .pause(), .stop() or .is_playing", other
+                ))),
+            };
+            if call.args.len() > 1 {
+                format!("_music({}u, {}u, ", i, command)
+            } else {
+                format!("_music({}u, {}u, 0.0)", i, command)
+            }
+        }
+        "music_end" => ")".to_string(),
+        "texture" => format!("_texture_{}", index_of(&metadata.textures, str_arg(call, 0, file, text)?)),
+        // Texture binding number
+        "texture_index" => format!("{}u", index_of(&metadata.textures, str_arg(call, 0, file, text)?)),
+        "video" => format!("_video_{}", index_of(&metadata.videos, str_arg(call, 0, file, text)?)),
+        "camera" => {
+            let cam = int_arg(call, 0, file, text)?;
+            format!("_camera_{}", metadata.cameras.iter().position(|c| *c == cam).unwrap_or(0))
+        }
+        "buffer" => {
+            let buffer = str_arg(call, 0, file, text)?;
+            match metadata.passes.iter().position(|p| p.name == buffer) {
+                Some(i) => format!("_buffer_{}", i),
+                None => return Err(directive_error(file, text, call.span.start, format!("no @pass named \"{}\" for @buffer", buffer))),
+            }
+        }
+        "osc" => format!("_engine.osc[{}]", index_of(&metadata.osc_params, str_arg(call, 0, file, text)?)),
+        // Usage: @model("file.obj").positions[idx] becomes _model_0_positions.data[idx]
+        "model" => {
+            let model = str_arg(call, 0, file, text)?;
+            let i = index_of(&metadata.models, model.clone());
+            match call.member.as_deref() {
+                Some("positions") => format!("_model_{}_positions.data", i),
+                Some("normals") => format!("_model_{}_normals.data", i),
+                None => format!("/* @model(\"{}\") - use .positions or .normals */", model),
+                Some(other) => return Err(directive_error(file, text, call.span.start, format!("unknown model field '.{}', expected .positions or .normals", other))),
+            }
+        }
+        // Fixed-size array of character codes (padded with zeros)
+        "str" => {
+            let mut char_codes: Vec<u32> = str_arg(call, 0, file, text)?.chars().map(|c| c as u32).collect();
+            if char_codes.len() > 128 {
+                return Err(directive_error(file, text, call.span.start, "@str() is limited to 128 characters".to_string()));
+            }
+            char_codes.resize(128, 0);
             let codes_str = char_codes.iter()
                 .map(|c| format!("{}u", c))
                 .collect::<Vec<_>>()
                 .join(", ");
-            let replacement = format!("array<u32, 128>({})", codes_str);
-
-            source = source.replace(&full_match, &replacement);
+            format!("array<u32, 128>({})", codes_str)
         }
+        other => unreachable!("unhandled macro @{}", other),
+    })
+}
 
-        // Replace @model() - Note: This creates a struct-like accessor
-        // Usage: @model("file.obj").positions[idx] becomes _model_0_positions.data[idx]
-        for (i, model) in metadata.models.iter().enumerate() {
-            let escaped = model.replace(".", "\\.");
-            // Replace @model("file").positions with _model_N_positions.data
-            let pos_re = Regex::new(&format!(r#"@model\("{}"\)\.positions"#, escaped))?;
-            source = pos_re.replace_all(&source, &format!("_model_{}_positions.data", i)).to_string();
+/// Generated declarations that precede the game code: GameState, GameEngineHost, constants and bindings
+fn build_header(metadata: &Metadata, game_state: Option<&str>) -> String {
+    let mut header = String::new();
+    header.push_str("// Preprocessed WGSL - generated from macros\n\n");
 
-            // Replace @model("file").normals with _model_N_normals.data
-            let norm_re = Regex::new(&format!(r#"@model\("{}"\)\.normals"#, escaped))?;
-            source = norm_re.replace_all(&source, &format!("_model_{}_normals.data", i)).to_string();
+    // Add GameState first
+    if let Some(gs) = game_state {
+        header.push_str(gs);
+        header.push_str("\n\n");
+    }
 
-            // Replace any remaining @model("file") with a comment about proper usage
-            let model_re = Regex::new(&format!(r#"@model\("{}"\)"#, escaped))?;
-            source = model_re.replace_all(&source, &format!("/* @model(\"{}\") - use .positions or .normals */", model)).to_string();
-        }
+    if !metadata.sounds.is_empty() {
+        header.push_str("// A @sound() call: which sound, whether it loops (1) or stops (2), and how it plays\n");
+        header.push_str("struct _SoundTrigger {\n    sound: u32,\n    flags: u32,\n    volume: f32,\n    pitch: f32,\n    pan: f32,\n}\n\n");
+    }
 
-        Ok((header + &source, metadata))
+    // Add GameEngineHost struct
+    header.push_str("// Engine host struct that contains all engine state\n");
+    let host_start = header.len();
+    header.push_str("struct GameEngineHost {\n");
+    header.push_str("    buttons: array<i32, 12>, // the current state of virtual SNES gamepad (BTN_*)\n");
+    header.push_str("    time: f32, // clock time\n");
+    header.push_str("    delta_time: f32, // time since last frame\n");
+    header.push_str("    screen_width: f32, // current screensize\n");
+    header.push_str("    screen_height: f32, // current screensize\n");
+    header.push_str("    mouse: vec4f, // mouse state (iMouse): xy=pos, z=click_x (neg if not pressed), w=click_y\n");
+    if game_state.is_some() {
+        header.push_str("    state: GameState, // user's game state that persists across frames\n");
+    }
+    if !metadata.sounds.is_empty() {
+        header.push_str("    sound_count: atomic<u32>, // @sound() calls this frame; the runtime reads them back and empties the queue\n");
+        header.push_str(&format!("    sound_triggers: array<_SoundTrigger, {}>, // the first of them, in order\n", MAX_SOUND_TRIGGERS));
+        header.push_str(&format!("    sound_voices: array<u32, {}>, // voices of each sound playing, written by the runtime\n", metadata.sounds.len()));
+    }
+    if !metadata.music.is_empty() {
+        header.push_str("    music_time: f32, // seconds into the current @music() track, written by the runtime\n");
+        header.push_str("    music_duration: f32, // the current track's length in seconds\n");
+        header.push_str("    music_track: u32, // which track is playing, from 1 (0 if none is or it's paused)\n");
+        header.push_str("    music_command: u32, // the frame's last @music() call: 1 play, 2 loop, 3 pause, 4 stop; the runtime reads it back and clears it\n");
+        header.push_str("    music_command_track: u32, // the track it was for\n");
+        header.push_str("    music_command_fade: f32, // seconds to fade over\n");
+    }
+    if metadata.uses_audio_analysis {
+        header.push_str("    audio_level: f32, // RMS level of everything playing, written by the runtime\n");
+        header.push_str(&format!("    spectrum: array<f32, {}>, // its spectrum, 0 to 1 a bin from 0 Hz up to half the sample rate\n", SPECTRUM_SIZE));
+        header.push_str(&format!("    waveform: array<f32, {}>, // its latest samples, mixed down to mono\n", WAVEFORM_SIZE));
+    }
+    header.push_str(&format!("    osc: array<f32, {}>, // OSC float uniforms: /u/name or /u/N\n", OSC_FLOAT_COUNT));
+    header.push_str(&format!("    keys: array<u32, {}>, // raw key state: 1=down, 0=up, indexed by KEY_* constants\n", KEY_ARRAY_SIZE));
+    header.push_str("    reset: u32, // set by @engine.reset(): the runtime clears GameState and runs init() before the next frame\n");
+    header.push_str("    save: u32, // set by @engine.save(): the runtime writes @engine.persist to the save file\n");
+    header.push_str("    load: u32, // set by @engine.load(): the runtime reads the save file back into @engine.persist\n");
+    header.push_str("    quit: u32, // set by @engine.quit(): the runtime closes the window, or ends a headless run\n");
+    if let Some(name) = &metadata.persist_struct {
+        header.push_str(&format!("    persist: {}, // @persist struct, loaded from the game's save file on startup\n", name));
     }
+    if !metadata.asserts.is_empty() {
+        header.push_str(&format!("    asserts: array<u32, {}>, // set by failing @assert()s, read back by wgsleng test\n", metadata.asserts.len()));
+    }
+    header.push_str("}\n\n");
+    // The vertex stage's read-only copy can't hold an atomic, so it's the same struct with a plain sound_count
+    let view = header[host_start..]
+        .replacen("struct GameEngineHost", "struct _GameEngineView", 1)
+        .replacen("sound_count: atomic<u32>", "sound_count: u32", 1);
+    header.push_str(&view);
+
+    // Add button constants
+    header.push_str("// Button constants for input\n");
+    header.push_str("const BTN_UP: u32 = 0u;\n");
+    header.push_str("const BTN_DOWN: u32 = 1u;\n");
+    header.push_str("const BTN_LEFT: u32 = 2u;\n");
+    header.push_str("const BTN_RIGHT: u32 = 3u;\n");
+    header.push_str("const BTN_A: u32 = 4u;\n");
+    header.push_str("const BTN_B: u32 = 5u;\n");
+    header.push_str("const BTN_X: u32 = 6u;\n");
+    header.push_str("const BTN_Y: u32 = 7u;\n");
+    header.push_str("const BTN_L: u32 = 8u;\n");
+    header.push_str("const BTN_R: u32 = 9u;\n");
+    header.push_str("const BTN_START: u32 = 10u;\n");
+    header.push_str("const BTN_SELECT: u32 = 11u;\n\n");
+
+    // Key constants — indices match winit KeyCode enum order / web e.code strings
+    header.push_str("// Key constants for @engine.keys[] — same on native and web\n");
+    header.push_str("const KEY_BACKQUOTE: u32 = 0u;\n");
+    header.push_str("const KEY_BACKSLASH: u32 = 1u;\n");
+    header.push_str("const KEY_BRACKET_LEFT: u32 = 2u;\n");
+    header.push_str("const KEY_BRACKET_RIGHT: u32 = 3u;\n");
+    header.push_str("const KEY_COMMA: u32 = 4u;\n");
+    header.push_str("const KEY_0: u32 = 5u;\n");
+    header.push_str("const KEY_1: u32 = 6u;\n");
+    header.push_str("const KEY_2: u32 = 7u;\n");
+    header.push_str("const KEY_3: u32 = 8u;\n");
+    header.push_str("const KEY_4: u32 = 9u;\n");
+    header.push_str("const KEY_5: u32 = 10u;\n");
+    header.push_str("const KEY_6: u32 = 11u;\n");
+    header.push_str("const KEY_7: u32 = 12u;\n");
+    header.push_str("const KEY_8: u32 = 13u;\n");
+    header.push_str("const KEY_9: u32 = 14u;\n");
+    header.push_str("const KEY_EQUAL: u32 = 15u;\n");
+    header.push_str("const KEY_INTL_BACKSLASH: u32 = 16u;\n");
+    header.push_str("const KEY_INTL_RO: u32 = 17u;\n");
+    header.push_str("const KEY_INTL_YEN: u32 = 18u;\n");
+    header.push_str("const KEY_A: u32 = 19u;\n");
+    header.push_str("const KEY_B: u32 = 20u;\n");
+    header.push_str("const KEY_C: u32 = 21u;\n");
+    header.push_str("const KEY_D: u32 = 22u;\n");
+    header.push_str("const KEY_E: u32 = 23u;\n");
+    header.push_str("const KEY_F: u32 = 24u;\n");
+    header.push_str("const KEY_G: u32 = 25u;\n");
+    header.push_str("const KEY_H: u32 = 26u;\n");
+    header.push_str("const KEY_I: u32 = 27u;\n");
+    header.push_str("const KEY_J: u32 = 28u;\n");
+    header.push_str("const KEY_K: u32 = 29u;\n");
+    header.push_str("const KEY_L: u32 = 30u;\n");
+    header.push_str("const KEY_M: u32 = 31u;\n");
+    header.push_str("const KEY_N: u32 = 32u;\n");
+    header.push_str("const KEY_O: u32 = 33u;\n");
+    header.push_str("const KEY_P: u32 = 34u;\n");
+    header.push_str("const KEY_Q: u32 = 35u;\n");
+    header.push_str("const KEY_R: u32 = 36u;\n");
+    header.push_str("const KEY_S: u32 = 37u;\n");
+    header.push_str("const KEY_T: u32 = 38u;\n");
+    header.push_str("const KEY_U: u32 = 39u;\n");
+    header.push_str("const KEY_V: u32 = 40u;\n");
+    header.push_str("const KEY_W: u32 = 41u;\n");
+    header.push_str("const KEY_X: u32 = 42u;\n");
+    header.push_str("const KEY_Y: u32 = 43u;\n");
+    header.push_str("const KEY_Z: u32 = 44u;\n");
+    header.push_str("const KEY_MINUS: u32 = 45u;\n");
+    header.push_str("const KEY_PERIOD: u32 = 46u;\n");
+    header.push_str("const KEY_QUOTE: u32 = 47u;\n");
+    header.push_str("const KEY_SEMICOLON: u32 = 48u;\n");
+    header.push_str("const KEY_SLASH: u32 = 49u;\n");
+    header.push_str("const KEY_ALT_LEFT: u32 = 50u;\n");
+    header.push_str("const KEY_ALT_RIGHT: u32 = 51u;\n");
+    header.push_str("const KEY_BACKSPACE: u32 = 52u;\n");
+    header.push_str("const KEY_CAPS_LOCK: u32 = 53u;\n");
+    header.push_str("const KEY_CONTEXT_MENU: u32 = 54u;\n");
+    header.push_str("const KEY_CTRL_LEFT: u32 = 55u;\n");
+    header.push_str("const KEY_CTRL_RIGHT: u32 = 56u;\n");
+    header.push_str("const KEY_ENTER: u32 = 57u;\n");
+    header.push_str("const KEY_SUPER_LEFT: u32 = 58u;\n");
+    header.push_str("const KEY_SUPER_RIGHT: u32 = 59u;\n");
+    header.push_str("const KEY_SHIFT_LEFT: u32 = 60u;\n");
+    header.push_str("const KEY_SHIFT_RIGHT: u32 = 61u;\n");
+    header.push_str("const KEY_SPACE: u32 = 62u;\n");
+    header.push_str("const KEY_TAB: u32 = 63u;\n");
+    header.push_str("const KEY_DELETE: u32 = 72u;\n");
+    header.push_str("const KEY_END: u32 = 73u;\n");
+    header.push_str("const KEY_HOME: u32 = 75u;\n");
+    header.push_str("const KEY_INSERT: u32 = 76u;\n");
+    header.push_str("const KEY_PAGE_DOWN: u32 = 77u;\n");
+    header.push_str("const KEY_PAGE_UP: u32 = 78u;\n");
+    header.push_str("const KEY_DOWN: u32 = 79u;\n");
+    header.push_str("const KEY_LEFT: u32 = 80u;\n");
+    header.push_str("const KEY_RIGHT: u32 = 81u;\n");
+    header.push_str("const KEY_UP: u32 = 82u;\n");
+    header.push_str("const KEY_ESCAPE: u32 = 114u;\n");
+    header.push_str("const KEY_F1: u32 = 159u;\n");
+    header.push_str("const KEY_F2: u32 = 160u;\n");
+    header.push_str("const KEY_F3: u32 = 161u;\n");
+    header.push_str("const KEY_F4: u32 = 162u;\n");
+    header.push_str("const KEY_F5: u32 = 163u;\n");
+    header.push_str("const KEY_F6: u32 = 164u;\n");
+    header.push_str("const KEY_F7: u32 = 165u;\n");
+    header.push_str("const KEY_F8: u32 = 166u;\n");
+    header.push_str("const KEY_F9: u32 = 167u;\n");
+    header.push_str("const KEY_F10: u32 = 168u;\n");
+    header.push_str("const KEY_F11: u32 = 169u;\n");
+    header.push_str("const KEY_F12: u32 = 170u;\n");
+    header.push('\n');
+
+    // Add bindings
+    header.push_str("// Bindings: group 0 = textures, group 1 = engine state\n\n");
+    header.push_str("@group(0) @binding(0) var _engine_sampler: sampler;\n");
+
+    for (i, tex) in metadata.textures.iter().enumerate() {
+        header.push_str(&format!("@group(0) @binding({}) var _texture_{}: texture_2d<f32>; // {}\n", i + 1, i, tex));
+    }
+
+    let video_base = metadata.textures.len() + 1;
+    for (i, vid) in metadata.videos.iter().enumerate() {
+        header.push_str(&format!(
+            "@group(0) @binding({}) var _video_{}: texture_2d<f32>; // {}\n",
+            video_base + i, i, vid
+        ));
+    }
+
+    let camera_base = metadata.textures.len() + metadata.videos.len() + 1;
+    for (i, cam) in metadata.cameras.iter().enumerate() {
+        header.push_str(&format!(
+            "@group(0) @binding({}) var _camera_{}: texture_2d<f32>; // camera {}\n",
+            camera_base + i, i, cam
+        ));
+    }
+
+    let buffer_base = camera_base + metadata.cameras.len();
+    for (i, pass) in metadata.passes.iter().enumerate() {
+        header.push_str(&format!(
+            "@group(0) @binding({}) var _buffer_{}: texture_2d<f32>; // @pass(\"{}\")\n",
+            buffer_base + i, i, pass.name
+        ));
+    }
+
+    let canvas_base = buffer_base + metadata.passes.len();
+    for (i, canvas) in metadata.canvases.iter().enumerate() {
+        header.push_str(&format!("@group(0) @binding({}) var _canvas_{}: texture_2d<f32>; // @canvas(\"{}\")\n", canvas_base + i, i, canvas.name));
+    }
+    let canvas_storage_base = canvas_base + metadata.canvases.len();
+    for (i, canvas) in metadata.canvases.iter().enumerate() {
+        header.push_str(&format!(
+            "@group(0) @binding({}) var _canvas_{}_storage: texture_storage_2d<{}, write>; // @canvas(\"{}\").storage\n",
+            canvas_storage_base + i, i, canvas.format, canvas.name
+        ));
+    }
+
+    header.push_str("\n@group(1) @binding(0) var<storage, read_write> _engine: GameEngineHost;\n");
+    header.push_str("@group(1) @binding(1) var<storage, read> _engine_view: _GameEngineView; // @engine in code the vertex stage runs\n");
+    for (i, buffer) in metadata.storage_buffers.iter().enumerate() {
+        header.push_str(&format!(
+            "@group(1) @binding({}) var<storage, read_write> _storage_{}: {}; // @storage(\"{}\")\n",
+            i + 2, i, buffer.ty, buffer.name
+        ));
+    }
+
+    if !metadata.passes.is_empty() {
+        header.push_str("\n// @pass() targets are drawn by one triangle that covers them, whatever vs_main draws\n");
+        header.push_str("struct _PassVertex {\n    @builtin(position) position: vec4f,\n    @location(0) uv: vec2f,\n}\n");
+        header.push_str(&format!(
+            "@vertex\nfn {}(@builtin(vertex_index) i: u32) -> _PassVertex {{\n    let p = vec2f(f32((i << 1u) & 2u), f32(i & 2u));\n    return _PassVertex(vec4f(p * 2.0 - 1.0, 0.0, 1.0), vec2f(p.x, 1.0 - p.y));\n}}\n",
+            PASS_VERTEX_ENTRY
+        ));
+    }
+
+    if !metadata.asserts.is_empty() {
+        header.push_str("\n// @assert(condition, \"message\") records a failure for wgsleng test to report\n");
+        header.push_str("fn _assert(id: u32, condition: bool) {\n    if (!condition) {\n        _engine.asserts[id] = 1u;\n    }\n}\n");
+    }
+
+    if !metadata.sounds.is_empty() {
+        header.push_str(&format!(
+            "\n// @sound(\"x\").play(), .loop() and .stop() queue a trigger for the runtime; past {} a frame they're only counted\n",
+            MAX_SOUND_TRIGGERS
+        ));
+        header.push_str(&format!(
+            "fn _sound(sound: u32, flags: u32, volume: f32, pitch: f32, pan: f32) {{\n    let i = atomicAdd(&_engine.sound_count, 1u);\n    if (i < {}u) {{\n        _engine.sound_triggers[i] = _SoundTrigger(sound, flags, volume, pitch, pan);\n    }}\n}}\n",
+            MAX_SOUND_TRIGGERS
+        ));
+    }
+
+    if !metadata.music.is_empty() {
+        header.push_str("\n// @music(\"x\").play(), .loop(), .pause() and .stop() leave a command for the runtime; the last one a frame wins\n");
+        header.push_str("fn _music(track: u32, command: u32, fade: f32) {\n    _engine.music_command = command;\n    _engine.music_command_track = track;\n    _engine.music_command_fade = fade;\n}\n");
+    }
+
+    if !metadata.prints.is_empty() {
+        let values = metadata.print_values();
+        header.push_str(&format!(
+            "\n// @print(\"format {{}}\", values...) claims a slot for the runtime to log; past {} a frame they're only counted\n",
+            MAX_PRINTS_PER_FRAME
+        ));
+        header.push_str(&format!("struct _PrintSlot {{\n    id: u32,\n    values: array<f32, {}>,\n}}\n", values));
+        header.push_str(&format!(
+            "struct _Prints {{\n    count: atomic<u32>,\n    slots: array<_PrintSlot, {}>,\n}}\n",
+            MAX_PRINTS_PER_FRAME
+        ));
+        header.push_str(&format!(
+            "@group(1) @binding({}) var<storage, read_write> _prints: _Prints;\n",
+            2 + metadata.storage_buffers.len()
+        ));
+        header.push_str(&format!(
+            "fn _print(id: u32, values: array<f32, {}>) {{\n    let slot = atomicAdd(&_prints.count, 1u);\n    if (slot < {}u) {{\n        _prints.slots[slot] = _PrintSlot(id, values);\n    }}\n}}\n",
+            values, MAX_PRINTS_PER_FRAME
+        ));
+    }
+
+    // Add model buffers
+    if !metadata.models.is_empty() {
+        header.push_str("\n// Model data buffers\n");
+        for (i, model) in metadata.models.iter().enumerate() {
+            let binding_base = 1 + i * 2;
+            header.push_str(&format!("struct Model{}Positions {{ data: array<vec3f> }}\n", i));
+            header.push_str(&format!("@group(2) @binding({}) var<storage, read> _model_{}_positions: Model{}Positions; // {}\n", binding_base, i, i, model));
+
+            header.push_str(&format!("struct Model{}Normals {{ data: array<vec3f> }}\n", i));
+            header.push_str(&format!("@group(2) @binding({}) var<storage, read> _model_{}_normals: Model{}Normals;\n", binding_base + 1, i, i));
+        }
+    }
+
+    header.push('\n');
+    header
 }
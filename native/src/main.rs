@@ -1,7 +1,6 @@
 use std::io::Cursor;
 use std::sync::Arc;
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
-use wgpu::util::DeviceExt;
+use rodio::OutputStream;
 use winit::{
     application::ApplicationHandler,
     event::*,
@@ -9,11 +8,17 @@ use winit::{
     keyboard::{KeyCode, PhysicalKey},
     window::{Window, WindowId},
 };
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand};
+
+mod events;
+mod game;
+mod headless;
+mod mixer;
+mod music;
+mod sound;
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use rosc::{OscPacket, OscType};
-use std::collections::HashMap;
-use wgsleng::{GameSource, PreprocessorState, OSC_FLOAT_COUNT, KEY_ARRAY_SIZE, keycode_index,
+use wgsleng::{GameSource, PreprocessorOptions, PreprocessorState, OSC_FLOAT_COUNT, KEY_ARRAY_SIZE, keycode_index,
     BTN_UP, BTN_DOWN, BTN_LEFT, BTN_RIGHT, BTN_A, BTN_B, BTN_X, BTN_Y, BTN_L, BTN_R, BTN_START, BTN_SELECT};
 
 enum OscMessage {
@@ -25,14 +30,20 @@ enum OscMessage {
     LoadShader(String),
     /// /reload
     Reload,
+    /// /reset
+    Reset,
 }
 
 #[derive(Parser, Debug)]
 #[command(name = "wgsl-game")]
 #[command(about = "Run WGSL shader games from directory or zip file")]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path to game.wgsl file or .zip containing main.wgsl
-    game_path: String,
+    game_path: Option<String>,
 
     /// Watch for file changes and hot-reload shader/textures (directory sources only)
     #[arg(long, short = 'r')]
@@ -41,6 +52,111 @@ struct Args {
     /// Listen for OSC messages on this UDP port (e.g. --osc-port 9000)
     #[arg(long)]
     osc_port: Option<u16>,
+
+    /// Directory for @persist save files (default: the platform's per-user data directory)
+    #[arg(long, value_name = "DIR")]
+    save_dir: Option<std::path::PathBuf>,
+
+    /// Record every frame's dt and input to FILE, for replaying the session with --replay
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["replay", "hot_reload"])]
+    record: Option<std::path::PathBuf>,
+
+    /// Play back a session recorded with --record instead of live input, then hand over to live input
+    #[arg(long, value_name = "FILE", conflicts_with = "hot_reload")]
+    replay: Option<std::path::PathBuf>,
+
+    /// Seconds per frame in place of the clock, e.g. 1/60, for every frame of a --record or --replay too
+    /// (default: the clock, or a replay's recorded ones)
+    #[arg(long, value_name = "SECONDS", value_parser = parse_dt)]
+    fixed_dt: Option<f32>,
+
+    /// Define a symbol for @if() in the game (repeatable, VALUE defaults to 1)
+    #[arg(long = "define", short = 'D', value_name = "NAME=VALUE", value_parser = parse_define, global = true)]
+    defines: Vec<(String, String)>,
+
+    /// Extra directory to search for std: modules and assets (repeatable, searched before the built-in library)
+    #[arg(long = "lib-path", value_name = "DIR", global = true)]
+    lib_paths: Vec<std::path::PathBuf>,
+}
+
+fn parse_define(arg: &str) -> Result<(String, String), String> {
+    let (name, value) = arg.split_once('=').unwrap_or((arg, "1"));
+    let valid = name.starts_with(|c: char| c == '_' || c.is_alphabetic()) && name.chars().all(|c| c == '_' || c.is_alphanumeric());
+    if !valid {
+        return Err(format!("invalid symbol name '{}'", name));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// A frame time in seconds, as a number or a fraction like 1/60
+fn parse_dt(arg: &str) -> Result<f32, String> {
+    let dt = match arg.split_once('/') {
+        Some((n, d)) => n.trim().parse::<f32>().ok().zip(d.trim().parse::<f32>().ok()).map(|(n, d)| n / d),
+        None => arg.trim().parse().ok(),
+    };
+    dt.filter(|dt| dt.is_finite() && *dt >= 0.0).ok_or_else(|| format!("'{}' isn't a frame time like 1/60 or 0.016", arg))
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Validate games with naga without opening a window or needing a GPU
+    Check {
+        /// Games to check (.wgsl file, directory or .zip)
+        #[arg(required = true)]
+        games: Vec<String>,
+    },
+    /// Run a game; with --headless, offscreen for a set number of frames, writing some of them as PNGs
+    Run {
+        /// Game to run (.wgsl file, directory or .zip)
+        game: String,
+        /// Render offscreen instead of in a window, logging sounds instead of playing them
+        #[arg(long)]
+        headless: bool,
+        /// Frames to run (default: the length of --input, otherwise 1)
+        #[arg(long, requires = "headless")]
+        frames: Option<usize>,
+        /// Seconds per frame, e.g. 1/60 or 0.02 (default: the recorded ones with --input, otherwise 1/60 headless and the clock in a window)
+        #[arg(long, value_name = "SECONDS", value_parser = parse_dt)]
+        fixed_dt: Option<f32>,
+        /// Input recorded with --record, replayed from the first frame
+        #[arg(long, value_name = "FILE")]
+        input: Option<std::path::PathBuf>,
+        /// Directory for the PNGs, named frame_00060.png and so on
+        #[arg(long, value_name = "DIR", default_value = "frames", requires = "headless")]
+        out: std::path::PathBuf,
+        /// Frame numbers (from 1) to write, e.g. 1,60,600 (default: the last frame)
+        #[arg(long, value_name = "FRAMES", value_delimiter = ',', requires = "headless")]
+        capture: Vec<usize>,
+        /// Also write every Nth frame
+        #[arg(long, value_name = "N", requires = "headless")]
+        every: Option<usize>,
+        /// Use a software adapter (lavapipe, llvmpipe, WARP), e.g. on CI machines without a GPU
+        #[arg(long, requires = "headless")]
+        software: bool,
+    },
+    /// Run the golden-image tests in each game's tests.json headless and compare the frames with their PNGs
+    Test {
+        /// Games to test (.wgsl file or directory, with tests.json next to it)
+        #[arg(required = true)]
+        games: Vec<String>,
+        /// Write the frames as the goldens where they're missing or don't match, instead of failing
+        #[arg(long)]
+        update: bool,
+        /// Use a software adapter (lavapipe, llvmpipe, WARP), e.g. on CI machines without a GPU
+        #[arg(long)]
+        software: bool,
+    },
+    /// Write the preprocessed WGSL and the metadata (assets, bindings, buffer layout) as JSON
+    Inspect {
+        /// Game to inspect (.wgsl file, directory or .zip)
+        game: String,
+        /// Where to write the preprocessed shader ("-" for stdout)
+        #[arg(long, value_name = "FILE", default_value = "shader.wgsl")]
+        wgsl: String,
+        /// Where to write the metadata JSON ("-" for stdout)
+        #[arg(long, value_name = "FILE", default_value = "metadata.json")]
+        json: String,
+    },
 }
 
 // All preprocessing logic is now in lib.rs
@@ -66,7 +182,7 @@ enum CameraSourceRuntime {
         height: u32,
         stop: Arc<std::sync::atomic::AtomicBool>,
     },
-    Black(u32, u32),
+    Black,
 }
 
 fn load_gif_source(data: &[u8]) -> Result<(VideoSourceRuntime, u32, u32), Box<dyn std::error::Error>> {
@@ -79,7 +195,7 @@ fn load_gif_source(data: &[u8]) -> Result<(VideoSourceRuntime, u32, u32), Box<dy
     for frame_result in decoder.into_frames() {
         let frame = frame_result?;
         let (numer, denom) = frame.delay().numer_denom_ms();
-        let delay_ms = if denom == 0 { 100 } else { (numer / denom).max(10) };
+        let delay_ms = numer.checked_div(denom).map_or(100, |ms| ms.max(10));
         let img = frame.into_buffer();
         width = img.width();
         height = img.height();
@@ -260,59 +376,159 @@ fn open_camera_source(cam_idx: u32) -> (CameraSourceRuntime, u32, u32) {
     }
     #[cfg(not(feature = "camera"))]
     eprintln!("[camera] camera feature not enabled for camera index {}", cam_idx);
-    (CameraSourceRuntime::Black(640, 480), 640, 480)
+    (CameraSourceRuntime::Black, 640, 480)
 }
 
 struct State {
     window: Arc<Window>,
-    title: String,
     surface: wgpu::Surface<'static>,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
-    size: winit::dpi::PhysicalSize<u32>,
-    compute_pipeline: wgpu::ComputePipeline,
-    render_pipeline: wgpu::RenderPipeline,
-    empty_bind_group: wgpu::BindGroup,
-    compute_bind_group: wgpu::BindGroup,
-    render_bind_group0: wgpu::BindGroup,
-    render_bind_group1: wgpu::BindGroup,
-    render_bind_group2: Option<wgpu::BindGroup>,
-    engine_buffer: wgpu::Buffer,
-    staging_buffer: wgpu::Buffer,
-    buffer_offsets: BufferOffsets,
+    game: game::Game,
+    // Where @persist save files go
+    saves: Saves,
+    session: Session,
     buttons: [i32; 12],
     mouse: [f32; 4], // [pos_x, pos_y, click_x (neg=not pressed), click_y]
     keys: [u32; KEY_ARRAY_SIZE], // raw key state indexed by DOM keycode
     last_time: std::time::Instant,
     time: f32,
-    model_vertex_count: usize,
-    depth_texture: wgpu::Texture,
-    depth_view: wgpu::TextureView,
+    delta_time: f32,
+    // Seconds per frame in place of the clock and a replay's recorded ones, from --fixed-dt
+    fixed_dt: Option<f32>,
+    // Set by @engine.quit(), closing the window after this frame
+    quit: bool,
     _stream: OutputStream,
-    stream_handle: OutputStreamHandle,
-    sound_buffers: Vec<Vec<u8>>,
-    audio_count: usize,
-    // For hot-reload state preservation
-    engine_buffer_size: usize,
-    // OSC name → osc slot index mapping (populated from @osc("name") in shader)
-    osc_name_map: HashMap<String, usize>,
-    // Dynamic video textures
-    video_textures: Vec<wgpu::Texture>,
-    video_sources: Vec<VideoSourceRuntime>,
-    video_filenames: Vec<String>,
-    // Dynamic camera textures
-    camera_textures: Vec<wgpu::Texture>,
-    camera_sources: Vec<CameraSourceRuntime>,
+    // Everything audible plays through it, tapped for @engine.spectrum
+    mixer: mixer::Mixer,
 }
 
 struct BufferOffsets {
     buttons: u64,
     floats: u64,
     state: u64,
-    audio: u64,
+    state_size: u64,
+    sounds: u64,
+    sound_voices: u64,
+    music: u64,
+    music_command: u64,
+    audio_level: u64,
     osc_floats: u64,
     keys: u64,
+    reset: u64,
+}
+
+impl BufferOffsets {
+    /// Offsets of the GameEngineHost members, as laid out by the preprocessor
+    fn new(metadata: &wgsleng::Metadata) -> Self {
+        let offset = |member: &str| metadata.engine_offset(member).map(|o| o as u64);
+        let osc_floats = offset("osc").unwrap_or(0);
+        Self {
+            buttons: offset("buttons").unwrap_or(0),
+            floats: offset("time").unwrap_or(0),
+            state: offset("state").unwrap_or(osc_floats),
+            state_size: metadata.state_size as u64,
+            sounds: offset("sound_count").unwrap_or(osc_floats),
+            sound_voices: offset("sound_voices").unwrap_or(osc_floats),
+            music: offset("music_time").unwrap_or(osc_floats),
+            music_command: offset("music_command").unwrap_or(osc_floats),
+            audio_level: offset("audio_level").unwrap_or(osc_floats),
+            osc_floats,
+            keys: offset("keys").unwrap_or(0),
+            reset: offset("reset").unwrap_or(0),
+        }
+    }
+}
+
+/// Copy `size` bytes of a buffer back from the GPU, waiting for them (zeros if mapping fails)
+fn read_buffer(device: &wgpu::Device, queue: &wgpu::Queue, buffer: &wgpu::Buffer, offset: u64, size: usize) -> Vec<u8> {
+    if size == 0 {
+        return Vec::new();
+    }
+
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Engine Readback"),
+        size: size as u64,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_buffer_to_buffer(buffer, offset, &readback, 0, size as u64);
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = readback.slice(..);
+    let (tx, rx) = futures::channel::oneshot::channel();
+    slice.map_async(wgpu::MapMode::Read, move |r| { let _ = tx.send(r); });
+    device.poll(wgpu::Maintain::Wait);
+
+    if let Ok(Ok(())) = pollster::block_on(rx) {
+        let data = slice.get_mapped_range();
+        let result = data.to_vec();
+        drop(data);
+        readback.unmap();
+        result
+    } else {
+        vec![0u8; size]
+    }
+}
+
+
+/// Where @engine.save() and @engine.load() go
+enum Saves {
+    /// Save files in this directory
+    Dir(std::path::PathBuf),
+    /// A replay's saves, kept in memory starting from the @persist data it was recorded with,
+    /// so it neither sees saves made since nor overwrites them
+    Replay(Option<Vec<u8>>),
+}
+
+impl Saves {
+    /// The @persist bytes in a game's save file, if it has one written for this layout
+    fn read(&self, save: &wgsleng::save::SaveFile) -> Option<Vec<u8>> {
+        let dir = match self {
+            Saves::Dir(dir) => dir,
+            Saves::Replay(data) => return data.clone().filter(|data| data.len() == save.size),
+        };
+        let path = dir.join(format!("{}.json", save.key));
+        let contents = std::fs::read_to_string(&path).ok()?;
+        match save.decode(&contents) {
+            Ok(data) => {
+                println!("[save] loaded {}", path.display());
+                Some(data)
+            }
+            Err(e) => {
+                eprintln!("[save] ignoring {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Write the @persist bytes to the game's save file
+    fn write(&mut self, save: &wgsleng::save::SaveFile, data: &[u8]) {
+        let dir = match self {
+            Saves::Dir(dir) => dir,
+            Saves::Replay(saved) => {
+                *saved = Some(data.to_vec());
+                return;
+            }
+        };
+        let path = dir.join(format!("{}.json", save.key));
+        match std::fs::create_dir_all(&*dir).and_then(|_| std::fs::write(&path, save.encode(data))) {
+            Ok(()) => println!("[save] wrote {}", path.display()),
+            Err(e) => eprintln!("[save] failed to write {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// --record or --replay
+enum Session {
+    Live,
+    /// Input that doesn't show in the State's button, key and mouse arrays (OSC values, F2 and
+    /// OSC resets) is collected until the frame it lands in is written
+    Record { recorder: wgsleng::replay::Recorder, osc: Vec<(usize, f32)>, reset: bool },
+    Replay { replay: wgsleng::replay::Replay, frame: usize },
 }
 
 /// Map a winit physical KeyCode to its canonical index (shared with web via e.code).
@@ -518,9 +734,7 @@ fn winit_key_index(key: &KeyCode) -> Option<usize> {
 }
 
 impl State {
-    async fn new(window: Arc<Window>, mut game_source: GameSource, entry_file: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let _size = window.inner_size();
-
+    async fn new(window: Arc<Window>, game_source: GameSource, entry_file: &str, options: &PreprocessorOptions, saves: Saves) -> Result<Self, wgsleng::Error> {
         // Initialize WebGPU
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::all(),
@@ -559,28 +773,23 @@ impl State {
             .copied()
             .unwrap_or(surface_caps.formats[0]);
 
-        // Load and preprocess shader
-        let shader_code = game_source.read_text(entry_file)?;
-        let mut preprocessor = PreprocessorState::new(game_source);
-        let (processed_code, metadata) = preprocessor.preprocess_shader(&shader_code, true)?;
-
-        // Debug: print processed shader
-        if std::env::var("DEBUG_SHADER").is_ok() {
-            println!("\n=== PROCESSED SHADER ===");
-            println!("{}", processed_code);
-            println!("=== END SHADER ===\n");
-        }
+        // Load the game at the size it asks for, playing through the default output device
+        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
+        let mixer = mixer::Mixer::new(&stream_handle);
+        let preprocessor = PreprocessorState::new(game_source).with_entry_file(entry_file).with_options(options);
+        let target = game::Target { format: surface_format, size: None };
+        let game = game::Game::load(&device, &queue, preprocessor, &target, Some(&mixer), &saves, None).await?;
 
-        println!("Game: {}", metadata.title);
-        println!("Size: {}x{}", metadata.width, metadata.height);
-        println!("Textures: {:?}", metadata.textures);
-        println!("Sounds: {:?}", metadata.sounds);
+        println!("Game: {}", game.metadata.title);
+        println!("Size: {}x{}", game.size.width, game.size.height);
+        println!("Textures: {:?}", game.metadata.textures);
+        println!("Sounds: {:?}", game.metadata.sounds);
 
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
-            width: metadata.width,
-            height: metadata.height,
+            width: game.size.width,
+            height: game.size.height,
             present_mode: wgpu::PresentMode::Fifo,
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
@@ -588,589 +797,41 @@ impl State {
         };
         surface.configure(&device, &config);
 
-        // Create depth texture for 3D rendering
-        let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Depth Texture"),
-            size: wgpu::Extent3d {
-                width: metadata.width,
-                height: metadata.height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth24Plus,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[],
-        });
-
-        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-        // Load audio
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-        let mut sound_buffers = Vec::new();
-        for sound_file in &metadata.sounds {
-            let data = preprocessor.game_source.read_file(sound_file)?;
-            sound_buffers.push(data);
-        }
-
-        // Load models
-        let mut models = Vec::new();
-        let mut model_vertex_counts = Vec::new();
-        for model_file in &metadata.models {
-            let model_data = preprocessor.game_source.read_file(model_file)?;
-            let model_path = std::path::PathBuf::from(model_file);
-
-            // Write to temp file for OBJ loader
-            let temp_path = std::env::temp_dir().join(model_path.file_name().unwrap());
-            std::fs::write(&temp_path, model_data)?;
-
-            let model = wgsleng::ObjModel::load(&temp_path)?;
-            model_vertex_counts.push(model.vertex_count());
-
-            // Create positions buffer
-            // IMPORTANT: array<vec3f> in WGSL storage buffers has 16-byte alignment (like vec4)
-            let positions_data: Vec<f32> = model.positions.iter()
-                .flat_map(|p| [p[0], p[1], p[2], 0.0]) // Add padding
-                .collect();
-
-            let positions_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Model Positions"),
-                contents: bytemuck::cast_slice(&positions_data),
-                usage: wgpu::BufferUsages::STORAGE,
-            });
-
-            // Create normals buffer
-            // Same padding required for normals
-            let normals_data: Vec<f32> = model.normals.iter()
-                .flat_map(|n| [n[0], n[1], n[2], 0.0]) // Add padding
-                .collect();
-
-            let normals_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Model Normals"),
-                contents: bytemuck::cast_slice(&normals_data),
-                usage: wgpu::BufferUsages::STORAGE,
-            });
-
-            models.push((positions_buffer, normals_buffer));
-        }
-
-        // Load textures
-        let mut textures = Vec::new();
-        for texture_file in &metadata.textures {
-            let img_data = preprocessor.game_source.read_file(texture_file)?;
-            let img = image::load_from_memory(&img_data)?.to_rgba8();
-            let dimensions = img.dimensions();
-
-            let texture_size = wgpu::Extent3d {
-                width: dimensions.0,
-                height: dimensions.1,
-                depth_or_array_layers: 1,
-            };
-
-            let texture = device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("Game Texture"),
-                size: texture_size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8Unorm,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
-            });
-
-            queue.write_texture(
-                wgpu::ImageCopyTexture {
-                    texture: &texture,
-                    mip_level: 0,
-                    origin: wgpu::Origin3d::ZERO,
-                    aspect: wgpu::TextureAspect::All,
-                },
-                &img,
-                wgpu::ImageDataLayout {
-                    offset: 0,
-                    bytes_per_row: Some(4 * dimensions.0),
-                    rows_per_image: Some(dimensions.1),
-                },
-                texture_size,
-            );
-
-            textures.push(texture);
-        }
-
-        // Load video sources
-        let mut video_textures = Vec::new();
-        let mut video_sources: Vec<VideoSourceRuntime> = Vec::new();
-        for video_file in &metadata.videos {
-            let data = preprocessor.game_source.read_file(video_file)?;
-            let (source, vid_w, vid_h) = load_video_source(video_file, data);
-            let (init_data, vid_w, vid_h) = match &source {
-                VideoSourceRuntime::Gif { frames, width, height, current_frame, .. } =>
-                    (frames[*current_frame].0.clone(), *width, *height),
-                VideoSourceRuntime::Black(w, h) =>
-                    (vec![0u8; (*w * *h * 4) as usize], *w, *h),
-            };
-            let tex_size = wgpu::Extent3d { width: vid_w, height: vid_h, depth_or_array_layers: 1 };
-            let tex = device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("Video Texture"),
-                size: tex_size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8Unorm,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
-            });
-            queue.write_texture(
-                wgpu::ImageCopyTexture { texture: &tex, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
-                &init_data,
-                wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(4 * vid_w), rows_per_image: Some(vid_h) },
-                tex_size,
-            );
-            video_textures.push(tex);
-            video_sources.push(source);
-        }
-
-        // Open camera sources
-        let mut camera_textures = Vec::new();
-        let mut camera_sources: Vec<CameraSourceRuntime> = Vec::new();
-        for &cam_idx in &metadata.cameras {
-            let (source, cam_w, cam_h) = open_camera_source(cam_idx);
-            let black_data = vec![0u8; (cam_w * cam_h * 4) as usize];
-            let cam_size = wgpu::Extent3d { width: cam_w, height: cam_h, depth_or_array_layers: 1 };
-            let tex = device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("Camera Texture"),
-                size: cam_size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8Unorm,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
-            });
-            queue.write_texture(
-                wgpu::ImageCopyTexture { texture: &tex, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
-                &black_data,
-                wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(4 * cam_w), rows_per_image: Some(cam_h) },
-                cam_size,
-            );
-            camera_textures.push(tex);
-            camera_sources.push(source);
-        }
-
-        // Create sampler
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-
-        // Calculate buffer layout matching WGSL struct
-        let button_size = 12 * 4; // 48 bytes
-        let float_data_size = 8 * 4; // 32 bytes (time, delta, width, height + mouse vec4f)
-        // metadata.state_size is already aligned to the struct's own alignment by the preprocessor
-        let aligned_state_size = metadata.state_size;
-        let audio_size = metadata.sounds.len() * 4;
-        let osc_floats_offset = button_size + float_data_size + aligned_state_size + audio_size;
-        let keys_offset = osc_floats_offset + OSC_FLOAT_COUNT * 4;
-        let total_size_unaligned = keys_offset + KEY_ARRAY_SIZE * 4;
-        let total_size = ((total_size_unaligned + 15) / 16) * 16;
-
-        let buffer_offsets = BufferOffsets {
-            buttons: 0,
-            floats: button_size as u64,
-            state: (button_size + float_data_size) as u64,
-            audio: (button_size + float_data_size + aligned_state_size) as u64,
-            osc_floats: osc_floats_offset as u64,
-            keys: keys_offset as u64,
-        };
-
-
-        // Create engine buffer
-        let mut init_data = vec![0u8; total_size];
-
-        // Initialize screen size in floats section
-        let width_bytes = (metadata.width as f32).to_le_bytes();
-        let height_bytes = (metadata.height as f32).to_le_bytes();
-        init_data[buffer_offsets.floats as usize + 8..buffer_offsets.floats as usize + 12].copy_from_slice(&width_bytes);
-        init_data[buffer_offsets.floats as usize + 12..buffer_offsets.floats as usize + 16].copy_from_slice(&height_bytes);
-
-        // Initialize player position to center in state section
-        let center_x = ((metadata.width / 2) as f32).to_le_bytes();
-        let center_y = ((metadata.height / 2) as f32).to_le_bytes();
-        init_data[buffer_offsets.state as usize..buffer_offsets.state as usize + 4].copy_from_slice(&center_x);
-        init_data[buffer_offsets.state as usize + 4..buffer_offsets.state as usize + 8].copy_from_slice(&center_y);
-
-        let engine_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Engine Buffer"),
-            contents: &init_data,
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
-        });
-
-        // Create staging buffer for reading audio triggers
-        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Staging Buffer"),
-            size: total_size as u64,
-            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-
-        // Create shader module
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Game Shader"),
-            source: wgpu::ShaderSource::Wgsl(processed_code.into()),
-        });
-
-        // Create explicit bind group layouts for render pipeline
-        // Group 0: sampler (always) and textures (if present)
-        let mut render_group0_entries = vec![
-            // Sampler (always present since preprocessor always adds it)
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
-            }
-        ];
-
-        // Add texture bindings if present
-        for i in 0..metadata.textures.len() {
-            render_group0_entries.push(wgpu::BindGroupLayoutEntry {
-                binding: (i + 1) as u32,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
-                },
-                count: None,
-            });
-        }
-
-        // Add video texture bindings
-        let video_base = metadata.textures.len() + 1;
-        for i in 0..metadata.videos.len() {
-            render_group0_entries.push(wgpu::BindGroupLayoutEntry {
-                binding: (video_base + i) as u32,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
-                },
-                count: None,
-            });
-        }
-
-        // Add camera texture bindings
-        let camera_base = metadata.textures.len() + metadata.videos.len() + 1;
-        for i in 0..metadata.cameras.len() {
-            render_group0_entries.push(wgpu::BindGroupLayoutEntry {
-                binding: (camera_base + i) as u32,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
-                },
-                count: None,
-            });
-        }
-
-        let render_bind_group_layout0 = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Render Bind Group Layout 0"),
-            entries: &render_group0_entries,
-        });
-
-        // Group 1: engine buffer (read_write for fragment, not accessible in vertex)
-        let render_bind_group_layout1 = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Render Bind Group Layout 1"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: false },
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-        });
-
-        // Group 2: model buffers (positions and normals for each model)
-        let mut model_group_entries = Vec::new();
-        for i in 0..metadata.models.len() {
-            let binding_base = 1 + i * 2;
-            // Positions buffer
-            model_group_entries.push(wgpu::BindGroupLayoutEntry {
-                binding: binding_base as u32,
-                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: true },
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            });
-            // Normals buffer
-            model_group_entries.push(wgpu::BindGroupLayoutEntry {
-                binding: (binding_base + 1) as u32,
-                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: true },
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            });
-        }
-
-        let render_bind_group_layout2 = if !model_group_entries.is_empty() {
-            Some(device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Render Bind Group Layout 2"),
-                entries: &model_group_entries,
-            }))
-        } else {
-            None
-        };
-
-        let mut render_layouts: Vec<&wgpu::BindGroupLayout> = vec![&render_bind_group_layout0, &render_bind_group_layout1];
-        if let Some(ref layout2) = render_bind_group_layout2 {
-            render_layouts.push(layout2);
-        }
-
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &render_layouts,
-            push_constant_ranges: &[],
-        });
-
-        // Create pipelines
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_render"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                ..Default::default()
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth24Plus,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-            cache: None,
-        });
-
-        // Create explicit bind group layouts for compute shader
-        // Compute shader uses @group(1) @binding(0) for the engine buffer
-        // We need a placeholder for @group(0) since the shader uses @group(1)
-        let empty_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Empty Bind Group Layout"),
-            entries: &[],
-        });
-
-        let compute_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Compute Bind Group Layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: false },
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-        });
-
-        let compute_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Compute Pipeline Layout"),
-            bind_group_layouts: &[&empty_bind_group_layout, &compute_bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
-        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Compute Pipeline"),
-            layout: Some(&compute_pipeline_layout),
-            module: &shader,
-            entry_point: Some("update"),
-            compilation_options: Default::default(),
-            cache: None,
-        });
-
-        // Create texture views first (need to store them to avoid temporary borrow)
-        let texture_views: Vec<_> = textures.iter()
-            .map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()))
-            .collect();
-        let video_texture_views: Vec<_> = video_textures.iter()
-            .map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()))
-            .collect();
-        let camera_texture_views: Vec<_> = camera_textures.iter()
-            .map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()))
-            .collect();
-
-        // Create bind groups
-        // Group 0 always includes sampler (since preprocessor always adds it)
-        let mut group0_entries = vec![
-            wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::Sampler(&sampler),
-            }
-        ];
-
-        // Add texture views if present
-        for (i, view) in texture_views.iter().enumerate() {
-            group0_entries.push(wgpu::BindGroupEntry {
-                binding: (i + 1) as u32,
-                resource: wgpu::BindingResource::TextureView(view),
-            });
-        }
-
-        // Add video texture views
-        for (i, view) in video_texture_views.iter().enumerate() {
-            group0_entries.push(wgpu::BindGroupEntry {
-                binding: (video_base + i) as u32,
-                resource: wgpu::BindingResource::TextureView(view),
-            });
-        }
-
-        // Add camera texture views
-        for (i, view) in camera_texture_views.iter().enumerate() {
-            group0_entries.push(wgpu::BindGroupEntry {
-                binding: (camera_base + i) as u32,
-                resource: wgpu::BindingResource::TextureView(view),
-            });
-        }
-
-        let render_bind_group0 = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Render Bind Group 0"),
-            layout: &render_bind_group_layout0,
-            entries: &group0_entries,
-        });
-
-        let render_bind_group1 = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Render Bind Group 1"),
-            layout: &render_bind_group_layout1,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: engine_buffer.as_entire_binding(),
-            }],
-        });
-
-        // Create model bind group if models exist
-        let render_bind_group2 = if let Some(ref layout2) = render_bind_group_layout2 {
-            let mut model_entries = Vec::new();
-            for (i, (positions_buf, normals_buf)) in models.iter().enumerate() {
-                let binding_base = 1 + i * 2;
-                model_entries.push(wgpu::BindGroupEntry {
-                    binding: binding_base as u32,
-                    resource: positions_buf.as_entire_binding(),
-                });
-                model_entries.push(wgpu::BindGroupEntry {
-                    binding: (binding_base + 1) as u32,
-                    resource: normals_buf.as_entire_binding(),
-                });
-            }
-            Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Render Bind Group 2"),
-                layout: layout2,
-                entries: &model_entries,
-            }))
-        } else {
-            None
-        };
-
-        // Create empty bind group for @group(0) (compute shader doesn't use it but layout requires it)
-        let empty_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Empty Bind Group"),
-            layout: &empty_bind_group_layout,
-            entries: &[],
-        });
-
-        // Create bind group for compute pipeline using explicit layout
-        let compute_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Compute Bind Group"),
-            layout: &compute_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: engine_buffer.as_entire_binding(),
-            }],
-        });
-
         Ok(Self {
             window,
-            title: metadata.title.clone(),
             surface,
             device,
             queue,
             config,
-            size: winit::dpi::PhysicalSize::new(metadata.width, metadata.height),
-            compute_pipeline,
-            render_pipeline,
-            empty_bind_group,
-            compute_bind_group,
-            render_bind_group0,
-            render_bind_group1,
-            render_bind_group2,
-            engine_buffer,
-            staging_buffer,
-            buffer_offsets,
+            game,
+            saves,
+            session: Session::Live,
             buttons: [0; 12],
             mouse: [0.0f32; 4],
             keys: [0u32; KEY_ARRAY_SIZE],
             last_time: std::time::Instant::now(),
             time: 0.0,
-            model_vertex_count: model_vertex_counts.get(0).copied().unwrap_or(0),
-            depth_texture,
-            depth_view,
+            delta_time: 0.0,
+            fixed_dt: None,
+            quit: false,
             _stream,
-            stream_handle,
-            sound_buffers,
-            audio_count: metadata.sounds.len(),
-            engine_buffer_size: total_size,
-            osc_name_map: metadata.osc_params.iter().cloned().zip(0..).collect(),
-            video_textures,
-            video_sources,
-            video_filenames: metadata.videos.clone(),
-            camera_textures,
-            camera_sources,
+            mixer,
         })
     }
 
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
-            self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
+            self.game.resize(&self.device, new_size);
         }
     }
-
     fn input(&mut self, event: &WindowEvent) -> bool {
+        // A replay is the only input until it runs out
+        if matches!(self.session, Session::Replay { .. }) {
+            return false;
+        }
         match event {
             WindowEvent::KeyboardInput {
                 event:
@@ -1198,6 +859,7 @@ impl State {
                     KeyCode::KeyW => self.buttons[BTN_R] = value,
                     KeyCode::Enter => self.buttons[BTN_START] = value,
                     KeyCode::ShiftLeft | KeyCode::ShiftRight => self.buttons[BTN_SELECT] = value,
+                    KeyCode::F2 if pressed => self.host_reset(),
                     _ => {}
                 }
 
@@ -1235,800 +897,225 @@ impl State {
         }
     }
 
-    fn update_dynamic_textures(&mut self, dt_secs: f32) {
-        // Update GIF video frames
-        for i in 0..self.video_sources.len() {
-            let maybe_write: Option<(Vec<u8>, u32, u32)> = match &mut self.video_sources[i] {
-                VideoSourceRuntime::Gif { frames, width, height, current_frame, frame_elapsed_ms } => {
-                    *frame_elapsed_ms += dt_secs * 1000.0;
-                    let prev = *current_frame;
-                    loop {
-                        let delay = frames[*current_frame].1 as f32;
-                        if *frame_elapsed_ms >= delay {
-                            *frame_elapsed_ms -= delay;
-                            *current_frame = (*current_frame + 1) % frames.len();
-                        } else {
-                            break;
-                        }
-                    }
-                    // Only upload when the frame actually changed
-                    if *current_frame != prev {
-                        Some((frames[*current_frame].0.clone(), *width, *height))
-                    } else {
-                        None
-                    }
-                }
-                VideoSourceRuntime::Black(_, _) => None,
-            };
-            if let Some((data, w, h)) = maybe_write {
-                self.queue.write_texture(
-                    wgpu::ImageCopyTexture { texture: &self.video_textures[i], mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
-                    &data,
-                    wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(4 * w), rows_per_image: Some(h) },
-                    wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
-                );
-            }
-        }
-
-        // Update camera frames
-        for i in 0..self.camera_sources.len() {
-            let maybe_write: Option<(Vec<u8>, u32, u32)> = match &mut self.camera_sources[i] {
-                #[cfg(feature = "camera")]
-                CameraSourceRuntime::Live { latest_frame, width, height, .. } => {
-                    latest_frame.try_lock().ok()
-                        .and_then(|mut g| g.take())
-                        .map(|data| (data, *width, *height))
-                }
-                CameraSourceRuntime::Black(_, _) => None,
-            };
-            if let Some((data, w, h)) = maybe_write {
-                self.queue.write_texture(
-                    wgpu::ImageCopyTexture { texture: &self.camera_textures[i], mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
-                    &data,
-                    wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(4 * w), rows_per_image: Some(h) },
-                    wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
-                );
-            }
-        }
-    }
-
     fn update(&mut self) {
         let now = std::time::Instant::now();
-        let dt = (now - self.last_time).as_secs_f32();
-        let dt = dt.min(0.1);
+        let mut dt = (now - self.last_time).as_secs_f32();
+        dt = dt.min(0.1);
         self.last_time = now;
-        self.time += dt;
-
-        // Update dynamic textures (video frames + camera frames)
-        self.update_dynamic_textures(dt);
 
-        // Write input data to buffer (buttons + floats)
-        let mut input_data = Vec::new();
-
-        // Buttons (48 bytes)
-        for &button in &self.buttons {
-            input_data.extend_from_slice(&button.to_le_bytes());
+        // A replay supplies the frame's dt and input in place of the clock and live input, and
+        // --fixed-dt the dt in place of both, as in a headless run
+        if let Some(input) = self.next_replay_frame() {
+            dt = input.dt;
+            self.apply_frame_input(&input);
+        }
+        if let Some(fixed_dt) = self.fixed_dt {
+            dt = fixed_dt;
         }
+        self.record_frame(dt);
 
-        // Time + mouse data (32 bytes: time, delta, width, height, mouse xyzw)
-        input_data.extend_from_slice(&self.time.to_le_bytes());
-        input_data.extend_from_slice(&dt.to_le_bytes());
-        input_data.extend_from_slice(&(self.size.width as f32).to_le_bytes());
-        input_data.extend_from_slice(&(self.size.height as f32).to_le_bytes());
-        input_data.extend_from_slice(&self.mouse[0].to_le_bytes());
-        input_data.extend_from_slice(&self.mouse[1].to_le_bytes());
-        input_data.extend_from_slice(&self.mouse[2].to_le_bytes());
-        input_data.extend_from_slice(&self.mouse[3].to_le_bytes());
-
-        self.queue.write_buffer(&self.engine_buffer, 0, &input_data);
-
-        // Write raw key state at its offset (after osc)
-        let keys_data: Vec<u8> = self.keys.iter()
-            .flat_map(|&k| k.to_le_bytes())
-            .collect();
-        self.queue.write_buffer(&self.engine_buffer, self.buffer_offsets.keys, &keys_data);
+        self.time += dt;
+        self.delta_time = dt;
+        self.game.update(&self.queue, self.time, dt, &self.buttons, &self.mouse, &self.keys);
     }
 
+    /// Play and draw a frame. The surface comes first: a frame that can't be drawn isn't played or
+    /// recorded either, so a replay runs the same steps the session did
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
         let output = self.surface.get_current_texture()?;
+        self.update();
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
+        let events = self.game.frame(&self.device, &self.queue, &view, self.time, self.delta_time);
+        output.present();
 
-        // Run compute shader
-        {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Compute Pass"),
-                timestamp_writes: None,
-            });
-            compute_pass.set_pipeline(&self.compute_pipeline);
-            compute_pass.set_bind_group(0, &self.empty_bind_group, &[]);
-            compute_pass.set_bind_group(1, &self.compute_bind_group, &[]);
-            compute_pass.dispatch_workgroups(1, 1, 1);
+        // Act on the events of the frame LATENCY back, which the GPU is done with by now
+        if let Some(events) = events {
+            self.handle_events(events);
         }
 
-        // Render
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.render_bind_group0, &[]);
-            render_pass.set_bind_group(1, &self.render_bind_group1, &[]);
-            if let Some(ref bind_group2) = self.render_bind_group2 {
-                render_pass.set_bind_group(2, bind_group2, &[]);
-            }
+        Ok(())
+    }
 
-            // Draw either model vertices or fullscreen triangle
-            let vertex_count = if self.model_vertex_count > 0 {
-                self.model_vertex_count as u32
-            } else {
-                3  // Fullscreen triangle
-            };
-            render_pass.draw(0..vertex_count, 0..1);
+    /// Play the sounds and music a frame triggered, log its prints and carry out its reset, save, load and quit requests
+    fn handle_events(&mut self, events: events::FrameEvents) {
+        self.game.handle_events(&self.queue, &events, &mut self.saves);
+        for line in wgsleng::read_prints(&self.game.metadata.prints, &events.prints) {
+            println!("[print] {}", line);
         }
+        if events.reset {
+            self.reset();
+        }
+        if events.quit {
+            println!("[quit] the game quit");
+            self.quit = true;
+        }
+    }
 
-        // Copy audio buffer to staging for readback
-        if self.audio_count > 0 {
-            encoder.copy_buffer_to_buffer(
-                &self.engine_buffer,
-                self.buffer_offsets.audio,
-                &self.staging_buffer,
-                0,
-                (self.audio_count * 4) as u64,
-            );
+    /// Clear GameState and run the game's init() before the next frame.
+    /// Triggered by F2, an OSC /reset message or @engine.reset() in the shader.
+    fn reset(&mut self) {
+        println!("[reset] clearing GameState{}", if self.game.init_pipeline.is_some() { " and running init()" } else { "" });
+        self.game.reset(&self.queue);
+    }
+
+    /// Clear GameState for F2 or an OSC /reset, noting it in a recording
+    fn host_reset(&mut self) {
+        if let Session::Record { reset, .. } = &mut self.session {
+            *reset = true;
         }
+        self.reset();
+    }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+    /// Start writing every frame's input to `path`, from the @persist data the game has now and at
+    /// its size, which the window keeps until the recording stops
+    fn start_recording(&mut self, path: &std::path::Path) -> std::io::Result<()> {
+        let header = wgsleng::replay::Header {
+            title: self.game.metadata.title.clone(),
+            source_hash: self.game.source_hash.clone(),
+            size: Some((self.game.size.width, self.game.size.height)),
+            persist: self.game.save_file.as_ref().map(|save| self.game.read_engine_bytes(&self.device, &self.queue, save.offset as u64, save.size)),
+        };
+        let recorder = wgsleng::replay::Recorder::create(path, &header)?;
+        println!("[record] recording to {}", path.display());
+        self.session = Session::Record { recorder, osc: Vec::new(), reset: false };
+        self.window.set_resizable(false);
+        Ok(())
+    }
 
-        // Read audio triggers
-        if self.audio_count > 0 {
-            let slice = self.staging_buffer.slice(0..(self.audio_count * 4) as u64);
-            let (sender, receiver) = futures::channel::oneshot::channel();
-            slice.map_async(wgpu::MapMode::Read, move |result| {
-                sender.send(result).unwrap();
-            });
-            self.device.poll(wgpu::Maintain::Wait);
-
-            if let Ok(Ok(())) = pollster::block_on(receiver) {
-                let data = slice.get_mapped_range();
-                let triggers: &[u32] = bytemuck::cast_slice(&data);
-
-                for (i, &trigger) in triggers.iter().enumerate() {
-                    if trigger > 0 && i < self.sound_buffers.len() {
-                        let cursor = Cursor::new(self.sound_buffers[i].clone());
-                        if let Ok(source) = Decoder::new(cursor) {
-                            let sink = Sink::try_new(&self.stream_handle).unwrap();
-                            sink.append(source);
-                            sink.detach();
-                        }
-                    }
-                }
+    /// Replay a recording made with the same preprocessed shader, at the size it was recorded at,
+    /// which the window keeps until the replay runs out
+    fn start_replay(&mut self, replay: wgsleng::replay::Replay) -> Result<(), String> {
+        if replay.header.source_hash != self.game.source_hash {
+            return Err(format!(
+                "recording is of a different build of '{}' (source hash {}, this one is {}); replaying it wouldn't reproduce the session",
+                replay.header.title, replay.header.source_hash, self.game.source_hash
+            ));
+        }
+        println!("[replay] playing {} frames", replay.frames.len());
+        if let Some((width, height)) = replay.header.size {
+            self.resize(winit::dpi::PhysicalSize::new(width, height));
+        }
+        self.session = Session::Replay { replay, frame: 0 };
+        self.window.set_resizable(false);
+        Ok(())
+    }
 
-                drop(data);
-                self.staging_buffer.unmap();
+    /// The replay's input for this frame, handing over to live input once it runs out
+    fn next_replay_frame(&mut self) -> Option<wgsleng::replay::FrameInput> {
+        let Session::Replay { replay, frame } = &mut self.session else {
+            return None;
+        };
+        let Some(input) = replay.frames.get(*frame).cloned() else {
+            println!("[replay] finished after {} frames, live input from here on", frame);
+            self.session = Session::Live;
+            self.window.set_resizable(true);
+            return None;
+        };
+        *frame += 1;
+        Some(input)
+    }
 
-                // Reset audio triggers
-                let zeros = vec![0u8; self.audio_count * 4];
-                self.queue.write_buffer(&self.engine_buffer, self.buffer_offsets.audio, &zeros);
-            }
+    fn apply_frame_input(&mut self, input: &wgsleng::replay::FrameInput) {
+        self.buttons = input.buttons;
+        self.mouse = input.mouse;
+        self.keys = [0; KEY_ARRAY_SIZE];
+        for &key in input.keys.iter().filter(|&&key| key < KEY_ARRAY_SIZE) {
+            self.keys[key] = 1;
+        }
+        for &(slot, value) in &input.osc {
+            self.game.set_osc_float(&self.queue, slot, value);
+        }
+        if input.reset {
+            self.reset();
         }
+    }
 
-        Ok(())
+    /// Write this frame's dt and input to the recording, if there is one
+    fn record_frame(&mut self, dt: f32) {
+        let Session::Record { recorder, osc, reset } = &mut self.session else {
+            return;
+        };
+        let input = wgsleng::replay::FrameInput {
+            dt,
+            buttons: self.buttons,
+            mouse: self.mouse,
+            keys: (0..KEY_ARRAY_SIZE).filter(|&key| self.keys[key] != 0).collect(),
+            osc: std::mem::take(osc),
+            reset: std::mem::take(reset),
+        };
+        if let Err(e) = recorder.write_frame(&input) {
+            eprintln!("[record] stopped after {} frames: {}", recorder.frames, e);
+            self.session = Session::Live;
+            self.window.set_resizable(true);
+        }
     }
 
     /// Apply an OSC message by writing directly into the engine buffer.
     fn apply_osc_message(&mut self, msg: &OscMessage) {
+        // During a replay, input comes from the recording
+        if matches!(self.session, Session::Replay { .. }) && matches!(msg, OscMessage::SetFloat(..) | OscMessage::Reset) {
+            return;
+        }
         match msg {
             OscMessage::SetFloat(name, value) => {
                 // Try name lookup first, then parse as numeric index
-                let idx = self.osc_name_map.get(name).copied().or_else(|| name.parse::<usize>().ok());
+                let idx = self.game.osc_name_map.get(name).copied().or_else(|| name.parse::<usize>().ok());
                 match idx {
                     Some(i) if i < OSC_FLOAT_COUNT => {
-                        let offset = self.buffer_offsets.osc_floats + (i * 4) as u64;
-                        self.queue.write_buffer(&self.engine_buffer, offset, &value.to_le_bytes());
+                        self.game.set_osc_float(&self.queue, i, *value);
+                        if let Session::Record { osc, .. } = &mut self.session {
+                            osc.push((i, *value));
+                        }
                     }
                     Some(i) => log::warn!("[osc] /u/{} index {} out of range (max {})", name, i, OSC_FLOAT_COUNT - 1),
                     None => log::warn!("[osc] /u/{} not declared with @osc(\"{}\") in shader", name, name),
                 }
             }
             OscMessage::SetVideoPosition(filename, position) => {
-                if let Some(idx) = self.video_filenames.iter().position(|f| f == filename) {
-                    // Gather what we need (may clone frame data for GIF) before touching queue
-                    let gif_frame: Option<(Vec<u8>, u32, u32)> = match &mut self.video_sources[idx] {
-                        VideoSourceRuntime::Gif { frames, current_frame, frame_elapsed_ms, width, height } => {
-                            let new_frame = ((*position * frames.len() as f32) as usize)
-                                .min(frames.len().saturating_sub(1));
-                            *current_frame = new_frame;
-                            *frame_elapsed_ms = 0.0;
-                            Some((frames[new_frame].0.clone(), *width, *height))
-                        }
-                        VideoSourceRuntime::Black(_, _) => None,
-                    };
-                    // Upload the new GIF frame immediately so the seek is visible this frame
-                    if let Some((data, w, h)) = gif_frame {
-                        self.queue.write_texture(
-                            wgpu::ImageCopyTexture { texture: &self.video_textures[idx], mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
-                            &data,
-                            wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(4 * w), rows_per_image: Some(h) },
-                            wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
-                        );
-                    }
-                } else {
+                let seeked = self.game.seek_video(&self.queue, filename, *position);
+                if !seeked {
                     log::warn!("[osc] /vid/{}/position: no video named '{}' loaded", filename, filename);
                 }
             }
+            OscMessage::Reset => self.host_reset(),
             // LoadShader and Reload are handled at the App level
             _ => {}
         }
     }
 
-    /// Read the GameState section from the GPU buffer so we can restore it after reload.
-    fn read_game_state_bytes(&self) -> Vec<u8> {
-        let state_offset = self.buffer_offsets.state;
-        let state_size = if self.audio_count > 0 {
-            (self.buffer_offsets.audio - state_offset) as usize
-        } else {
-            self.engine_buffer_size.saturating_sub(state_offset as usize)
-        };
-
-        if state_size == 0 {
-            return Vec::new();
-        }
-
-        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("State Readback"),
-            size: state_size as u64,
-            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-        encoder.copy_buffer_to_buffer(&self.engine_buffer, state_offset, &readback, 0, state_size as u64);
-        self.queue.submit(std::iter::once(encoder.finish()));
-
-        let slice = readback.slice(..);
-        let (tx, rx) = futures::channel::oneshot::channel();
-        slice.map_async(wgpu::MapMode::Read, move |r| { let _ = tx.send(r); });
-        self.device.poll(wgpu::Maintain::Wait);
-
-        if let Ok(Ok(())) = pollster::block_on(rx) {
-            let data = slice.get_mapped_range();
-            let result = data.to_vec();
-            drop(data);
-            readback.unmap();
-            result
-        } else {
-            vec![0u8; state_size]
-        }
-    }
-
-    /// Hot-reload: re-preprocess shader, rebuild pipelines and textures, preserve GameState.
-    fn reload(&mut self, game_path: &str, entry_file: &str) -> Result<(), Box<dyn std::error::Error>> {
-
-        // Signal camera threads to stop before rebuilding
-        #[cfg(feature = "camera")]
-        for source in &self.camera_sources {
-            if let CameraSourceRuntime::Live { stop, .. } = source {
-                stop.store(true, std::sync::atomic::Ordering::Relaxed);
+    /// Hot-reload: load the game again in place of the old one, keeping its GameState, @persist,
+    /// @storage() buffers and canvases where their layout hasn't changed
+    fn reload(&mut self, game_path: &str, entry_file: &str, options: &PreprocessorOptions) -> Result<(), wgsleng::Error> {
+        let preprocessor = PreprocessorState::new(GameSource::open(game_path)?).with_entry_file(entry_file).with_options(options);
+        let target = game::Target { format: self.config.format, size: Some(winit::dpi::PhysicalSize::new(self.config.width, self.config.height)) };
+        let loaded = pollster::block_on(game::Game::load(&self.device, &self.queue, preprocessor, &target, Some(&self.mixer), &self.saves, Some(&self.game)));
+        // On an error the old game keeps running untouched, cameras and all
+        let loaded = loaded.inspect_err(|e| {
+            if matches!(e, wgsleng::Error::Shader { .. }) {
+                eprintln!("[hot-reload] shader error, keeping old pipelines");
             }
-        }
-
-        // Save GameState bytes before rebuilding
-        let saved_state = self.read_game_state_bytes();
-        let old_state_size = saved_state.len();
-
-        // Re-open game source
-        let mut game_source = GameSource::open(game_path)?;
-
-        // Preprocess shader
-        let shader_code = game_source.read_text(entry_file)?;
-        let mut preprocessor = PreprocessorState::new(game_source);
-        let (processed_code, metadata) = preprocessor.preprocess_shader(&shader_code, true)?;
-
-        println!("[hot-reload] shader preprocessed ({}x{}, {} textures)", metadata.width, metadata.height, metadata.textures.len());
-
-        // Load audio
-        let mut sound_buffers = Vec::new();
-        for sound_file in &metadata.sounds {
-            match preprocessor.game_source.read_file(sound_file) {
-                Ok(data) => sound_buffers.push(data),
-                Err(e) => eprintln!("[hot-reload] warning: failed to load sound {}: {}", sound_file, e),
+        })?;
+        let mut previous = std::mem::replace(&mut self.game, loaded);
+        previous.stop_cameras();
+
+        // The old game's last frames are still on their way back. Their sounds, music, prints and
+        // saves are the old game's; a reset or load goes to the new one, which has its state now
+        for events in previous.events.flush(&self.device) {
+            previous.handle_events(&self.queue, &events, &mut self.saves);
+            for line in wgsleng::read_prints(&previous.metadata.prints, &events.prints) {
+                println!("[print] {}", line);
             }
-        }
-
-        // Load models
-        let mut models: Vec<(wgpu::Buffer, wgpu::Buffer)> = Vec::new();
-        let mut model_vertex_counts: Vec<usize> = Vec::new();
-        for model_file in &metadata.models {
-            let model_data = preprocessor.game_source.read_file(model_file)?;
-            let model_path = std::path::PathBuf::from(model_file);
-            let temp_path = std::env::temp_dir().join(model_path.file_name().unwrap());
-            std::fs::write(&temp_path, model_data)?;
-            let model = wgsleng::ObjModel::load(&temp_path)?;
-            model_vertex_counts.push(model.vertex_count());
-
-            let positions_data: Vec<f32> = model.positions.iter()
-                .flat_map(|p| [p[0], p[1], p[2], 0.0])
-                .collect();
-            let positions_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Model Positions"),
-                contents: bytemuck::cast_slice(&positions_data),
-                usage: wgpu::BufferUsages::STORAGE,
-            });
-
-            let normals_data: Vec<f32> = model.normals.iter()
-                .flat_map(|n| [n[0], n[1], n[2], 0.0])
-                .collect();
-            let normals_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Model Normals"),
-                contents: bytemuck::cast_slice(&normals_data),
-                usage: wgpu::BufferUsages::STORAGE,
-            });
-            models.push((positions_buffer, normals_buffer));
-        }
-
-        // Load textures
-        let mut textures: Vec<wgpu::Texture> = Vec::new();
-        for texture_file in &metadata.textures {
-            let img_data = preprocessor.game_source.read_file(texture_file)?;
-            let img = image::load_from_memory(&img_data)?.to_rgba8();
-            let dimensions = img.dimensions();
-            let texture_size = wgpu::Extent3d { width: dimensions.0, height: dimensions.1, depth_or_array_layers: 1 };
-            let texture = self.device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("Game Texture"),
-                size: texture_size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8Unorm,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
-            });
-            self.queue.write_texture(
-                wgpu::ImageCopyTexture { texture: &texture, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
-                &img,
-                wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(4 * dimensions.0), rows_per_image: Some(dimensions.1) },
-                texture_size,
-            );
-            textures.push(texture);
-        }
-
-        // Load video sources
-        let mut new_video_textures: Vec<wgpu::Texture> = Vec::new();
-        let mut new_video_sources: Vec<VideoSourceRuntime> = Vec::new();
-        for video_file in &metadata.videos {
-            let data = match preprocessor.game_source.read_file(video_file) {
-                Ok(d) => d,
-                Err(e) => { eprintln!("[hot-reload] warning: failed to load video {}: {}", video_file, e); Vec::new() }
-            };
-            let (source, vid_w, vid_h) = if data.is_empty() {
-                (VideoSourceRuntime::Black(1, 1), 1u32, 1u32)
-            } else {
-                load_video_source(video_file, data)
-            };
-            let (init_data, vid_w, vid_h) = match &source {
-                VideoSourceRuntime::Gif { frames, width, height, current_frame, .. } =>
-                    (frames[*current_frame].0.clone(), *width, *height),
-                VideoSourceRuntime::Black(w, h) =>
-                    (vec![0u8; (*w * *h * 4) as usize], *w, *h),
-            };
-            let tex_size = wgpu::Extent3d { width: vid_w, height: vid_h, depth_or_array_layers: 1 };
-            let tex = self.device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("Video Texture"),
-                size: tex_size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8Unorm,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
-            });
-            self.queue.write_texture(
-                wgpu::ImageCopyTexture { texture: &tex, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
-                &init_data,
-                wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(4 * vid_w), rows_per_image: Some(vid_h) },
-                tex_size,
-            );
-            new_video_textures.push(tex);
-            new_video_sources.push(source);
-        }
-
-        // Open camera sources
-        let mut new_camera_textures: Vec<wgpu::Texture> = Vec::new();
-        let mut new_camera_sources: Vec<CameraSourceRuntime> = Vec::new();
-        for &cam_idx in &metadata.cameras {
-            let (source, cam_w, cam_h) = open_camera_source(cam_idx);
-            let black_data = vec![0u8; (cam_w * cam_h * 4) as usize];
-            let cam_size = wgpu::Extent3d { width: cam_w, height: cam_h, depth_or_array_layers: 1 };
-            let tex = self.device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("Camera Texture"),
-                size: cam_size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8Unorm,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
-            });
-            self.queue.write_texture(
-                wgpu::ImageCopyTexture { texture: &tex, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
-                &black_data,
-                wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(4 * cam_w), rows_per_image: Some(cam_h) },
-                cam_size,
-            );
-            new_camera_textures.push(tex);
-            new_camera_sources.push(source);
-        }
-
-        // Compute buffer layout (same logic as State::new)
-        let button_size = 12 * 4usize;
-        let float_data_size = 8 * 4usize; // 32 bytes (time, delta, width, height + mouse vec4f)
-        // metadata.state_size is already aligned to the struct's own alignment by the preprocessor
-        let aligned_state_size = metadata.state_size;
-        let audio_size = metadata.sounds.len() * 4;
-        let osc_floats_offset = button_size + float_data_size + aligned_state_size + audio_size;
-        let keys_offset = osc_floats_offset + OSC_FLOAT_COUNT * 4;
-        let total_size_unaligned = keys_offset + KEY_ARRAY_SIZE * 4;
-        let total_size = ((total_size_unaligned + 15) / 16) * 16;
-
-        let new_buffer_offsets = BufferOffsets {
-            buttons: 0,
-            floats: button_size as u64,
-            state: (button_size + float_data_size) as u64,
-            audio: (button_size + float_data_size + aligned_state_size) as u64,
-            osc_floats: osc_floats_offset as u64,
-            keys: keys_offset as u64,
-        };
-
-        let new_state_size = if metadata.sounds.len() > 0 {
-            (new_buffer_offsets.audio - new_buffer_offsets.state) as usize
-        } else {
-            osc_floats_offset.saturating_sub(new_buffer_offsets.state as usize)
-        };
-
-        // Build new engine buffer, preserving GameState if sizes match
-        let mut init_data = vec![0u8; total_size];
-        let w_bytes = (self.config.width as f32).to_le_bytes();
-        let h_bytes = (self.config.height as f32).to_le_bytes();
-        let f = new_buffer_offsets.floats as usize;
-        init_data[f + 8..f + 12].copy_from_slice(&w_bytes);
-        init_data[f + 12..f + 16].copy_from_slice(&h_bytes);
-
-        if new_state_size == old_state_size && !saved_state.is_empty() {
-            let ss = new_buffer_offsets.state as usize;
-            let se = ss + new_state_size;
-            if se <= init_data.len() {
-                init_data[ss..se].copy_from_slice(&saved_state);
-                println!("[hot-reload] GameState preserved ({} bytes)", new_state_size);
+            if events.load {
+                self.game.handle_events(&self.queue, &events::FrameEvents { load: true, ..Default::default() }, &mut self.saves);
             }
-        } else if new_state_size != old_state_size {
-            println!("[hot-reload] GameState size changed ({} -> {}), resetting state", old_state_size, new_state_size);
-        }
-
-        let engine_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Engine Buffer"),
-            contents: &init_data,
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
-        });
-
-        let staging_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Staging Buffer"),
-            size: total_size as u64,
-            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-
-        // Sampler
-        let sampler = self.device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-
-        // Bind group layouts (same as State::new)
-        let mut render_group0_entries = vec![wgpu::BindGroupLayoutEntry {
-            binding: 0,
-            visibility: wgpu::ShaderStages::FRAGMENT,
-            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-            count: None,
-        }];
-        for i in 0..metadata.textures.len() {
-            render_group0_entries.push(wgpu::BindGroupLayoutEntry {
-                binding: (i + 1) as u32,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
-                },
-                count: None,
-            });
-        }
-        let reload_video_base = metadata.textures.len() + 1;
-        for i in 0..metadata.videos.len() {
-            render_group0_entries.push(wgpu::BindGroupLayoutEntry {
-                binding: (reload_video_base + i) as u32,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
-                },
-                count: None,
-            });
-        }
-        let reload_camera_base = metadata.textures.len() + metadata.videos.len() + 1;
-        for i in 0..metadata.cameras.len() {
-            render_group0_entries.push(wgpu::BindGroupLayoutEntry {
-                binding: (reload_camera_base + i) as u32,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
-                },
-                count: None,
-            });
-        }
-        let render_bind_group_layout0 = self.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Render Bind Group Layout 0"),
-            entries: &render_group0_entries,
-        });
-        let render_bind_group_layout1 = self.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Render Bind Group Layout 1"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: false },
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-        });
-
-        let mut model_group_entries: Vec<wgpu::BindGroupLayoutEntry> = Vec::new();
-        for i in 0..metadata.models.len() {
-            let bb = 1 + i * 2;
-            model_group_entries.push(wgpu::BindGroupLayoutEntry {
-                binding: bb as u32,
-                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: true }, has_dynamic_offset: false, min_binding_size: None },
-                count: None,
-            });
-            model_group_entries.push(wgpu::BindGroupLayoutEntry {
-                binding: (bb + 1) as u32,
-                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: true }, has_dynamic_offset: false, min_binding_size: None },
-                count: None,
-            });
-        }
-        let render_bind_group_layout2 = if !model_group_entries.is_empty() {
-            Some(self.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Render Bind Group Layout 2"),
-                entries: &model_group_entries,
-            }))
-        } else {
-            None
-        };
-
-        let empty_bind_group_layout = self.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Empty Bind Group Layout"),
-            entries: &[],
-        });
-        let compute_bind_group_layout = self.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Compute Bind Group Layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: false }, has_dynamic_offset: false, min_binding_size: None },
-                count: None,
-            }],
-        });
-
-        // Create pipelines inside an error scope to catch shader errors gracefully
-        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
-
-        let shader = self.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Game Shader"),
-            source: wgpu::ShaderSource::Wgsl(processed_code.into()),
-        });
-
-        let mut render_layouts: Vec<&wgpu::BindGroupLayout> = vec![&render_bind_group_layout0, &render_bind_group_layout1];
-        if let Some(ref layout2) = render_bind_group_layout2 {
-            render_layouts.push(layout2);
-        }
-        let render_pipeline_layout = self.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &render_layouts,
-            push_constant_ranges: &[],
-        });
-        let compute_pipeline_layout = self.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Compute Pipeline Layout"),
-            bind_group_layouts: &[&empty_bind_group_layout, &compute_bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
-        let render_pipeline = self.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState { module: &shader, entry_point: Some("vs_main"), buffers: &[], compilation_options: Default::default() },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_render"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: self.config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleList, ..Default::default() },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth24Plus,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-            cache: None,
-        });
-
-        let compute_pipeline = self.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Compute Pipeline"),
-            layout: Some(&compute_pipeline_layout),
-            module: &shader,
-            entry_point: Some("update"),
-            compilation_options: Default::default(),
-            cache: None,
-        });
-
-        // Check for shader/pipeline errors
-        let pipeline_error = pollster::block_on(self.device.pop_error_scope());
-        if let Some(err) = pipeline_error {
-            eprintln!("[hot-reload] shader error, keeping old pipelines:\n  {}", err);
-            return Err(format!("shader error: {}", err).into());
-        }
-
-        // Build bind groups with new resources
-        let texture_views: Vec<_> = textures.iter()
-            .map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()))
-            .collect();
-        let new_video_views: Vec<_> = new_video_textures.iter()
-            .map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()))
-            .collect();
-        let new_camera_views: Vec<_> = new_camera_textures.iter()
-            .map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()))
-            .collect();
-
-        let mut group0_entries = vec![wgpu::BindGroupEntry {
-            binding: 0,
-            resource: wgpu::BindingResource::Sampler(&sampler),
-        }];
-        for (i, view) in texture_views.iter().enumerate() {
-            group0_entries.push(wgpu::BindGroupEntry {
-                binding: (i + 1) as u32,
-                resource: wgpu::BindingResource::TextureView(view),
-            });
-        }
-        for (i, view) in new_video_views.iter().enumerate() {
-            group0_entries.push(wgpu::BindGroupEntry {
-                binding: (reload_video_base + i) as u32,
-                resource: wgpu::BindingResource::TextureView(view),
-            });
-        }
-        for (i, view) in new_camera_views.iter().enumerate() {
-            group0_entries.push(wgpu::BindGroupEntry {
-                binding: (reload_camera_base + i) as u32,
-                resource: wgpu::BindingResource::TextureView(view),
-            });
-        }
-        let render_bind_group0 = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Render Bind Group 0"),
-            layout: &render_bind_group_layout0,
-            entries: &group0_entries,
-        });
-        let render_bind_group1 = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Render Bind Group 1"),
-            layout: &render_bind_group_layout1,
-            entries: &[wgpu::BindGroupEntry { binding: 0, resource: engine_buffer.as_entire_binding() }],
-        });
-        let render_bind_group2 = if let Some(ref layout2) = render_bind_group_layout2 {
-            let mut model_entries = Vec::new();
-            for (i, (pos_buf, norm_buf)) in models.iter().enumerate() {
-                let bb = 1 + i * 2;
-                model_entries.push(wgpu::BindGroupEntry { binding: bb as u32, resource: pos_buf.as_entire_binding() });
-                model_entries.push(wgpu::BindGroupEntry { binding: (bb + 1) as u32, resource: norm_buf.as_entire_binding() });
+            if events.reset {
+                self.reset();
             }
-            Some(self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Render Bind Group 2"),
-                layout: layout2,
-                entries: &model_entries,
-            }))
-        } else {
-            None
-        };
-        let empty_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Empty Bind Group"),
-            layout: &empty_bind_group_layout,
-            entries: &[],
-        });
-        let compute_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Compute Bind Group"),
-            layout: &compute_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry { binding: 0, resource: engine_buffer.as_entire_binding() }],
-        });
-
-        // Recreate depth texture to match current surface size
-        let depth_texture = self.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Depth Texture"),
-            size: wgpu::Extent3d { width: self.config.width, height: self.config.height, depth_or_array_layers: 1 },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth24Plus,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[],
-        });
-        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-        // Atomically replace all pipeline state
-        self.compute_pipeline = compute_pipeline;
-        self.render_pipeline = render_pipeline;
-        self.empty_bind_group = empty_bind_group;
-        self.compute_bind_group = compute_bind_group;
-        self.render_bind_group0 = render_bind_group0;
-        self.render_bind_group1 = render_bind_group1;
-        self.render_bind_group2 = render_bind_group2;
-        self.engine_buffer = engine_buffer;
-        self.staging_buffer = staging_buffer;
-        self.buffer_offsets = new_buffer_offsets;
-        self.sound_buffers = sound_buffers;
-        self.audio_count = metadata.sounds.len();
-        self.model_vertex_count = model_vertex_counts.get(0).copied().unwrap_or(0);
-        self.depth_texture = depth_texture;
-        self.depth_view = depth_view;
-        self.engine_buffer_size = total_size;
-        self.osc_name_map = metadata.osc_params.iter().cloned().zip(0..).collect();
-        self.video_textures = new_video_textures;
-        self.video_sources = new_video_sources;
-        self.video_filenames = metadata.videos.clone();
-        self.camera_textures = new_camera_textures;
-        self.camera_sources = new_camera_sources;
+            self.quit |= events.quit;
+        }
 
         println!("[hot-reload] done");
         Ok(())
@@ -2040,6 +1127,15 @@ struct App {
     game_source: Option<GameSource>,
     entry_file: String,
     game_path: String,
+    /// --define symbols and --lib-path directories
+    options: PreprocessorOptions,
+    /// Where @persist save files go
+    save_dir: std::path::PathBuf,
+    /// --record FILE, and the recording to play from --replay
+    record: Option<std::path::PathBuf>,
+    replay: Option<wgsleng::replay::Replay>,
+    /// --fixed-dt
+    fixed_dt: Option<f32>,
     hot_reload_rx: Option<std::sync::mpsc::Receiver<()>>,
     _watcher: Option<RecommendedWatcher>,
     osc_rx: Option<std::sync::mpsc::Receiver<OscMessage>>,
@@ -2061,11 +1157,35 @@ impl ApplicationHandler for App {
                     .unwrap(),
             );
 
-            let state = pollster::block_on(State::new(window, game_source, &self.entry_file)).unwrap();
+            // A replay starts from the @persist data it was recorded with instead of the save file
+            let saves = match &self.replay {
+                Some(replay) => Saves::Replay(replay.header.persist.clone()),
+                None => Saves::Dir(self.save_dir.clone()),
+            };
+            let mut state = match pollster::block_on(State::new(window, game_source, &self.entry_file, &self.options, saves)) {
+                Ok(state) => state,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    event_loop.exit();
+                    return;
+                }
+            };
+
+            let session = match (&self.record, self.replay.take()) {
+                (Some(path), _) => state.start_recording(path).map_err(|e| format!("failed to record to {}: {}", path.display(), e)),
+                (None, Some(replay)) => state.start_replay(replay),
+                (None, None) => Ok(()),
+            };
+            if let Err(e) = session {
+                eprintln!("{}", e);
+                event_loop.exit();
+                return;
+            }
+            state.fixed_dt = self.fixed_dt;
 
             // Set window title and size from game metadata
-            state.window.set_title(&state.title);
-            let _ = state.window.request_inner_size(state.size);
+            state.window.set_title(&state.game.metadata.title);
+            let _ = state.window.request_inner_size(state.game.size);
 
             self.state = Some(state);
         }
@@ -2078,13 +1198,15 @@ impl ApplicationHandler for App {
                     WindowEvent::CloseRequested => event_loop.exit(),
                     WindowEvent::Resized(physical_size) => state.resize(physical_size),
                     WindowEvent::RedrawRequested => {
-                        state.update();
                         match state.render() {
                             Ok(_) => {}
-                            Err(wgpu::SurfaceError::Lost) => state.resize(state.size),
+                            Err(wgpu::SurfaceError::Lost) => state.resize(state.game.size),
                             Err(wgpu::SurfaceError::OutOfMemory) => event_loop.exit(),
                             Err(e) => eprintln!("{:?}", e),
                         }
+                        if state.quit {
+                            event_loop.exit();
+                        }
                     }
                     _ => {}
                 }
@@ -2101,7 +1223,7 @@ impl ApplicationHandler for App {
                         println!("[osc] switching shader entry to: {}", entry);
                         self.entry_file = entry.clone();
                         if let Some(state) = &mut self.state {
-                            if let Err(e) = state.reload(&self.game_path, &self.entry_file) {
+                            if let Err(e) = state.reload(&self.game_path, &self.entry_file, &self.options) {
                                 eprintln!("[osc] reload error: {}", e);
                             }
                         }
@@ -2109,7 +1231,7 @@ impl ApplicationHandler for App {
                     OscMessage::Reload => {
                         println!("[osc] /reload received");
                         if let Some(state) = &mut self.state {
-                            if let Err(e) = state.reload(&self.game_path, &self.entry_file) {
+                            if let Err(e) = state.reload(&self.game_path, &self.entry_file, &self.options) {
                                 eprintln!("[osc] reload error: {}", e);
                             }
                         }
@@ -2130,7 +1252,7 @@ impl ApplicationHandler for App {
                 while rx.try_recv().is_ok() {}
                 if let Some(state) = &mut self.state {
                     println!("[hot-reload] file change detected, reloading...");
-                    if let Err(e) = state.reload(&self.game_path, &self.entry_file) {
+                    if let Err(e) = state.reload(&self.game_path, &self.entry_file, &self.options) {
                         eprintln!("[hot-reload] error: {}", e);
                     }
                 }
@@ -2191,6 +1313,12 @@ fn dispatch_osc(tx: &std::sync::mpsc::Sender<OscMessage>, msg: rosc::OscMessage)
         return;
     }
 
+    // /reset
+    if addr == "/reset" {
+        let _ = tx.send(OscMessage::Reset);
+        return;
+    }
+
     // Unknown path — warn once per unique address so high-rate senders don't spam
     thread_local! {
         static WARNED: std::cell::RefCell<std::collections::HashSet<String>> =
@@ -2198,7 +1326,7 @@ fn dispatch_osc(tx: &std::sync::mpsc::Sender<OscMessage>, msg: rosc::OscMessage)
     }
     WARNED.with(|w| {
         if w.borrow_mut().insert(addr.to_string()) {
-            log::warn!("[osc] unknown path '{}' — expected /u/<name>, /shader, or /reload", addr);
+            log::warn!("[osc] unknown path '{}' — expected /u/<name>, /shader, /reload or /reset", addr);
             log::warn!("[osc] (set RUST_LOG=debug to see all received messages)");
         }
     });
@@ -2248,38 +1376,105 @@ fn start_osc_listener(port: u16) -> Option<std::sync::mpsc::Receiver<OscMessage>
     Some(rx)
 }
 
+/// `wgsleng check`: print diagnostics for each game and return the process exit code
+fn run_check(games: &[String], options: &PreprocessorOptions) -> i32 {
+    let mut failed = 0;
+    for game in games {
+        let diagnostics = wgsleng::check::check_game(game, options);
+        for diagnostic in &diagnostics {
+            eprintln!("{}", diagnostic);
+        }
+        if diagnostics.iter().any(|d| d.severity == wgsleng::check::Severity::Error) {
+            eprintln!("{}: FAILED", game);
+            failed += 1;
+        } else {
+            println!("{}: ok", game);
+        }
+    }
+    if failed > 0 { 1 } else { 0 }
+}
+
+/// `wgsleng inspect`: preprocess a game and write its shader and metadata, returning the process exit code
+fn run_inspect(game: &str, wgsl_path: &str, json_path: &str, options: &PreprocessorOptions) -> i32 {
+    let entry_file = GameSource::entry_file(game);
+    let result = GameSource::open(game).and_then(|mut game_source| {
+        let source = game_source.read_text(&entry_file)?;
+        PreprocessorState::new(game_source).with_entry_file(&entry_file).with_options(options).preprocess_shader(&source, true)
+    });
+    let (code, metadata) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+
+    let json = serde_json::to_string_pretty(&wgsleng::inspect::metadata_json(&metadata)).unwrap() + "\n";
+    for (path, contents) in [(wgsl_path, &code), (json_path, &json)] {
+        let written = match path {
+            "-" => std::io::Write::write_all(&mut std::io::stdout(), contents.as_bytes()),
+            path => std::fs::write(path, contents),
+        };
+        if let Err(e) = written {
+            eprintln!("failed to write {}: {}", path, e);
+            return 1;
+        }
+    }
+    0
+}
+
 fn main() {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
-    let args = Args::parse();
-
-    // Determine entry file
-    let entry_file = if args.game_path.ends_with(".wgsl") {
-        std::path::Path::new(&args.game_path)
-            .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string()
-    } else {
-        "main.wgsl".to_string()
+    let mut args = Args::parse();
+
+    let options = PreprocessorOptions { defines: args.defines, lib_paths: args.lib_paths };
+    match &args.command {
+        Some(Command::Check { games }) => std::process::exit(run_check(games, &options)),
+        Some(Command::Inspect { game, wgsl, json }) => std::process::exit(run_inspect(game, wgsl, json, &options)),
+        Some(Command::Test { games, update, software }) => std::process::exit(headless::test(games, &options, *update, *software)),
+        Some(Command::Run { game, headless: true, frames, fixed_dt, input, out, capture, every, software }) => {
+            let run = headless::RunOptions {
+                frames: *frames,
+                fixed_dt: *fixed_dt,
+                input: input.clone(),
+                out: out.clone(),
+                capture: capture.clone(),
+                every: *every,
+                software: *software,
+            };
+            std::process::exit(headless::run(game, &options, &run));
+        }
+        // In a window, the same as `wgsleng GAME --replay FILE`
+        Some(Command::Run { game, input, fixed_dt, .. }) => {
+            args.game_path = Some(game.clone());
+            args.replay = input.clone();
+            args.fixed_dt = *fixed_dt;
+        }
+        None => {}
+    }
+    let Some(game_path) = args.game_path else {
+        Args::command().error(clap::error::ErrorKind::MissingRequiredArgument, "a game path is required").exit();
     };
 
-    let game_source = GameSource::open(&args.game_path)
-        .expect("Failed to open game source");
+    let entry_file = GameSource::entry_file(&game_path);
+    let game_source = GameSource::open(&game_path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
 
     // Set up hot-reload file watcher if requested
     let (hot_reload_rx, _watcher) = if args.hot_reload {
-        if args.game_path.ends_with(".zip") {
+        if game_path.ends_with(".zip") {
             eprintln!("[hot-reload] not available for zip sources");
             (None, None)
         } else {
-            let watch_dir = if args.game_path.ends_with(".wgsl") {
-                std::path::Path::new(&args.game_path)
+            let watch_dir = if game_path.ends_with(".wgsl") {
+                std::path::Path::new(&game_path)
                     .parent()
                     .unwrap_or(std::path::Path::new("."))
                     .to_path_buf()
             } else {
-                std::path::PathBuf::from(&args.game_path)
+                std::path::PathBuf::from(&game_path)
             };
 
             let (tx, rx) = std::sync::mpsc::channel::<()>();
@@ -2321,12 +1516,22 @@ fn main() {
 
     let osc_rx = args.osc_port.and_then(start_osc_listener);
 
+    let replay = args.replay.map(|path| wgsleng::replay::Replay::open(&path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }));
+
     let event_loop = EventLoop::new().unwrap();
     let mut app = App {
         state: None,
         game_source: Some(game_source),
         entry_file,
-        game_path: args.game_path,
+        game_path,
+        options,
+        save_dir: args.save_dir.unwrap_or_else(wgsleng::save::default_dir),
+        record: args.record,
+        replay,
+        fixed_dt: args.fixed_dt,
         hot_reload_rx,
         _watcher,
         osc_rx,
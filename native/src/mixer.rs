@@ -0,0 +1,194 @@
+// The mix sounds and music play through
+//
+// Every voice and track plays into one rodio mixer, and that mixer into the
+// output stream, so the runtime has the samples on their way to the device:
+// a tap on the mix keeps the latest WAVEFORM_SIZE of them, mixed down to mono.
+// Each frame a game that reads @engine.spectrum, @engine.waveform or
+// @engine.audio_level gets them analysed the way the web's AnalyserNode does
+// it (a Blackman window, an FFT, smoothing over time and decibels mapped onto
+// 0 to 1), so a visualizer looks the same in a window and in a browser.
+
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::cpal::traits::HostTrait;
+use rodio::dynamic_mixer::{self, DynamicMixer, DynamicMixerController};
+use rodio::{DeviceTrait, OutputStreamHandle, Sink, Source};
+use wgsleng::{SPECTRUM_SIZE, WAVEFORM_SIZE};
+
+/// Mono samples the tap collects before handing them over
+const TAP_CHUNK: usize = 256;
+
+/// AnalyserNode's defaults: how much of the last frame's spectrum each frame keeps, and the
+/// decibels that map to 0 and 1
+const SMOOTHING: f32 = 0.8;
+const MIN_DECIBELS: f32 = -100.0;
+const MAX_DECIBELS: f32 = -30.0;
+
+/// Where sounds and music play
+#[derive(Clone)]
+pub struct Mixer {
+    controller: Arc<DynamicMixerController<f32>>,
+    /// The latest mono samples of the mix, oldest first
+    tapped: Arc<Mutex<VecDeque<f32>>>,
+}
+
+impl Mixer {
+    /// Start a mix on `output`, at the default device's sample rate so it isn't resampled twice
+    pub fn new(output: &OutputStreamHandle) -> Self {
+        let rate = rodio::cpal::default_host()
+            .default_output_device()
+            .and_then(|device| device.default_output_config().ok())
+            .map_or(44100, |config| config.sample_rate().0);
+        let (controller, mixer) = dynamic_mixer::mixer(2, rate);
+        let tapped = Arc::new(Mutex::new(VecDeque::from(vec![0.0; WAVEFORM_SIZE])));
+        let tap = Tap { mixer, frame: 0.0, channel: 0, pending: Vec::with_capacity(TAP_CHUNK), tapped: tapped.clone() };
+        if let Err(e) = output.play_raw(tap) {
+            eprintln!("[audio] warning: failed to start the mix: {}", e);
+        }
+        Self { controller, tapped }
+    }
+
+    /// A sink that plays into the mix
+    pub fn sink(&self) -> Sink {
+        let (sink, queue) = Sink::new_idle();
+        self.controller.add(queue);
+        sink
+    }
+
+    /// The latest WAVEFORM_SIZE samples of the mix
+    pub fn waveform(&self) -> Vec<f32> {
+        self.tapped.lock().map(|tapped| tapped.iter().copied().collect()).unwrap_or_else(|_| vec![0.0; WAVEFORM_SIZE])
+    }
+}
+
+/// The mix on its way to the device, keeping a copy of what passes
+struct Tap {
+    mixer: DynamicMixer<f32>,
+    /// Sum of the channels of the frame going past
+    frame: f32,
+    channel: u16,
+    /// Mono samples not handed over yet
+    pending: Vec<f32>,
+    tapped: Arc<Mutex<VecDeque<f32>>>,
+}
+
+impl Iterator for Tap {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        // The mixer ends whenever nothing is playing; the stream goes on with silence
+        let sample = self.mixer.next().unwrap_or(0.0);
+        self.frame += sample;
+        self.channel += 1;
+        if self.channel == self.mixer.channels() {
+            self.pending.push(self.frame / self.channel as f32);
+            (self.frame, self.channel) = (0.0, 0);
+        }
+        if self.pending.len() >= TAP_CHUNK {
+            // Never wait on the game here; if it's reading, hand them over next time
+            if let Ok(mut tapped) = self.tapped.try_lock() {
+                tapped.extend(self.pending.drain(..));
+                let excess = tapped.len().saturating_sub(WAVEFORM_SIZE);
+                tapped.drain(..excess);
+            } else if self.pending.len() > WAVEFORM_SIZE {
+                self.pending.drain(..TAP_CHUNK);
+            }
+        }
+        Some(sample)
+    }
+}
+
+impl Source for Tap {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.mixer.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.mixer.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Turns the mix's waveform into @engine.audio_level and @engine.spectrum, frame by frame
+pub struct Analyser {
+    /// Each bin's smoothed magnitude from the frame before
+    smoothed: Vec<f32>,
+}
+
+impl Default for Analyser {
+    fn default() -> Self {
+        Self { smoothed: vec![0.0; SPECTRUM_SIZE] }
+    }
+}
+
+impl Analyser {
+    /// audio_level, spectrum and waveform as the engine buffer lays them out
+    pub fn analyse(&mut self, waveform: &[f32]) -> Vec<f32> {
+        let n = WAVEFORM_SIZE;
+        let level = (waveform.iter().map(|x| x * x).sum::<f32>() / n as f32).sqrt();
+
+        let mut re: Vec<f32> = waveform.iter().enumerate().map(|(i, x)| {
+            let phase = 2.0 * PI * i as f32 / n as f32;
+            x * (0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos())
+        }).collect();
+        let mut im = vec![0.0; n];
+        fft(&mut re, &mut im);
+
+        let mut values = Vec::with_capacity(1 + SPECTRUM_SIZE + WAVEFORM_SIZE);
+        values.push(level);
+        for (k, smoothed) in self.smoothed.iter_mut().enumerate() {
+            let magnitude = (re[k] * re[k] + im[k] * im[k]).sqrt() / n as f32;
+            *smoothed = SMOOTHING * *smoothed + (1.0 - SMOOTHING) * magnitude;
+            if !smoothed.is_finite() {
+                *smoothed = 0.0;
+            }
+            let decibels = 20.0 * smoothed.log10();
+            values.push(((decibels - MIN_DECIBELS) / (MAX_DECIBELS - MIN_DECIBELS)).clamp(0.0, 1.0));
+        }
+        values.extend_from_slice(waveform);
+        values
+    }
+}
+
+/// An in-place radix-2 FFT; the length must be a power of two
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let (tr, ti) = (re[b] * cos - im[b] * sin, re[b] * sin + im[b] * cos);
+                (re[b], im[b]) = (re[a] - tr, im[a] - ti);
+                re[a] += tr;
+                im[a] += ti;
+            }
+        }
+        len <<= 1;
+    }
+}
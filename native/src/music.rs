@@ -0,0 +1,316 @@
+// Background music
+//
+// A @music() track is streamed from its file as it plays instead of being
+// decoded whole on load like a sound. Not every format rodio decodes can seek,
+// so a .loop() decodes the file again from its start each time round, skipping
+// what comes before the loop start. One track is current at a time; starting another with a
+// fade crossfades to it, the old one fading out alongside.
+//
+// In a window @engine.music_time counts the frames the audio device has taken
+// from the stream, so visuals can line up with the track to the sample; a
+// headless run has no device and moves it on by each frame's dt instead.
+
+use std::io::Cursor;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rodio::{Decoder, Sink, Source};
+use wgsleng::{MusicAction, MusicCommand, MusicLoop};
+
+use crate::mixer::Mixer;
+
+/// A music file, with where it loops
+#[derive(Clone)]
+pub struct Track {
+    bytes: Arc<[u8]>,
+    channels: u16,
+    rate: u32,
+    /// Length in frames
+    frames: u64,
+    loop_start: u64,
+    loop_end: u64,
+}
+
+impl Track {
+    pub fn open(bytes: Vec<u8>, music_loop: MusicLoop) -> Result<Self, String> {
+        let bytes: Arc<[u8]> = bytes.into();
+        let decoder = Decoder::new(Cursor::new(bytes.clone())).map_err(|e| e.to_string())?;
+        let (channels, rate) = (decoder.channels().max(1), decoder.sample_rate());
+        let frames = match (ogg_length(&bytes), decoder.total_duration()) {
+            (Some(frames), _) => frames,
+            (None, Some(duration)) => (duration.as_secs_f64() * rate as f64).round() as u64,
+            // Nothing says how long it is without decoding it all
+            (None, None) => decoder.count() as u64 / channels as u64,
+        };
+        let to_frames = |seconds: f32| ((seconds as f64 * rate as f64).round() as u64).min(frames);
+        let loop_end = music_loop.end.map_or(frames, to_frames);
+        let loop_start = to_frames(music_loop.start).min(loop_end.saturating_sub(1));
+        Ok(Self { bytes, channels, rate, frames, loop_start, loop_end })
+    }
+
+    /// Open a music file, warning rather than failing if it can't be decoded
+    pub fn load(name: &str, bytes: Vec<u8>, music_loop: MusicLoop) -> Option<Self> {
+        Self::open(bytes, music_loop).inspect_err(|e| eprintln!("[music] warning: failed to decode {}: {}", name, e)).ok()
+    }
+
+    fn decoder(&self) -> Option<Decoder<Cursor<Arc<[u8]>>>> {
+        Decoder::new(Cursor::new(self.bytes.clone())).ok()
+    }
+
+    fn seconds(&self, frames: f64) -> f32 {
+        (frames / self.rate as f64) as f32
+    }
+}
+
+/// The length of an Ogg file in frames, from the granule position of its last page
+fn ogg_length(bytes: &[u8]) -> Option<u64> {
+    if !bytes.starts_with(b"OggS") {
+        return None;
+    }
+    let page = bytes.windows(4).rposition(|w| w == b"OggS")?;
+    let granule = bytes.get(page + 6..page + 14)?;
+    Some(u64::from_le_bytes(granule.try_into().ok()?)).filter(|&frames| frames != u64::MAX)
+}
+
+/// A track as the audio device pulls it
+struct Stream {
+    track: Track,
+    decoder: Option<Decoder<Cursor<Arc<[u8]>>>>,
+    looping: bool,
+    /// Frame of the track being played, and the channel of it next
+    frame: u64,
+    channel: u16,
+    /// `frame`, for the game to read
+    position: Arc<AtomicU64>,
+}
+
+impl Stream {
+    /// Back to the loop start
+    fn restart(&mut self) {
+        let channels = self.track.channels as u64;
+        self.decoder = self.track.decoder();
+        let skipped = self.decoder.as_mut().map_or(0, |decoder| {
+            decoder.by_ref().take((self.track.loop_start * channels) as usize).count() as u64
+        });
+        self.frame = skipped / channels;
+        self.channel = 0;
+    }
+}
+
+impl Iterator for Stream {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        let channels = self.track.channels as u64;
+        let mut restarted = false;
+        loop {
+            if self.looping && self.channel == 0 && self.frame >= self.track.loop_end {
+                self.restart();
+            }
+            let Some(sample) = self.decoder.as_mut().and_then(|decoder| decoder.next()) else {
+                // The file ran out before the loop end: loop from there, unless there's nothing to play
+                if self.looping && !restarted {
+                    self.restart();
+                    restarted = true;
+                    continue;
+                }
+                return None;
+            };
+
+            self.channel += 1;
+            if self.channel as u64 == channels {
+                self.channel = 0;
+                self.frame += 1;
+                self.position.store(self.frame, Ordering::Relaxed);
+            }
+            return Some(sample);
+        }
+    }
+}
+
+impl Source for Stream {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.track.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.track.rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// A track that's been started
+struct Playing {
+    track: usize,
+    /// None without an audio device
+    sink: Option<Sink>,
+    /// Frames into the track the device has got to, with a sink
+    position: Arc<AtomicU64>,
+    /// Frames into the track by the game clock, without one
+    clock: f64,
+    looping: bool,
+    paused: bool,
+    volume: f32,
+    /// Volume it's fading to, and by how much a second
+    fade: Option<(f32, f32)>,
+}
+
+impl Playing {
+    /// Frames into the track it's at
+    fn frame(&self) -> f64 {
+        match self.sink {
+            Some(_) => self.position.load(Ordering::Relaxed) as f64,
+            None => self.clock,
+        }
+    }
+
+    /// Fade to `volume` over `seconds`
+    fn fade_to(&mut self, volume: f32, seconds: f32) {
+        self.fade = Some((volume, (volume - self.volume).abs() / seconds));
+    }
+
+    /// Move on by a frame; false once it's over
+    fn advance(&mut self, dt: f32, track: &Track) -> bool {
+        if let Some((to, rate)) = self.fade {
+            let step = rate * dt;
+            self.volume = if (to - self.volume).abs() <= step { to } else { self.volume + step.copysign(to - self.volume) };
+            if self.volume == to {
+                self.fade = None;
+            }
+            if let Some(sink) = &self.sink {
+                sink.set_volume(self.volume);
+            }
+        }
+        if self.fade.is_none() && self.volume <= 0.0 {
+            return false;
+        }
+        if let Some(sink) = &self.sink {
+            return !sink.empty();
+        }
+        if !self.paused {
+            self.clock += dt as f64 * track.rate as f64;
+            if self.looping && self.clock >= track.loop_end as f64 {
+                let length = (track.loop_end - track.loop_start).max(1) as f64;
+                self.clock = track.loop_start as f64 + (self.clock - track.loop_end as f64) % length;
+            }
+        }
+        self.looping || self.clock < track.frames as f64
+    }
+}
+
+impl Drop for Playing {
+    fn drop(&mut self) {
+        if let Some(sink) = &self.sink {
+            sink.stop();
+        }
+    }
+}
+
+/// A game's music
+pub struct Music {
+    output: Option<Mixer>,
+    /// Each track, or None if it couldn't be opened
+    tracks: Vec<Option<Track>>,
+    current: Option<Playing>,
+    /// Tracks fading out after a crossfade or a stop(fade)
+    fading: Vec<Playing>,
+}
+
+impl Music {
+    /// Play `tracks` on `output`, or with None only time them
+    pub fn new(output: Option<Mixer>, tracks: Vec<Option<Track>>) -> Self {
+        Self { output, tracks, current: None, fading: Vec::new() }
+    }
+
+    /// Carry out a @music("x") call
+    pub fn command(&mut self, command: &MusicCommand) {
+        let Some(Some(track)) = self.tracks.get(command.track) else {
+            return;
+        };
+        let fade = command.fade.max(0.0);
+        let current = self.current.as_mut().filter(|playing| playing.track == command.track);
+        match (command.action, current) {
+            (MusicAction::Play | MusicAction::Loop, Some(playing)) => {
+                if let Some(sink) = &playing.sink {
+                    sink.play();
+                }
+                playing.paused = false;
+            }
+            (MusicAction::Play | MusicAction::Loop, None) => {
+                let looping = command.action == MusicAction::Loop;
+                let position = Arc::new(AtomicU64::new(0));
+                let volume = if fade > 0.0 { 0.0 } else { 1.0 };
+                let sink = self.output.as_ref().map(Mixer::sink).inspect(|sink| {
+                    sink.set_volume(volume);
+                    sink.append(Stream {
+                        track: track.clone(),
+                        decoder: track.decoder(),
+                        looping,
+                        frame: 0,
+                        channel: 0,
+                        position: position.clone(),
+                    });
+                });
+                let mut playing = Playing { track: command.track, sink, position, clock: 0.0, looping, paused: false, volume, fade: None };
+                if fade > 0.0 {
+                    playing.fade_to(1.0, fade);
+                }
+                self.stop_current(fade);
+                self.current = Some(playing);
+            }
+            (MusicAction::Pause, Some(playing)) => {
+                if let Some(sink) = &playing.sink {
+                    sink.pause();
+                }
+                playing.paused = true;
+            }
+            (MusicAction::Stop, Some(_)) => self.stop_current(fade),
+            (MusicAction::Pause | MusicAction::Stop, None) => {}
+        }
+    }
+
+    /// Stop the current track, fading it out over `fade` seconds if that's more than 0
+    fn stop_current(&mut self, fade: f32) {
+        if let Some(mut playing) = self.current.take() {
+            if fade > 0.0 && !playing.paused {
+                playing.fade_to(0.0, fade);
+                self.fading.push(playing);
+            }
+        }
+    }
+
+    /// Move the fades and, without an audio device, the tracks on by a frame
+    pub fn advance(&mut self, dt: f32) {
+        let tracks = &self.tracks;
+        let track = |playing: &Playing| tracks[playing.track].as_ref().expect("only opened tracks play");
+        self.fading.retain_mut(|playing| playing.advance(dt, track(playing)));
+        if self.current.as_mut().is_some_and(|playing| !playing.advance(dt, track(playing))) {
+            self.current = None;
+        }
+    }
+
+    /// Whether the game has no @music() at all
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    /// music_time, music_duration and music_track for the engine buffer
+    pub fn engine_values(&self) -> [u32; 3] {
+        let Some(playing) = &self.current else {
+            return [0; 3];
+        };
+        let track = self.tracks[playing.track].as_ref().expect("only opened tracks play");
+        let time = track.seconds(playing.frame());
+        let duration = track.seconds(track.frames as f64);
+        let number = if playing.paused { 0 } else { playing.track as u32 + 1 };
+        [time.to_bits(), duration.to_bits(), number]
+    }
+}
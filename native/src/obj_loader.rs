@@ -1,6 +1,8 @@
 use std::fs;
 use std::path::Path;
 
+use crate::{io_error, Error};
+
 #[derive(Debug, Clone)]
 pub struct ObjModel {
     pub positions: Vec<[f32; 3]>,
@@ -9,15 +11,21 @@ pub struct ObjModel {
 }
 
 impl ObjModel {
-    pub fn load(path: &Path) -> Result<Self, String> {
-        let content = fs::read_to_string(path)
-            .map_err(|e| format!("Failed to read OBJ file: {}", e))?;
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let name = path.display().to_string();
+        let bytes = fs::read(path).map_err(|e| io_error(&name, e))?;
+        let content = String::from_utf8(bytes).map_err(|_| Error::InvalidUtf8 { path: name.clone(), referenced_from: None })?;
+        Self::parse(&name, &content)
+    }
 
+    /// Parse OBJ text; `path` is only used in errors
+    pub fn parse(path: &str, content: &str) -> Result<Self, Error> {
         let mut positions = Vec::new();
         let mut normals = Vec::new();
         let mut indices = Vec::new();
 
-        for line in content.lines() {
+        for (number, line) in content.lines().enumerate() {
+            let error = |message: String| Error::ObjParse { path: path.to_string(), line: number + 1, message };
             let line = line.trim();
 
             // Skip comments and empty lines
@@ -31,35 +39,32 @@ impl ObjModel {
             }
 
             match parts[0] {
-                "v" => {
+                "v" if parts.len() >= 4 => {
                     // Vertex position
-                    if parts.len() >= 4 {
-                        let x: f32 = parts[1].parse().map_err(|e| format!("Failed to parse vertex x: {}", e))?;
-                        let y: f32 = parts[2].parse().map_err(|e| format!("Failed to parse vertex y: {}", e))?;
-                        let z: f32 = parts[3].parse().map_err(|e| format!("Failed to parse vertex z: {}", e))?;
-                        positions.push([x, y, z]);
-                    }
+                    let x: f32 = parts[1].parse().map_err(|e| error(format!("invalid vertex x: {}", e)))?;
+                    let y: f32 = parts[2].parse().map_err(|e| error(format!("invalid vertex y: {}", e)))?;
+                    let z: f32 = parts[3].parse().map_err(|e| error(format!("invalid vertex z: {}", e)))?;
+                    positions.push([x, y, z]);
                 }
-                "vn" => {
+                "vn" if parts.len() >= 4 => {
                     // Vertex normal
-                    if parts.len() >= 4 {
-                        let x: f32 = parts[1].parse().map_err(|e| format!("Failed to parse normal x: {}", e))?;
-                        let y: f32 = parts[2].parse().map_err(|e| format!("Failed to parse normal y: {}", e))?;
-                        let z: f32 = parts[3].parse().map_err(|e| format!("Failed to parse normal z: {}", e))?;
-                        normals.push([x, y, z]);
-                    }
+                    let x: f32 = parts[1].parse().map_err(|e| error(format!("invalid normal x: {}", e)))?;
+                    let y: f32 = parts[2].parse().map_err(|e| error(format!("invalid normal y: {}", e)))?;
+                    let z: f32 = parts[3].parse().map_err(|e| error(format!("invalid normal z: {}", e)))?;
+                    normals.push([x, y, z]);
                 }
-                "f" => {
+                "f" if parts.len() >= 4 => {
                     // Face (triangle)
-                    if parts.len() >= 4 {
-                        // Parse face indices (format: v, v/vt, v/vt/vn, or v//vn)
-                        for i in 1..=3 {
-                            let index_str = parts[i].split('/').next().unwrap();
-                            let index: u32 = index_str.parse()
-                                .map_err(|e| format!("Failed to parse face index: {}", e))?;
-                            // OBJ indices are 1-based, convert to 0-based
-                            indices.push(index - 1);
+                    // Parse face indices (format: v, v/vt, v/vt/vn, or v//vn)
+                    for part in &parts[1..=3] {
+                        let index_str = part.split('/').next().unwrap();
+                        let index: u32 = index_str.parse()
+                            .map_err(|e| error(format!("invalid face index: {}", e)))?;
+                        // OBJ indices are 1-based, convert to 0-based
+                        if index == 0 || index as usize > positions.len() {
+                            return Err(error(format!("face index {} is out of range (1..={})", index, positions.len())));
                         }
+                        indices.push(index - 1);
                     }
                 }
                 _ => {
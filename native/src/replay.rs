@@ -0,0 +1,185 @@
+// Input recordings for replaying a session exactly
+//
+// `--record session.wgrep` logs everything the host feeds a game each frame:
+// the frame's dt, the gamepad buttons, held keys, the mouse, OSC values and
+// resets from F2 or OSC. `--replay session.wgrep` feeds that back instead of
+// the clock and live input, so the compute shaders see the same values in the
+// same order and the session plays out bit-for-bit.
+//
+// The file is JSON lines: a header with the title, a hash of the preprocessed
+// shader, the size the game was drawn at and the @persist data the session
+// started from, then one line per frame holding dt and whichever inputs changed
+// since the frame before.
+
+use std::io::Write;
+use std::path::Path;
+
+use serde_json::{json, Map, Value};
+
+/// Format version written to (and required from) the header
+const VERSION: u64 = 1;
+
+/// What a recording was made against
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    pub title: String,
+    /// `source_hash()` of the preprocessed shader
+    pub source_hash: String,
+    /// Width and height the game was drawn at, which it sees as @engine.screen_width/height
+    /// (None in recordings that don't say)
+    pub size: Option<(u32, u32)>,
+    /// @engine.persist when the session started, for games with a @persist struct
+    pub persist: Option<Vec<u8>>,
+}
+
+/// The host's input for one frame
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameInput {
+    pub dt: f32,
+    pub buttons: [i32; 12],
+    pub mouse: [f32; 4],
+    /// Indices into @engine.keys of the keys held down
+    pub keys: Vec<usize>,
+    /// OSC values set before this frame, as (slot, value)
+    pub osc: Vec<(usize, f32)>,
+    /// F2 or an OSC /reset before this frame (@engine.reset() is the game's own doing and isn't recorded)
+    pub reset: bool,
+}
+
+/// Hash identifying the game a recording belongs to
+pub fn source_hash(code: &str) -> String {
+    format!("{:08x}", crate::save::fnv1a(code.as_bytes()))
+}
+
+/// Writes a session's frames as they're played
+pub struct Recorder {
+    out: std::io::BufWriter<std::fs::File>,
+    previous: FrameInput,
+    pub frames: usize,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, header: &Header) -> std::io::Result<Self> {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let persist = header.persist.as_deref().map(crate::save::to_hex);
+        let size = header.size.map(|(width, height)| json!([width, height]));
+        let line = json!({ "wgrep": VERSION, "title": header.title, "source_hash": header.source_hash, "size": size, "persist": persist });
+        writeln!(out, "{}", line)?;
+        Ok(Self { out, previous: FrameInput::default(), frames: 0 })
+    }
+
+    /// Append a frame: its dt, plus the inputs that differ from the previous frame
+    pub fn write_frame(&mut self, frame: &FrameInput) -> std::io::Result<()> {
+        let mut line = Map::new();
+        line.insert("dt".into(), f32_value(frame.dt));
+        if frame.buttons != self.previous.buttons {
+            line.insert("buttons".into(), json!(frame.buttons));
+        }
+        if frame.mouse != self.previous.mouse {
+            line.insert("mouse".into(), frame.mouse.iter().copied().map(f32_value).collect());
+        }
+        if frame.keys != self.previous.keys {
+            line.insert("keys".into(), json!(frame.keys));
+        }
+        if !frame.osc.is_empty() {
+            line.insert("osc".into(), frame.osc.iter().map(|&(slot, value)| json!([slot, f32_value(value)])).collect());
+        }
+        if frame.reset {
+            line.insert("reset".into(), json!(true));
+        }
+        writeln!(self.out, "{}", Value::Object(line))?;
+        // Flushed every frame so a crash still leaves the frames leading up to it
+        self.out.flush()?;
+        self.previous = frame.clone();
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+/// A recorded session, read back for replaying
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub header: Header,
+    pub frames: Vec<FrameInput>,
+}
+
+impl Replay {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        Self::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut lines = contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        let (_, first) = lines.next().ok_or("empty recording")?;
+        let header: Value = serde_json::from_str(first).map_err(|e| format!("not a recording: {}", e))?;
+        if header["wgrep"].as_u64() != Some(VERSION) {
+            return Err(format!("not a version {} recording", VERSION));
+        }
+        let persist = match header["persist"].as_str() {
+            Some(hex) => Some(crate::save::from_hex(hex).ok_or("persist isn't hex")?),
+            None => None,
+        };
+        let size = match &header["size"] {
+            Value::Null => None,
+            size => Some(serde_json::from_value(size.clone()).map_err(|_| "size isn't [width, height]")?),
+        };
+        let header = Header {
+            title: header["title"].as_str().unwrap_or_default().to_string(),
+            source_hash: header["source_hash"].as_str().unwrap_or_default().to_string(),
+            size,
+            persist,
+        };
+
+        let mut frames = Vec::new();
+        let mut frame = FrameInput::default();
+        for (i, line) in lines {
+            let error = |message: &str| format!("line {}: {}", i + 1, message);
+            let value: Value = serde_json::from_str(line).map_err(|e| error(&e.to_string()))?;
+            frame.dt = value.get("dt").and_then(parse_f32).ok_or_else(|| error("frame has no dt"))?;
+            if let Some(buttons) = value.get("buttons") {
+                frame.buttons = serde_json::from_value(buttons.clone()).map_err(|_| error("buttons isn't 12 integers"))?;
+            }
+            if let Some(mouse) = value.get("mouse") {
+                frame.mouse = parse_floats(mouse).and_then(|m| m.try_into().ok()).ok_or_else(|| error("mouse isn't 4 numbers"))?;
+            }
+            if let Some(keys) = value.get("keys") {
+                frame.keys = serde_json::from_value(keys.clone()).map_err(|_| error("keys isn't a list of key indices"))?;
+            }
+            frame.osc = match value.get("osc") {
+                Some(Value::Array(values)) => values.iter()
+                    .map(|pair| Some((pair.get(0)?.as_u64()? as usize, parse_f32(pair.get(1)?)?)))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| error("osc isn't a list of [slot, value] pairs"))?,
+                Some(_) => return Err(error("osc isn't a list of [slot, value] pairs")),
+                None => Vec::new(),
+            };
+            frame.reset = value["reset"].as_bool().unwrap_or(false);
+            frames.push(frame.clone());
+        }
+        Ok(Self { header, frames })
+    }
+}
+
+/// An f32 as the shortest decimal that reads back as it (json! would widen it
+/// to f64 first and write all of its digits). JSON has no NaN or infinity, so
+/// those are written as the strings "NaN", "inf" and "-inf".
+fn f32_value(value: f32) -> Value {
+    match value.to_string().parse::<f64>().ok().and_then(serde_json::Number::from_f64) {
+        Some(number) => Value::Number(number),
+        None => Value::String(value.to_string()),
+    }
+}
+
+/// A recorded f32, parsed from the decimal as f32: going through f64 first could round twice
+fn parse_f32(value: &Value) -> Option<f32> {
+    match value {
+        Value::Number(number) => number.to_string().parse().ok(),
+        Value::String(text) if matches!(text.as_str(), "NaN" | "inf" | "-inf") => text.parse().ok(),
+        _ => None,
+    }
+}
+
+fn parse_floats(value: &Value) -> Option<Vec<f32>> {
+    value.as_array()?.iter().map(parse_f32).collect()
+}
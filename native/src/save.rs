@@ -0,0 +1,90 @@
+// Save files for a game's @persist struct
+//
+// `@persist struct SaveData { high_score: u32 }` puts the struct in the engine
+// buffer as `@engine.persist`. The runtime loads it from the game's save file on
+// startup, writes it back when the shader calls `@engine.save()` and reads it
+// again on `@engine.load()`. Saves are keyed by the title and a hash of the
+// struct's layout, and carry the layout itself, so a save written by a build
+// with different fields is ignored instead of landing at the wrong offsets.
+
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use crate::Metadata;
+
+/// Where a game's @persist data is kept, and the layout a save must match
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveFile {
+    /// Name the save is stored under: the title made filename-safe and a hash of `layout`
+    pub key: String,
+    pub title: String,
+    /// The struct's name, fields and size, e.g. `SaveData{high_score:0:4,level:4:4}8`
+    pub layout: String,
+    /// Byte offset of @engine.persist in the engine buffer
+    pub offset: usize,
+    pub size: usize,
+}
+
+impl SaveFile {
+    /// The save for a game with a @persist struct (None without one)
+    pub fn new(metadata: &Metadata) -> Option<Self> {
+        let name = metadata.persist_struct.as_ref()?;
+        let fields: Vec<String> = metadata.persist_fields.iter().map(|f| format!("{}:{}:{}", f.name, f.offset, f.size)).collect();
+        let layout = format!("{}{{{}}}{}", name, fields.join(","), metadata.persist_size);
+        let title: String = metadata.title.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' }).collect();
+        Some(Self {
+            key: format!("{}-{:08x}", title, fnv1a(layout.as_bytes())),
+            title: metadata.title.clone(),
+            layout,
+            offset: metadata.engine_offset("persist")?,
+            size: metadata.persist_size,
+        })
+    }
+
+    /// Save file contents for the @persist bytes read back from the engine buffer
+    pub fn encode(&self, data: &[u8]) -> String {
+        serde_json::to_string_pretty(&json!({ "title": self.title, "layout": self.layout, "data": to_hex(data) })).unwrap_or_default()
+    }
+
+    /// The @persist bytes in a save file, if it was written for this layout
+    pub fn decode(&self, contents: &str) -> Result<Vec<u8>, String> {
+        let value: Value = serde_json::from_str(contents).map_err(|e| format!("not a save file: {}", e))?;
+        let layout = value["layout"].as_str().unwrap_or_default();
+        if layout != self.layout {
+            return Err(format!("saved layout {} doesn't match {}", layout, self.layout));
+        }
+        let data = from_hex(value["data"].as_str().unwrap_or_default())
+            .filter(|data| data.len() == self.size)
+            .ok_or_else(|| format!("saved data isn't {} bytes of hex", self.size))?;
+        Ok(data)
+    }
+}
+
+/// Where native saves go without --save-dir: the platform's per-user data directory
+pub fn default_dir() -> PathBuf {
+    let env = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    let base = if cfg!(windows) {
+        env("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        env("XDG_DATA_HOME").or_else(|| env("HOME").map(|home| home.join(".local/share")))
+    };
+    base.unwrap_or_else(|| PathBuf::from(".")).join("wgsleng").join("saves")
+}
+
+/// 32-bit FNV-1a, simple enough for the web runtime to compute the same key
+pub(crate) fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, &b| (hash ^ b as u32).wrapping_mul(0x01000193))
+}
+
+/// Bytes as lowercase hex, two digits each, the way saves and recordings hold @persist data
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The bytes `to_hex` wrote (None if it isn't pairs of hex digits)
+pub(crate) fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len()).step_by(2).map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok())).collect()
+}
@@ -0,0 +1,211 @@
+// Sound playback
+//
+// Each sound is decoded once on load; a play() streams the decoded samples
+// from a shared buffer instead of decoding the file again. Voices are timed on
+// the game's clock, from the sample's length and pitch, rather than by asking
+// the audio device when they end: @sound("x").is_playing is then the same in a
+// window, a replay of it and a headless run, which has no device at all and
+// only keeps the books. A sound past its voice limit stops its oldest voice.
+
+use std::f32::consts::FRAC_PI_2;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rodio::{Decoder, Sink, Source};
+use wgsleng::SoundTrigger;
+
+use crate::mixer::Mixer;
+
+/// A decoded sound file
+pub struct Sample {
+    /// Interleaved samples
+    data: Arc<[f32]>,
+    channels: u16,
+    rate: u32,
+}
+
+impl Sample {
+    pub fn decode(bytes: Vec<u8>) -> Result<Self, String> {
+        let decoder = Decoder::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+        let (channels, rate) = (decoder.channels(), decoder.sample_rate());
+        Ok(Self { data: decoder.convert_samples().collect(), channels, rate })
+    }
+
+    /// Decode a sound file, warning rather than failing if it can't be
+    pub fn load(name: &str, bytes: Vec<u8>) -> Option<Self> {
+        Self::decode(bytes).inspect_err(|e| eprintln!("[audio] warning: failed to decode {}: {}", name, e)).ok()
+    }
+
+    /// Seconds it plays for at this pitch
+    fn duration(&self, pitch: f32) -> f64 {
+        let frames = self.data.len() / self.channels.max(1) as usize;
+        frames as f64 / self.rate as f64 / pitch as f64
+    }
+}
+
+/// A voice's samples, panned to stereo the way the web's StereoPannerNode does it
+struct Clip {
+    data: Arc<[f32]>,
+    channels: usize,
+    rate: u32,
+    pan: f32,
+    looping: bool,
+    /// Next frame to play
+    frame: usize,
+    /// The right channel of the frame just started
+    right: Option<f32>,
+}
+
+impl Iterator for Clip {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.right.take() {
+            return Some(right);
+        }
+        let frames = self.data.len() / self.channels;
+        if self.frame >= frames {
+            if !self.looping || frames == 0 {
+                return None;
+            }
+            self.frame = 0;
+        }
+        let at = self.frame * self.channels;
+        self.frame += 1;
+
+        let (left, right) = if self.channels == 1 {
+            let x = (self.pan + 1.0) / 2.0 * FRAC_PI_2;
+            (self.data[at] * x.cos(), self.data[at] * x.sin())
+        } else {
+            let (left, right) = (self.data[at], self.data[at + 1]);
+            if self.pan <= 0.0 {
+                let x = (self.pan + 1.0) * FRAC_PI_2;
+                (left + right * x.cos(), right * x.sin())
+            } else {
+                let x = self.pan * FRAC_PI_2;
+                (left * x.cos(), right + left * x.sin())
+            }
+        };
+        self.right = Some(right);
+        Some(left)
+    }
+}
+
+impl Source for Clip {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+struct Voice {
+    /// None without an audio device
+    sink: Option<Sink>,
+    /// Game time it ends at; None while it loops
+    ends: Option<f64>,
+}
+
+/// The voices of a game's sounds
+pub struct Player {
+    output: Option<Mixer>,
+    /// Each sound, or None if it couldn't be decoded
+    samples: Vec<Option<Sample>>,
+    limits: Vec<usize>,
+    voices: Vec<Vec<Voice>>,
+    /// Game time, in seconds
+    time: f64,
+}
+
+impl Player {
+    /// Play `samples` on `output`, or with None only time them; `limits` are the voices each can have
+    pub fn new(output: Option<Mixer>, samples: Vec<Option<Sample>>, limits: Vec<usize>) -> Self {
+        let voices = samples.iter().map(|_| Vec::new()).collect();
+        Self { output, samples, limits, voices, time: 0.0 }
+    }
+
+    /// Move the clock on by a frame, retiring the voices that have ended
+    pub fn advance(&mut self, dt: f32) {
+        self.time += dt as f64;
+        let time = self.time;
+        for voices in &mut self.voices {
+            voices.retain_mut(|voice| {
+                let playing = !voice.ends.is_some_and(|ends| ends <= time);
+                if !playing {
+                    // Whatever is left of it plays out
+                    if let Some(sink) = voice.sink.take() {
+                        sink.detach();
+                    }
+                }
+                playing
+            });
+        }
+    }
+
+    /// Carry out a play(), loop() or stop()
+    pub fn trigger(&mut self, trigger: &SoundTrigger) {
+        let Some(voices) = self.voices.get_mut(trigger.sound) else {
+            return;
+        };
+        if trigger.stop {
+            for voice in voices.drain(..) {
+                if let Some(sink) = voice.sink {
+                    sink.stop();
+                }
+            }
+            return;
+        }
+        let Some(sample) = &self.samples[trigger.sound] else {
+            return;
+        };
+
+        if voices.len() >= self.limits[trigger.sound] {
+            if let Some(sink) = voices.remove(0).sink {
+                sink.stop();
+            }
+        }
+        let pitch = trigger.pitch.max(0.01);
+        let sink = self.output.as_ref().map(Mixer::sink).inspect(|sink| {
+            sink.set_volume(trigger.volume.max(0.0));
+            sink.set_speed(pitch);
+            sink.append(Clip {
+                data: sample.data.clone(),
+                channels: sample.channels.max(1) as usize,
+                rate: sample.rate,
+                pan: trigger.pan.clamp(-1.0, 1.0),
+                looping: trigger.looping,
+                frame: 0,
+                right: None,
+            });
+        });
+        let ends = (!trigger.looping).then(|| self.time + sample.duration(pitch));
+        voices.push(Voice { sink, ends });
+    }
+
+    /// How many voices of each sound are playing, for the engine buffer's sound_voices
+    pub fn voices(&self) -> Vec<u32> {
+        self.voices.iter().map(|voices| voices.len() as u32).collect()
+    }
+}
+
+impl Drop for Player {
+    /// Let one-shots finish when a reload replaces the player; loops stop with it
+    fn drop(&mut self) {
+        for voice in self.voices.drain(..).flatten() {
+            if let (Some(sink), Some(_)) = (voice.sink, voice.ends) {
+                sink.detach();
+            }
+        }
+    }
+}
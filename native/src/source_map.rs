@@ -0,0 +1,175 @@
+// Mapping from preprocessed WGSL back to the files it was built from
+//
+// The preprocessor flattens imports, prepends a generated header and expands
+// macros, so line 900 of the shader naga sees can be line 42 of helpers.wgsl.
+// While writing the output we record, per output line, which source file
+// position each run of text came from.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// `┌─ label:line:col` in a naga/wgpu diagnostic
+static LOCATION_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(┌─ )[^\n]*?:(\d+):(\d+)").unwrap());
+/// The line number in its gutter, e.g. ` 42 │`
+static GUTTER_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^( *)(\d+)( │)").unwrap());
+
+/// A position in one of the game's source files (1-based line and column)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    /// 0-based byte column in the output line where this segment starts
+    column: usize,
+    file: usize,
+    /// 0-based line and byte column in the source file
+    src_line: usize,
+    src_column: usize,
+    /// Copied text maps column-for-column; expanded macros all map to the macro start
+    verbatim: bool,
+}
+
+/// Line/column map from preprocessed output back to the original files
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<String>,
+    lines: Vec<Vec<Segment>>,
+}
+
+impl SourceMap {
+    /// Files referenced by the map, in the order the preprocessor loaded them
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// Map a 1-based line/column of the preprocessed shader back to its source.
+    /// Returns None for the generated header and other engine-generated lines.
+    pub fn lookup(&self, line: usize, column: usize) -> Option<SourceLocation> {
+        let segments = self.lines.get(line.checked_sub(1)?)?;
+        let column = column.saturating_sub(1);
+        let segment = segments.iter().rev().find(|s| s.column <= column).or(segments.first())?;
+        let src_column = if segment.verbatim {
+            segment.src_column + column.saturating_sub(segment.column)
+        } else {
+            segment.src_column
+        };
+        Some(SourceLocation {
+            file: self.files[segment.file].clone(),
+            line: segment.src_line + 1,
+            column: src_column + 1,
+        })
+    }
+
+    /// Account for `len` bytes inserted at a 0-based output line/column after the map was built
+    pub(crate) fn insert(&mut self, line: usize, column: usize, len: usize) {
+        if let Some(segments) = self.lines.get_mut(line) {
+            segments.iter_mut().filter(|s| s.column > column).for_each(|s| s.column += len);
+        }
+    }
+
+    /// Rewrite `┌─ label:line:col` locations and the line-number gutter in a naga/wgpu
+    /// diagnostic so they point at the original files
+    pub fn rewrite_diagnostic(&self, message: &str) -> String {
+        let message = LOCATION_RE.replace_all(message, |cap: &regex::Captures| {
+            let line = cap[2].parse().unwrap_or(0);
+            let column = cap[3].parse().unwrap_or(0);
+            match self.lookup(line, column) {
+                Some(loc) => format!("{}{}", &cap[1], loc),
+                None => format!("{}<generated>:{}:{}", &cap[1], line, column),
+            }
+        });
+
+        // Keep the gutter width so the caret lines below stay aligned
+        GUTTER_RE.replace_all(&message, |cap: &regex::Captures| {
+            let width = cap[1].len() + cap[2].len();
+            let line = cap[2].parse().unwrap_or(0);
+            match self.lookup(line, 1) {
+                Some(loc) => format!("{:>width$}{}", loc.line, &cap[3], width = width),
+                None => cap[0].to_string(),
+            }
+        }).to_string()
+    }
+}
+
+/// Byte offsets of line starts, for turning offsets into line/column quickly
+pub(crate) struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub(crate) fn new(text: &str) -> Self {
+        let mut starts = vec![0];
+        starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+        Self { starts }
+    }
+
+    /// 0-based line and byte column of an offset
+    pub(crate) fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        (line, offset - self.starts[line])
+    }
+}
+
+/// Output buffer that records a SourceMap while text is appended
+pub(crate) struct MappedWriter {
+    pub(crate) out: String,
+    map: SourceMap,
+    column: usize,
+}
+
+impl MappedWriter {
+    pub(crate) fn new(files: Vec<String>) -> Self {
+        Self { out: String::new(), map: SourceMap { files, lines: vec![Vec::new()] }, column: 0 }
+    }
+
+    /// Append engine-generated text that has no source location
+    pub(crate) fn generated(&mut self, text: &str) {
+        self.append(text, None, false);
+    }
+
+    /// Append text copied verbatim from `file`, starting at the given 0-based line/column
+    pub(crate) fn copied(&mut self, text: &str, file: usize, position: (usize, usize)) {
+        self.append(text, Some((file, position)), true);
+    }
+
+    /// Append a macro expansion; every character maps back to the macro's position
+    pub(crate) fn expanded(&mut self, text: &str, file: usize, position: (usize, usize)) {
+        self.append(text, Some((file, position)), false);
+    }
+
+    fn append(&mut self, text: &str, origin: Option<(usize, (usize, usize))>, verbatim: bool) {
+        let mut origin = origin;
+        for (i, piece) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.out.push('\n');
+                self.map.lines.push(Vec::new());
+                self.column = 0;
+                if verbatim {
+                    origin = origin.map(|(file, (line, _))| (file, (line + 1, 0)));
+                }
+            }
+            if let Some((file, (src_line, src_column))) = origin {
+                if !piece.is_empty() {
+                    self.map.lines.last_mut().unwrap().push(Segment { column: self.column, file, src_line, src_column, verbatim });
+                }
+            }
+            self.out.push_str(piece);
+            self.column += piece.len();
+        }
+    }
+
+    pub(crate) fn finish(self) -> (String, SourceMap) {
+        (self.out, self.map)
+    }
+}
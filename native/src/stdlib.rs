@@ -0,0 +1,48 @@
+// Standard library modules and assets embedded in the engine
+//
+// Games reach these as `@import("std:draw2d")` or `@texture("std:font8x8.png")`
+// instead of shipping their own copies. Directories passed with --lib-path are
+// searched first, so a team can add shared modules under the same prefix (or
+// override a built-in one) without rebuilding the engine.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::{io_error, Error};
+
+/// Prefix that marks a library path in @import and asset macros
+pub const PREFIX: &str = "std:";
+
+/// Embedded files, by name inside the library
+const FILES: &[(&str, &[u8])] = &[
+    ("draw2d.wgsl", include_bytes!("../std/draw2d.wgsl")),
+    ("draw3d.wgsl", include_bytes!("../std/draw3d.wgsl")),
+    ("font.wgsl", include_bytes!("../std/font.wgsl")),
+    ("font8x8.png", include_bytes!("../std/font8x8.png")),
+];
+
+/// Names of the embedded library files
+pub fn files() -> impl Iterator<Item = &'static str> {
+    FILES.iter().map(|(name, _)| *name)
+}
+
+/// Look up an embedded library file by name (without the `std:` prefix)
+pub fn embedded(name: &str) -> Option<&'static [u8]> {
+    FILES.iter().find(|(n, _)| *n == name).map(|(_, data)| *data)
+}
+
+/// Read a library file (without the `std:` prefix), trying each search directory before the embedded files
+pub fn read(lib_paths: &[PathBuf], name: &str) -> Result<Vec<u8>, Error> {
+    let path = format!("{}{}", PREFIX, name);
+    if Path::new(name).components().any(|c| !matches!(c, Component::Normal(_))) {
+        return Err(Error::InvalidPath { path, referenced_from: None });
+    }
+    for dir in lib_paths {
+        let file = dir.join(name);
+        if file.is_file() {
+            return std::fs::read(file).map_err(|e| io_error(&path, e));
+        }
+    }
+    embedded(name)
+        .map(<[u8]>::to_vec)
+        .ok_or(Error::AssetNotFound { path, referenced_from: None })
+}
@@ -219,6 +219,23 @@ const BTN_L = 8,
   BTN_START = 10,
   BTN_SELECT = 11;
 
+// Texel formats a @canvas() may use: writable from compute and filterable when sampled
+const CANVAS_FORMATS = ["rgba8unorm", "rgba8snorm", "rgba16float"];
+
+// Slots in the print buffer: @print()s past this many in a frame are counted but not logged (same as native)
+const MAX_PRINTS_PER_FRAME = 32;
+
+// Slots in the engine buffer's sound queue, and the voices a sound can have without @set_voices()
+const MAX_SOUND_TRIGGERS = 32;
+const DEFAULT_SOUND_VOICES = 8;
+
+// Samples of the mix in @engine.waveform, and bins in @engine.spectrum (same as native)
+const WAVEFORM_SIZE = 1024;
+const SPECTRUM_SIZE = WAVEFORM_SIZE / 2;
+
+// Vertex entry point the header adds for @pass() pipelines, covering the whole target (same as native)
+const PASS_VERTEX_ENTRY = "_vs_pass";
+
 // Keyboard mapping to SNES controller
 const KEY_MAP = {
   ArrowUp: BTN_UP,
@@ -297,30 +314,297 @@ class WGSLGameEngine {
     this.keys = new Uint32Array(194);
 
     this.sounds = [];
+    this.music = [];
     this.audioContext = null;
+
+    // Symbols visible to @if() (the native runtime defines WGSLENG_NATIVE instead)
+    this.defines = {
+      WGSLENG_WEB: "1",
+      HAS_CAMERA: navigator.mediaDevices?.getUserMedia ? "1" : "0",
+      DEBUG: "0",
+    };
+  }
+
+  // Evaluate an @if() condition: NAME, !NAME, NAME == value, NAME != value, && || and parentheses.
+  // Undefined names are "0"; a value is true unless it is "0", "false" or empty.
+  evalCondition(condition, defines) {
+    const tokens = [...condition.matchAll(/\s*(&&|\|\||==|!=|!|\(|\)|"(?:[^"\\]|\\.)*"|[A-Za-z_]\w*|[0-9][\w.]*)/g)]
+      .map((m) => m[1]);
+    let pos = 0;
+    const eat = (op) => (tokens[pos] === op ? (pos++, true) : false);
+    const truthy = (value) => !["", "0", "false"].includes(value);
+    const operand = () => {
+      const tok = tokens[pos++];
+      if (tok === undefined) throw new Error(`unexpected end of @if condition: ${condition}`);
+      if (tok.startsWith('"')) return JSON.parse(tok);
+      if (/^[A-Za-z_]/.test(tok)) return defines[tok] ?? "0";
+      return tok;
+    };
+    const unary = () => {
+      if (eat("!")) return !unary();
+      if (eat("(")) {
+        const value = or();
+        if (!eat(")")) throw new Error(`expected ')' in @if condition: ${condition}`);
+        return value;
+      }
+      const left = operand();
+      if (eat("==")) return left === operand();
+      if (eat("!=")) return left !== operand();
+      return truthy(left);
+    };
+    const and = () => {
+      let value = unary();
+      while (eat("&&")) value = unary() && value;
+      return value;
+    };
+    const or = () => {
+      let value = and();
+      while (eat("||")) value = and() || value;
+      return value;
+    };
+    const value = or();
+    if (pos < tokens.length) throw new Error(`unexpected '${tokens[pos]}' in @if condition: ${condition}`);
+    return value;
+  }
+
+  // Compile a @dispatch() workgroup count (numbers, @define symbols, @engine.time/delta_time/screen_width/screen_height
+  // with + - * / % and parentheses) to a function of the engine values, evaluated every frame
+  parseDispatchCount(expr, defines) {
+    const members = ["time", "delta_time", "screen_width", "screen_height"];
+    const tokens = [...expr.matchAll(/\s*(@engine\.\w+|[A-Za-z_]\w*|[0-9][\w.]*|[-+*/%()])/g)].map((m) => m[1]);
+    let pos = 0;
+    const peek = () => tokens[pos];
+    const operand = () => {
+      const tok = tokens[pos++];
+      if (tok === undefined) throw new Error(`unexpected end of @dispatch workgroup count: ${expr}`);
+      if (tok.startsWith("@engine.")) {
+        const member = tok.slice(8);
+        if (!members.includes(member)) throw new Error(`@engine.${member} can't be used in @dispatch; available: ${members.join(", ")}`);
+        return (engine) => engine[member];
+      }
+      const text = /^[A-Za-z_]/.test(tok) ? defines[tok] : tok;
+      if (text === undefined) throw new Error(`unknown name '${tok}' in @dispatch; use a number, a @define()d symbol or @engine.${members.join("/")}`);
+      const n = parseFloat(text.replace(/[uif]$/, ""));
+      if (Number.isNaN(n)) throw new Error(`'${tok}' is defined as '${text}', which is not a number`);
+      return () => n;
+    };
+    const unary = () => {
+      if (peek() === "-") {
+        pos++;
+        const value = unary();
+        return (engine) => -value(engine);
+      }
+      if (peek() === "(") {
+        pos++;
+        const value = sum();
+        if (tokens[pos++] !== ")") throw new Error(`expected ')' in @dispatch workgroup count: ${expr}`);
+        return value;
+      }
+      return operand();
+    };
+    const ops = { "+": (a, b) => a + b, "-": (a, b) => a - b, "*": (a, b) => a * b, "/": (a, b) => a / b, "%": (a, b) => a % b };
+    const binary = (next, symbols) => () => {
+      let value = next();
+      while (symbols.includes(peek())) {
+        const [op, left, right] = [ops[tokens[pos++]], value, next()];
+        value = (engine) => op(left(engine), right(engine));
+      }
+      return value;
+    };
+    const product = binary(unary, ["*", "/", "%"]);
+    const sum = binary(product, ["+", "-"]);
+    const value = sum();
+    if (pos < tokens.length) throw new Error(`unexpected '${tokens[pos]}' in @dispatch workgroup count: ${expr}`);
+    return value;
   }
 
-  async preprocessShader(source, importedFiles = new Set(), isTopLevel = true) {
+  // Size and alignment of a WGSL type in a storage buffer (scalars, vectors, matrices, fixed-size arrays
+  // and structs declared in source), following the WGSL memory layout rules
+  wgslLayout(type, source) {
+    type = type.trim();
+    const roundUp = (align, n) => Math.ceil(n / align) * align;
+    const scalars = { f32: 4, u32: 4, i32: 4, f16: 2, bool: 4 };
+    if (scalars[type]) return { size: scalars[type], align: scalars[type] };
+    const atomic = type.match(/^atomic<\s*(\w+)\s*>$/);
+    if (atomic) return { size: 4, align: 4 };
+    const vec = type.match(/^vec([234])(?:<\s*(\w+)\s*>|([fuih]))$/);
+    if (vec) {
+      const n = parseInt(vec[1]);
+      const scalar = vec[3] === "h" || vec[2] === "f16" ? 2 : 4;
+      const align = scalar * (n === 2 ? 2 : 4);
+      return { size: scalar * n, align };
+    }
+    const mat = type.match(/^mat([234])x([234])(?:<\s*(\w+)\s*>|([fh]))$/);
+    if (mat) {
+      const column = this.wgslLayout(`vec${mat[2]}<${mat[3] ?? (mat[4] === "h" ? "f16" : "f32")}>`, source);
+      return { size: parseInt(mat[1]) * roundUp(column.align, column.size), align: column.align };
+    }
+    const array = type.match(/^array<(.+?)(?:,\s*([^,<>]+))?>$/s);
+    if (array) {
+      if (array[2] === undefined) throw new Error(`storage type ${type} needs a fixed size, e.g. array<${array[1]}, 1024>`);
+      const element = this.wgslLayout(array[1], source);
+      return { size: roundUp(element.align, element.size) * parseInt(array[2]), align: element.align };
+    }
+    const struct = source.match(new RegExp(`struct\\s+${type}\\s*{([^}]*)}`));
+    if (struct) {
+      let size = 0;
+      let align = 1;
+      const fields = [];
+      const members = struct[1].replace(/\/\/[^\n]*/g, "").split(/,(?![^<]*>)/).filter((m) => m.includes(":"));
+      for (const member of members) {
+        const layout = this.wgslLayout(member.slice(member.indexOf(":") + 1), source);
+        size = roundUp(layout.align, size);
+        fields.push({ name: member.slice(0, member.indexOf(":")).trim(), offset: size, size: layout.size });
+        size += layout.size;
+        align = Math.max(align, layout.align);
+      }
+      return { size: roundUp(align, size), align, fields };
+    }
+    throw new Error(`can't lay out type ${type} for @storage`);
+  }
+
+  // Resolve @define/@if/@else/@endif in one file, dropping inactive code before imports are read
+  applyConditionals(source, defines) {
+    const directive = /@(define|if|else|endif)\b(?:\(((?:[^()]|\([^()]*\))*)\))?/g;
+    const stack = [];
+    const active = () => stack.every((c) => c.taken !== c.seenElse);
+    let out = "";
+    let last = 0;
+    for (const match of source.matchAll(directive)) {
+      if (active()) out += source.slice(last, match.index);
+      last = match.index + match[0].length;
+      const [, name, args = ""] = match;
+      if (name === "if") {
+        stack.push({ taken: active() && this.evalCondition(args, defines), seenElse: false });
+      } else if (name === "else") {
+        if (!stack.length) throw new Error("@else without @if");
+        stack[stack.length - 1].seenElse = true;
+      } else if (name === "endif") {
+        if (!stack.pop()) throw new Error("@endif without @if");
+      } else if (active()) {
+        const [symbol, ...value] = args.split(",");
+        defines[symbol.trim()] = value.length ? value.join(",").trim().replace(/^"(.*)"$/, "$1") : "1";
+      }
+    }
+    if (stack.length) throw new Error("@if without matching @endif");
+    if (active()) out += source.slice(last);
+    return out;
+  }
+
+  // Resolve an @import path against the importing file's directory ("/x" starts at the game root)
+  resolveImport(importer, path) {
+    // std: paths name the built-in library (.wgsl optional); relative imports inside it stay there
+    const std = path.startsWith("std:") || (importer.startsWith("std:") && !path.startsWith("/"));
+    if (std) {
+      const from = path.startsWith("std:") ? "" : importer.slice(4);
+      let module;
+      try {
+        module = this.resolveImport(from, path.replace(/^std:/, ""));
+      } catch {
+        throw new Error(`import path '${path}' leaves the library`);
+      }
+      const extension = path.startsWith("std:") && !/\.[^/]*$/.test(module) ? ".wgsl" : "";
+      return `std:${module}${extension}`;
+    }
+    const parts = path.startsWith("/") ? [] : importer.split("/").slice(0, -1);
+    for (const part of path.split("/")) {
+      if (part === "" || part === ".") continue;
+      if (part !== "..") {
+        parts.push(part);
+      } else if (!parts.pop()) {
+        throw new Error(`import path '${path}' leaves the game directory`);
+      }
+    }
+    return parts.join("/");
+  }
+
+  // Prefix a module's top-level names for `@import("file.wgsl") as prefix` (same rules as native):
+  // member accesses, attributes and struct field declarations keep their names
+  applyNamespace(source, prefix) {
+    const tokens = [...source.matchAll(/\/\/[^\n]*|\/\*[\s\S]*?\*\/|"(?:[^"\\]|\\.)*"|[A-Za-z_]\w*|\S/g)]
+      .filter((m) => !m[0].startsWith("//") && !m[0].startsWith("/*"));
+    const isIdent = (m) => /^[A-Za-z_]/.test(m[0]);
+
+    const names = new Set();
+    let depth = 0;
+    tokens.forEach((m, k) => {
+      if (m[0] === "{") depth++;
+      else if (m[0] === "}") depth--;
+      else if (depth === 0 && ["fn", "struct", "const", "var", "override", "alias"].includes(m[0])) {
+        let next = k + 1;
+        if (tokens[next]?.[0] === "<") while (next < tokens.length && tokens[next][0] !== ">") next++;
+        if (tokens[next]?.[0] === ">") next++;
+        if (tokens[next] && isIdent(tokens[next])) names.add(tokens[next][0]);
+      }
+    });
+    names.delete("GameState");
+
+    const renames = [];
+    let structBody = null;
+    depth = 0;
+    tokens.forEach((m, k) => {
+      if (m[0] === "{") {
+        depth++;
+        if (tokens[k - 2]?.[0] === "struct") structBody = depth;
+      } else if (m[0] === "}") {
+        if (structBody === depth) structBody = null;
+        depth--;
+      } else if (names.has(m[0])) {
+        const prev = tokens[k - 1]?.[0];
+        const isField = structBody === depth && tokens[k + 1]?.[0] === ":";
+        if (prev !== "." && prev !== "@" && !isField) renames.push(m);
+      }
+    });
+
+    let out = "";
+    let last = 0;
+    for (const m of renames) {
+      out += source.slice(last, m.index) + `${prefix}_${m[0]}`;
+      last = m.index + m[0].length;
+    }
+    return out + source.slice(last);
+  }
+
+  // importedFiles maps each loaded path to the namespace it was imported under.
+  // ctx carries the @define symbols, the current file, the chain of files being imported and its namespace.
+  async preprocessShader(source, importedFiles = new Map(), isTopLevel = true, ctx = {}) {
+    const { defines = { ...this.defines }, file = "main.wgsl", stack = [file], namespace = null } = ctx;
+    if (isTopLevel) importedFiles.set(file, null);
+    source = this.applyConditionals(source, defines);
+    if (namespace) source = this.applyNamespace(source, namespace);
+
+    // Tag each @print() with where it is, while this file's lines are still its own
+    source = source.replace(/@print\(/g, (call, offset) => `@print[${file}:${source.slice(0, offset).split("\n").length}](`);
+
     // Process @import directives first (recursive, like C #include)
-    const importMatches = [...source.matchAll(/@import\("([^"]+)"\)/g)];
+    const importMatches = [...source.matchAll(/@import\("([^"]+)"\)(?:\s+as\s+([A-Za-z_]\w*))?/g)];
     for (const match of importMatches) {
-      const filename = match[1];
+      const filename = this.resolveImport(file, match[1]);
+      const alias = match[2] ?? null;
+
+      if (stack.includes(filename)) {
+        throw new Error(`import cycle: ${[...stack.slice(stack.indexOf(filename)), filename].join(" -> ")}`);
+      }
 
       // Skip if already imported (each file is only included once)
       if (importedFiles.has(filename)) {
+        if (importedFiles.get(filename) !== alias) {
+          throw new Error(`${filename} is imported under different namespaces (${importedFiles.get(filename)}, ${alias})`);
+        }
         source = source.replace(match[0], `// Already imported: ${filename}`);
         continue;
       }
 
       // Mark as imported
-      importedFiles.add(filename);
+      importedFiles.set(filename, alias);
 
-      // Read and process the imported file (same importedFiles set, not top level)
+      // Read and process the imported file (same importedFiles map, not top level)
       const importedCode = await this.readFileText(filename);
       const processedImport = await this.preprocessShader(
         importedCode,
         importedFiles,
         false, // Not top level - don't add header
+        { defines, file: filename, stack: [...stack, filename], namespace: alias },
       );
 
       // Replace @import with the processed code (just the code, not metadata)
@@ -340,6 +624,10 @@ class WGSLGameEngine {
       videos: [],
       cameras: [],
       oscParams: [],
+      dispatches: [],
+      passes: [],
+      storage: [],
+      canvases: [],
     };
 
     // Extract @set_title
@@ -353,15 +641,43 @@ class WGSLGameEngine {
       metadata.height = parseInt(sizeMatch[2]);
     }
 
-    // Find all @sound() references (both with and without .play()/.stop())
-    const soundMatches = source.matchAll(
-      /@sound\("([^"]+)"\)(?:\.(?:play|stop)\(\))?/g,
-    );
-    for (const match of soundMatches) {
-      if (!metadata.sounds.includes(match[1])) {
-        metadata.sounds.push(match[1]);
+    // Find all @sound() references, and the @set_voices() limits
+    const voiceLimits = new Map();
+    for (const match of source.matchAll(/@(sound|set_voices)\("([^"]+)"(?:\s*,\s*(\d+))?/g)) {
+      if (!metadata.sounds.includes(match[2])) {
+        metadata.sounds.push(match[2]);
+      }
+      if (match[1] === "set_voices") {
+        const voices = parseInt(match[3]);
+        if (!(voices > 0)) throw new Error("@set_voices needs at least 1 voice");
+        if (voiceLimits.has(match[2]) && voiceLimits.get(match[2]) !== voices) {
+          throw new Error(`@set_voices("${match[2]}") is set twice`);
+        }
+        voiceLimits.set(match[2], voices);
+      }
+    }
+    metadata.soundVoices = metadata.sounds.map((sound) => voiceLimits.get(sound) ?? DEFAULT_SOUND_VOICES);
+
+    // Find all @music() tracks, and where @set_music_loop() loops them (the whole track otherwise)
+    metadata.music = [];
+    const musicLoops = new Map();
+    for (const match of source.matchAll(/@(music|set_music_loop)\("([^"]+)"(?:\s*,\s*([^,)]+)(?:\s*,\s*([^,)]+))?)?/g)) {
+      if (!metadata.music.includes(match[2])) {
+        metadata.music.push(match[2]);
+      }
+      if (match[1] === "set_music_loop") {
+        const start = parseFloat(match[3]);
+        const end = match[4] === undefined ? null : parseFloat(match[4]);
+        if (!(start >= 0)) throw new Error(`@set_music_loop expects a number of seconds, found '${match[3]}'`);
+        if (end !== null && !(end > start)) throw new Error("@set_music_loop's end must come after its start");
+        const previous = musicLoops.get(match[2]);
+        if (previous && (previous.start !== start || previous.end !== end)) {
+          throw new Error(`@set_music_loop("${match[2]}") is set twice`);
+        }
+        musicLoops.set(match[2], { start, end });
       }
     }
+    metadata.musicLoops = metadata.music.map((track) => musicLoops.get(track) ?? { start: 0, end: null });
 
     // Find all @texture() references
     const textureMatches = source.matchAll(/@texture\("([^"]+)"\)/g);
@@ -414,9 +730,193 @@ class WGSLGameEngine {
       }
     }
 
+    // Find all @dispatch() directives, in order; counts may contain parentheses.
+    // They stay in imported code so the top level sees every file's dispatches.
+    const dispatchSpans = [];
+    for (const match of source.matchAll(/@dispatch\(/g)) {
+      let depth = 1;
+      let end = match.index + match[0].length;
+      while (end < source.length && depth > 0) {
+        if (source[end] === "(") depth++;
+        if (source[end] === ")") depth--;
+        end++;
+      }
+      const args = [""];
+      depth = 0;
+      for (const c of source.slice(match.index + match[0].length, end - 1)) {
+        if (c === "," && depth === 0) {
+          args.push("");
+          continue;
+        }
+        if (c === "(") depth++;
+        if (c === ")") depth--;
+        args[args.length - 1] += c;
+      }
+      const [entryPoint, ...counts] = args.map((arg) => arg.trim());
+      if (!/^[A-Za-z_]\w*$/.test(entryPoint)) {
+        throw new Error(`@dispatch expects a compute entry point name, e.g. @dispatch(update_particles, 64)`);
+      }
+      if (entryPoint === "init") {
+        throw new Error("init runs on load and on reset, not every frame; it can't be @dispatch()ed");
+      }
+      if (counts.length > 3) throw new Error("@dispatch takes an entry point and at most 3 workgroup counts");
+      const workgroups = [0, 1, 2].map((i) => (counts[i] ? this.parseDispatchCount(counts[i], defines) : () => 1));
+      metadata.dispatches.push({ entryPoint, workgroups });
+      dispatchSpans.push([match.index, end]);
+    }
+    // Strip them before any other directive moves the recorded spans
+    if (isTopLevel) {
+      for (const [start, end] of dispatchSpans.reverse()) {
+        source = source.slice(0, start) + source.slice(end);
+      }
+    }
+
+    // Find all @pass() render targets, in order
+    const passRegex = /@pass\("([^"]+)"\s*,\s*([A-Za-z_]\w*)\s*\)/g;
+    for (const match of source.matchAll(passRegex)) {
+      if (metadata.passes.some((p) => p.name === match[1])) {
+        throw new Error(`@pass("${match[1]}") is declared twice`);
+      }
+      metadata.passes.push({ name: match[1], entryPoint: match[2] });
+    }
+    if (isTopLevel) source = source.replace(passRegex, "");
+
+    // Find all @storage("name", type) declarations; sizes are laid out once every file is inlined
+    const storageRegex = /@storage\("([^"]+)"\s*,\s*([^)]+)\)/g;
+    for (const match of source.matchAll(storageRegex)) {
+      if (metadata.storage.some((b) => b.name === match[1])) {
+        throw new Error(`@storage("${match[1]}") is declared twice`);
+      }
+      metadata.storage.push({ name: match[1], type: match[2].trim() });
+    }
+    if (isTopLevel) {
+      source = source.replace(storageRegex, "");
+      for (const buffer of metadata.storage) buffer.size = this.wgslLayout(buffer.type, source).size;
+    }
+
+    // Find all @canvas("name", width, height[, format]) declarations
+    const canvasRegex = /@canvas\("([^"]+)"\s*,\s*(\d+)u?\s*,\s*(\d+)u?\s*(?:,\s*(\w+)\s*)?\)/g;
+    for (const match of source.matchAll(canvasRegex)) {
+      const format = match[4] || "rgba8unorm";
+      if (!CANVAS_FORMATS.includes(format)) {
+        throw new Error(`@canvas format must be one of ${CANVAS_FORMATS.join(", ")}`);
+      }
+      if (Number(match[2]) === 0 || Number(match[3]) === 0) {
+        throw new Error("@canvas needs a width and height above 0");
+      }
+      if (metadata.canvases.some((c) => c.name === match[1])) {
+        throw new Error(`@canvas("${match[1]}") is declared twice`);
+      }
+      metadata.canvases.push({ name: match[1], width: Number(match[2]), height: Number(match[3]), format });
+    }
+    if (isTopLevel) source = source.replace(canvasRegex, "");
+
+    if (!metadata.dispatches.some((d) => d.entryPoint === "update")) {
+      metadata.dispatches.unshift({ entryPoint: "update", workgroups: [() => 1, () => 1, () => 1] });
+    }
+
+    // A `@compute fn init()` runs on load and on reset; @engine.reset() requests one from the shader
+    if (isTopLevel) {
+      const init = source.match(/@(vertex|fragment|compute)\b[^{;]*?\bfn\s+init\s*\(/);
+      if (init && init[1] !== "compute") {
+        throw new Error("'init' is the entry point the engine runs on load and on reset, so it must be @compute");
+      }
+      metadata.hasInit = Boolean(init);
+      metadata.usesReset = /@engine\.reset\s*\(\s*\)/.test(source);
+      metadata.usesQuit = /@engine\.quit\s*\(\s*\)/.test(source);
+      metadata.usesAudioAnalysis = /[@_]engine\.(spectrum|waveform|audio_level)\b/.test(source);
+
+      // @persist marks the struct kept in the save file as @engine.persist
+      const persists = [...source.matchAll(/@persist\b(\s*struct\s+([A-Za-z_]\w*))?/g)];
+      if (persists.some((m) => !m[1])) {
+        throw new Error("@persist must come right before a struct declaration, e.g. @persist struct SaveData { ... }");
+      }
+      if (persists.length > 1) throw new Error("only one struct can be @persist");
+      if (persists.length === 0 && /@engine\.(persist|save|load)\b/.test(source)) {
+        const member = source.match(/@engine\.(persist|save|load)\b/)[1];
+        throw new Error(`@engine.${member} needs a @persist struct to hold the saved data`);
+      }
+      if (persists.length === 1) {
+        const name = persists[0][2];
+        const { size, align, fields } = this.wgslLayout(name, source);
+        // Same layout string and key as the native runtime: the title made filename-safe and an FNV-1a hash of the layout
+        const layout = `${name}{${fields.map((f) => `${f.name}:${f.offset}:${f.size}`).join(",")}}${size}`;
+        let hash = 0x811c9dc5;
+        for (const byte of new TextEncoder().encode(layout)) hash = Math.imul(hash ^ byte, 0x01000193) >>> 0;
+        const title = metadata.title.replace(/[^A-Za-z0-9]/gu, "_").toLowerCase();
+        metadata.persist = { struct: name, size, align, layout, key: `${title}-${hash.toString(16).padStart(8, "0")}` };
+        source = source.replace(/@persist\b/g, "");
+      }
+
+      // @test marks compute entry points run by `wgsleng test`; the web runtime only needs them to compile
+      if (/@test\b(?!\s*(@\w+(\([^)]*\))?\s*)*fn\s+[A-Za-z_])/.test(source)) {
+        throw new Error("@test must come right before a compute function, e.g. @test @compute @workgroup_size(1) fn test_move() { ... }");
+      }
+      source = source.replace(/@test\b/g, "");
+
+      // @assert(condition, "message") becomes _assert(id, condition) and @print("format {}", values...)
+      // becomes _print(id, array<f32, N>(f32(value), ..., 0.0)); ids in source order like native
+      metadata.asserts = [];
+      metadata.prints = [];
+      const calls = [];
+      for (const match of source.matchAll(/@(assert|print)(?:\[([^\]]*)\])?\(/g)) {
+        if (calls.length > 0 && match.index < calls[calls.length - 1].end) {
+          throw new Error(`@${match[1]} can't be nested`);
+        }
+        const args = [""];
+        let depth = 1;
+        let end = match.index + match[0].length;
+        while (end < source.length && depth > 0) {
+          const c = source[end++];
+          if (c === '"') {
+            const close = source.indexOf('"', end);
+            args[args.length - 1] += source.slice(end - 1, close === -1 ? source.length : close + 1);
+            end = close === -1 ? source.length : close + 1;
+            continue;
+          }
+          if (c === "(") depth++;
+          if (c === ")" && --depth === 0) break;
+          if (c === "," && depth === 1) args.push("");
+          else args[args.length - 1] += c;
+        }
+        const [first, ...rest] = args.map((arg) => arg.trim());
+        if (match[1] === "assert") {
+          const message = rest.length === 1 && rest[0].match(/^"([^"]*)"$/);
+          if (!first || !message) {
+            throw new Error('@assert expects a condition and a message, e.g. @assert(score == 10.0, "eating food scores 10")');
+          }
+          calls.push({ start: match.index, end, id: metadata.asserts.length, values: [first] });
+          metadata.asserts.push({ condition: first, message: message[1] });
+        } else {
+          const format = first.match(/^"([^"]*)"$/);
+          if (!format) throw new Error(`@print expects a string literal, found '${first}'`);
+          let placeholders = 0;
+          for (const brace of format[1].match(/\{\{|\}\}|\{\}|[{}]/g) || []) {
+            if (brace === "{}") placeholders++;
+            else if (brace.length === 1) throw new Error("bad @print format string: use {} for a value, and {{ or }} for a literal brace");
+          }
+          if (placeholders !== rest.length) {
+            throw new Error(`@print format string has ${placeholders} {} but ${rest.length} value${rest.length === 1 ? " is" : "s are"} given, e.g. @print("x = {}", @engine.state.x)`);
+          }
+          calls.push({ start: match.index, end, id: metadata.prints.length, values: rest, print: true });
+          metadata.prints.push({ format: format[1], location: match[2] || "main.wgsl", values: rest.length });
+        }
+      }
+      const printValues = Math.max(1, ...metadata.prints.map((p) => p.values));
+      for (const { start, end, id, values, print } of calls.reverse()) {
+        const replacement = print
+          ? `_print(${id}u, array<f32, ${printValues}>(${[...values.map((v) => `f32(${v})`), ...Array(printValues - values.length).fill("0.0")].join(", ")}))`
+          : `_assert(${id}u, ${values[0]})`;
+        source = source.slice(0, start) + replacement + source.slice(end);
+      }
+      metadata.printValues = printValues;
+    }
+
     // Remove @set_* directives
     source = source.replace(/@set_title\([^)]+\)[^\n]*/g, "");
     source = source.replace(/@set_size\([^)]+\)[^\n]*/g, "");
+    source = source.replace(/@set_voices\([^)]+\)[^\n]*/g, "");
+    source = source.replace(/@set_music_loop\([^)]+\)[^\n]*/g, "");
 
     // Find GameState struct to inject before GameEngineHost
     const gameStateMatch = source.match(/struct GameState\s*{[^}]+}/s);
@@ -497,8 +997,14 @@ class WGSLGameEngine {
         header += `${gameStateStruct}\n\n`;
       }
 
+      if (metadata.sounds.length > 0) {
+        header += `// A @sound() call: which sound, whether it loops (1) or stops (2), and how it plays\n`;
+        header += `struct _SoundTrigger {\n    sound: u32,\n    flags: u32,\n    volume: f32,\n    pitch: f32,\n    pan: f32,\n}\n\n`;
+      }
+
       // Add GameEngineHost struct
       header += `// Engine host struct that contains all engine state\n`;
+      const hostStart = header.length;
       header += `struct GameEngineHost {\n`;
       header += `    buttons: array<i32, 12>, // the current state of virtual SNES gamepad (BTN_*)\n`;
       header += `    time: f32, // clock time\n`;
@@ -510,11 +1016,41 @@ class WGSLGameEngine {
         header += `    state: GameState, // user's game state that persists across frames\n`;
       }
       if (metadata.sounds.length > 0) {
-        header += `    audio: array<u32, ${metadata.sounds.length}>, // audio trigger counters\n`;
+        header += `    sound_count: atomic<u32>, // @sound() calls this frame; the runtime reads them back and empties the queue\n`;
+        header += `    sound_triggers: array<_SoundTrigger, ${MAX_SOUND_TRIGGERS}>, // the first of them, in order\n`;
+        header += `    sound_voices: array<u32, ${metadata.sounds.length}>, // voices of each sound playing, written by the runtime\n`;
+      }
+      if (metadata.music.length > 0) {
+        header += `    music_time: f32, // seconds into the current @music() track, written by the runtime\n`;
+        header += `    music_duration: f32, // the current track's length in seconds\n`;
+        header += `    music_track: u32, // which track is playing, from 1 (0 if none is or it's paused)\n`;
+        header += `    music_command: u32, // the frame's last @music() call: 1 play, 2 loop, 3 pause, 4 stop; the runtime reads it back and clears it\n`;
+        header += `    music_command_track: u32, // the track it was for\n`;
+        header += `    music_command_fade: f32, // seconds to fade over\n`;
+      }
+      if (metadata.usesAudioAnalysis) {
+        header += `    audio_level: f32, // RMS level of everything playing, written by the runtime\n`;
+        header += `    spectrum: array<f32, ${SPECTRUM_SIZE}>, // its spectrum, 0 to 1 a bin from 0 Hz up to half the sample rate\n`;
+        header += `    waveform: array<f32, ${WAVEFORM_SIZE}>, // its latest samples, mixed down to mono\n`;
       }
       header += `    osc: array<f32, 64>, // OSC float uniforms: /u/name or /u/N\n`;
       header += `    keys: array<u32, 194>, // raw key state: 1=down, 0=up, indexed by KEY_* constants\n`;
+      header += `    reset: u32, // set by @engine.reset(): the runtime clears GameState and runs init() before the next frame\n`;
+      header += `    save: u32, // set by @engine.save(): the runtime writes @engine.persist to the save file\n`;
+      header += `    load: u32, // set by @engine.load(): the runtime reads the save file back into @engine.persist\n`;
+      header += `    quit: u32, // set by @engine.quit(): the runtime stops the game\n`;
+      if (metadata.persist) {
+        header += `    persist: ${metadata.persist.struct}, // @persist struct, loaded from the game's save file on startup\n`;
+      }
+      if (metadata.asserts.length > 0) {
+        header += `    asserts: array<u32, ${metadata.asserts.length}>, // set by failing @assert()s, read back by wgsleng test\n`;
+      }
       header += `}\n\n`;
+      // The vertex stage's read-only copy can't hold an atomic, so it's the same struct with a plain sound_count
+      header += header
+        .slice(hostStart)
+        .replace("struct GameEngineHost", "struct _GameEngineView")
+        .replace("sound_count: atomic<u32>", "sound_count: u32");
 
       // Add button constants
       header += `// Button constants for input\n`;
@@ -645,8 +1181,52 @@ class WGSLGameEngine {
         header += `@group(0) @binding(${cameraBase + i}) var _camera_${i}: texture_2d<f32>; // camera ${camIdx}\n`;
       });
 
+      // Add pass buffer bindings
+      const bufferBase = cameraBase + metadata.cameras.length;
+      metadata.passes.forEach((pass, i) => {
+        header += `@group(0) @binding(${bufferBase + i}) var _buffer_${i}: texture_2d<f32>; // @pass("${pass.name}")\n`;
+      });
+
+      // Add canvas bindings: sampled for render stages, then write-only storage for compute
+      const canvasBase = bufferBase + metadata.passes.length;
+      metadata.canvases.forEach((canvas, i) => {
+        header += `@group(0) @binding(${canvasBase + i}) var _canvas_${i}: texture_2d<f32>; // @canvas("${canvas.name}")\n`;
+      });
+      const canvasStorageBase = canvasBase + metadata.canvases.length;
+      metadata.canvases.forEach((canvas, i) => {
+        header += `@group(0) @binding(${canvasStorageBase + i}) var _canvas_${i}_storage: texture_storage_2d<${canvas.format}, write>; // @canvas("${canvas.name}").storage\n`;
+      });
+
       // Add engine buffer
       header += `\n@group(1) @binding(0) var<storage, read_write> _engine: GameEngineHost;\n`;
+      header += `@group(1) @binding(1) var<storage, read> _engine_view: _GameEngineView; // @engine in code the vertex stage runs\n`;
+      metadata.storage.forEach((buffer, i) => {
+        header += `@group(1) @binding(${i + 2}) var<storage, read_write> _storage_${i}: ${buffer.type}; // @storage("${buffer.name}")\n`;
+      });
+      if (metadata.passes.length > 0) {
+        header += `\n// @pass() targets are drawn by one triangle that covers them, whatever vs_main draws\n`;
+        header += `struct _PassVertex {\n    @builtin(position) position: vec4f,\n    @location(0) uv: vec2f,\n}\n`;
+        header += `@vertex\nfn ${PASS_VERTEX_ENTRY}(@builtin(vertex_index) i: u32) -> _PassVertex {\n    let p = vec2f(f32((i << 1u) & 2u), f32(i & 2u));\n    return _PassVertex(vec4f(p * 2.0 - 1.0, 0.0, 1.0), vec2f(p.x, 1.0 - p.y));\n}\n`;
+      }
+      if (metadata.asserts.length > 0) {
+        header += `\n// @assert(condition, "message") records a failure for wgsleng test to report\n`;
+        header += `fn _assert(id: u32, condition: bool) {\n    if (!condition) {\n        _engine.asserts[id] = 1u;\n    }\n}\n`;
+      }
+      if (metadata.sounds.length > 0) {
+        header += `\n// @sound("x").play(), .loop() and .stop() queue a trigger for the runtime; past ${MAX_SOUND_TRIGGERS} a frame they're only counted\n`;
+        header += `fn _sound(sound: u32, flags: u32, volume: f32, pitch: f32, pan: f32) {\n    let i = atomicAdd(&_engine.sound_count, 1u);\n    if (i < ${MAX_SOUND_TRIGGERS}u) {\n        _engine.sound_triggers[i] = _SoundTrigger(sound, flags, volume, pitch, pan);\n    }\n}\n`;
+      }
+      if (metadata.music.length > 0) {
+        header += `\n// @music("x").play(), .loop(), .pause() and .stop() leave a command for the runtime; the last one a frame wins\n`;
+        header += `fn _music(track: u32, command: u32, fade: f32) {\n    _engine.music_command = command;\n    _engine.music_command_track = track;\n    _engine.music_command_fade = fade;\n}\n`;
+      }
+      if (metadata.prints.length > 0) {
+        header += `\n// @print("format {}", values...) claims a slot for the runtime to log; past ${MAX_PRINTS_PER_FRAME} a frame they're only counted\n`;
+        header += `struct _PrintSlot {\n    id: u32,\n    values: array<f32, ${metadata.printValues}>,\n}\n`;
+        header += `struct _Prints {\n    count: atomic<u32>,\n    slots: array<_PrintSlot, ${MAX_PRINTS_PER_FRAME}>,\n}\n`;
+        header += `@group(1) @binding(${metadata.storage.length + 2}) var<storage, read_write> _prints: _Prints;\n`;
+        header += `fn _print(id: u32, values: array<f32, ${metadata.printValues}>) {\n    let slot = atomicAdd(&_prints.count, 1u);\n    if (slot < ${MAX_PRINTS_PER_FRAME}u) {\n        _prints.slots[slot] = _PrintSlot(id, values);\n    }\n}\n`;
+      }
 
       // Add model buffers
       if (metadata.models && metadata.models.length > 0) {
@@ -670,6 +1250,8 @@ class WGSLGameEngine {
 
     // Replace macros in source
     // Replace @engine.* with _engine.*
+    source = source.replace(/@engine\.(reset|save|load|quit)\s*\(\s*\)/g, "_engine.$1 = 1u");
+    source = source.replace(/@engine\.persist/g, "_engine.persist");
     source = source.replace(/@engine\.buttons/g, "_engine.buttons");
     source = source.replace(/@engine\.time/g, "_engine.time");
     source = source.replace(/@engine\.delta_time/g, "_engine.delta_time");
@@ -679,30 +1261,74 @@ class WGSLGameEngine {
     source = source.replace(/@engine\.keys/g, "_engine.keys");
     source = source.replace(/@engine\.sampler/g, "_engine_sampler");
     source = source.replace(/@engine\.state/g, "_engine.state");
+    if (metadata.music.length === 0 && /@engine\.music_(time|duration)\b/.test(source)) {
+      throw new Error(`@engine.music_${source.match(/@engine\.music_(time|duration)\b/)[1]} needs a @music() track`);
+    }
+    source = source.replace(/@engine\.music_time\b/g, "_engine.music_time");
+    source = source.replace(/@engine\.music_duration\b/g, "_engine.music_duration");
+    source = source.replace(/@engine\.(spectrum|waveform|audio_level)\b/g, "_engine.$1");
+
+    // @sound("x").play(volume, pitch, pan) becomes _sound(i, flags, volume, pitch, pan) with the
+    // arguments it wasn't given filled in; .loop() flags it 1 and .stop() 2
+    const soundDefaults = ["1.0", "1.0", "0.0"];
+    const soundCalls = [...source.matchAll(/@sound\("([^"]+)"\)\.(\w+)\(/g)];
+    for (const match of soundCalls.reverse()) {
+      const [, name, method] = match;
+      const flags = { play: 0, loop: 1, stop: 2 }[method];
+      if (flags === undefined) {
+        throw new Error(`unknown sound method '.${method}()', expected .play(), .loop(), .stop() or .is_playing`);
+      }
+      const args = [""];
+      let depth = 1;
+      let end = match.index + match[0].length;
+      while (end < source.length && depth > 0) {
+        const c = source[end++];
+        if (c === "(" || c === "[") depth++;
+        if ((c === ")" || c === "]") && --depth === 0) break;
+        if (c === "," && depth === 1) args.push("");
+        else args[args.length - 1] += c;
+      }
+      const values = args.map((arg) => arg.trim()).filter((arg) => arg !== "");
+      if (method === "stop" && values.length > 0) throw new Error("@sound(...).stop() takes no arguments");
+      if (values.length > 3) throw new Error(`@sound(...).${method}() takes up to 3 arguments: volume, pitch and pan`);
+      const replacement = `_sound(${metadata.sounds.indexOf(name)}u, ${flags}u, ${[...values, ...soundDefaults.slice(values.length)].join(", ")})`;
+      source = source.slice(0, match.index) + replacement + source.slice(end);
+    }
 
-    // Replace @sound().play() and @sound().stop() with audio trigger operations
     metadata.sounds.forEach((soundName, i) => {
       const escapedName = soundName.replace(/\./g, "\\.");
-      // Replace @sound("file").play() with _engine.audio[index]++ (triggers playback)
-      const playRegex = new RegExp(
-        `@sound\\("${escapedName}"\\)\\.play\\(\\)`,
-        "g",
-      );
-      source = source.replace(playRegex, `_engine.audio[${i}]++`);
-
-      // Replace @sound("file").stop() with no-op for now (could implement later)
-      const stopRegex = new RegExp(
-        `@sound\\("${escapedName}"\\)\\.stop\\(\\)`,
-        "g",
-      );
-      source = source.replace(
-        stopRegex,
-        `/* stop sound ${i} - not implemented */`,
-      );
+      source = source.replace(new RegExp(`@sound\\("${escapedName}"\\)\\.is_playing\\b`, "g"), `(_engine.sound_voices[${i}] > 0u)`);
+      // Legacy @sound("file")++ syntax plays it
+      source = source.replace(new RegExp(`@sound\\("${escapedName}"\\)\\+\\+`, "g"), `_sound(${i}u, 0u, ${soundDefaults.join(", ")})`);
+    });
 
-      // Also support legacy @sound("file")++ syntax
-      const legacyRegex = new RegExp(`@sound\\("${escapedName}"\\)`, "g");
-      source = source.replace(legacyRegex, `_engine.audio[${i}]`);
+    // @music("x").loop(fade) becomes _music(i, command, fade)
+    const musicCalls = [...source.matchAll(/@music\("([^"]+)"\)\.(\w+)\(/g)];
+    for (const match of musicCalls.reverse()) {
+      const [, name, method] = match;
+      const command = { play: 1, loop: 2, pause: 3, stop: 4 }[method];
+      if (command === undefined) {
+        throw new Error(`unknown music method '.${method}()', expected .play(), .loop(), .pause(), .stop() or .is_playing`);
+      }
+      const args = [""];
+      let depth = 1;
+      let end = match.index + match[0].length;
+      while (end < source.length && depth > 0) {
+        const c = source[end++];
+        if (c === "(" || c === "[") depth++;
+        if ((c === ")" || c === "]") && --depth === 0) break;
+        if (c === "," && depth === 1) args.push("");
+        else args[args.length - 1] += c;
+      }
+      const values = args.map((arg) => arg.trim()).filter((arg) => arg !== "");
+      if (method === "pause" && values.length > 0) throw new Error("@music(...).pause() takes no arguments");
+      if (values.length > 1) throw new Error(`@music(...).${method}() takes at most 1 argument: the seconds to fade over`);
+      const replacement = `_music(${metadata.music.indexOf(name)}u, ${command}u, ${values[0] ?? "0.0"})`;
+      source = source.slice(0, match.index) + replacement + source.slice(end);
+    }
+    metadata.music.forEach((trackName, i) => {
+      const escapedName = trackName.replace(/\./g, "\\.");
+      source = source.replace(new RegExp(`@music\\("${escapedName}"\\)\\.is_playing\\b`, "g"), `(_engine.music_track == ${i + 1}u)`);
     });
 
     // Replace @texture() with _texture_index
@@ -736,6 +1362,27 @@ class WGSLGameEngine {
       source = source.replace(regex, `_camera_${i}`);
     });
 
+    // Replace @buffer(), @storage() and @canvas() once every file's declarations are known
+    if (isTopLevel) {
+      source = source.replace(/@canvas\("([^"]+)"\)(?:\.(\w+))?/g, (_, name, member) => {
+        const i = metadata.canvases.findIndex((c) => c.name === name);
+        if (i < 0) throw new Error(`no @canvas("${name}", width, height) declared`);
+        if (member === undefined) return `_canvas_${i}`;
+        if (member === "storage") return `_canvas_${i}_storage`;
+        throw new Error(`unknown canvas field '.${member}', expected .storage`);
+      });
+      source = source.replace(/@storage\("([^"]+)"\)/g, (_, name) => {
+        const i = metadata.storage.findIndex((b) => b.name === name);
+        if (i < 0) throw new Error(`no @storage("${name}", type) declared`);
+        return `_storage_${i}`;
+      });
+      source = source.replace(/@buffer\("([^"]+)"\)/g, (_, name) => {
+        const i = metadata.passes.findIndex((p) => p.name === name);
+        if (i < 0) throw new Error(`no @pass named "${name}" for @buffer`);
+        return `_buffer_${i}`;
+      });
+    }
+
     // Replace @osc()
     metadata.oscParams.forEach((oscName, i) => {
       const escaped = oscName.replace(/\./g, "\\.");
@@ -791,12 +1438,94 @@ class WGSLGameEngine {
       source = source.replace(match[0], replacement);
     }
 
+    if (isTopLevel) source = this.routeVertexEngineReads(source, header);
+
     return {
       code: header + source,
       metadata,
     };
   }
 
+  // Vertex shaders can't bind the read-write engine buffer, so functions reachable
+  // from a @vertex entry point read _engine_view instead
+  routeVertexEngineReads(source, header) {
+    // Comments blanked out so braces and names inside them don't count
+    const mask = (text) => text.replace(/\/\/[^\n]*|\/\*[\s\S]*?\*\//g, (c) => c.replace(/[^\n]/g, " "));
+    const findFunctions = (masked) => {
+      const found = new Map();
+      for (const match of masked.matchAll(/(?:@(vertex|fragment|compute)\b[^{;]*?)?\bfn\s+([A-Za-z_]\w*)/g)) {
+        const open = masked.indexOf("{", match.index + match[0].length);
+        if (open < 0) continue;
+        let depth = 0;
+        let close = open;
+        for (; close < masked.length; close++) {
+          if (masked[close] === "{") depth++;
+          else if (masked[close] === "}" && --depth === 0) break;
+        }
+        found.set(match[2], { stage: match[1], start: open, end: close });
+      }
+      return found;
+    };
+    const masked = mask(source);
+    const functions = findFunctions(masked);
+
+    // An assignment, increment or atomic on anything inside _engine
+    const writesEngine = (body) =>
+      /\b_engine\b(?:\s*\.\s*\w+|\s*\[[^\]]*\])*\s*(?:(?:[-+*\/%&|^]|<<|>>)?=(?!=)|\+\+|--)|&\s*_engine\b/.test(body);
+    // Header helpers behind @sound(), @music() and the rest that write to _engine
+    const maskedHeader = mask(header);
+    const helpers = new Set(
+      [...findFunctions(maskedHeader)].filter(([, f]) => writesEngine(maskedHeader.slice(f.start, f.end))).map(([name]) => name),
+    );
+
+    const reachable = (stage) => {
+      const seen = new Set([...functions].filter(([, f]) => f.stage === stage).map(([name]) => name));
+      const pending = [...seen];
+      while (pending.length > 0) {
+        const { start, end } = functions.get(pending.pop());
+        for (const call of masked.slice(start, end).matchAll(/\b([A-Za-z_]\w*)\s*\(/g)) {
+          if (functions.has(call[1]) && !seen.has(call[1])) {
+            seen.add(call[1]);
+            pending.push(call[1]);
+          }
+        }
+      }
+      return seen;
+    };
+    const vertex = reachable("vertex");
+    const compute = reachable("compute");
+
+    // Nothing the vertex stage runs may write to @engine, directly or through a header helper
+    for (const name of vertex) {
+      const { start, end } = functions.get(name);
+      const body = masked.slice(start, end);
+      const calls = [...body.matchAll(/\b([A-Za-z_]\w*)\s*\(/g)].map((call) => call[1]);
+      if (writesEngine(body) || calls.some((call) => helpers.has(call))) {
+        throw new Error(
+          `'${name}' runs in the vertex stage, which only gets a read-only copy of @engine, so it can't change @engine or play @sound()s and @music(); do that from a compute entry point`,
+        );
+      }
+    }
+
+    const spans = [];
+    for (const name of vertex) {
+      const { start, end } = functions.get(name);
+      for (const use of masked.slice(start, end).matchAll(/\b_engine\b/g)) {
+        if (compute.has(name)) {
+          throw new Error(
+            `'${name}' uses @engine and is called from both vertex and compute entry points; the vertex stage only gets a read-only copy, so give it its own function`,
+          );
+        }
+        spans.push(start + use.index + "_engine".length);
+      }
+    }
+    spans.sort((a, b) => b - a);
+    for (const at of spans) {
+      source = source.slice(0, at) + "_view" + source.slice(at);
+    }
+    return source;
+  }
+
   showError(msg) {
     console.error(msg);
     this.handleError(msg);
@@ -871,15 +1600,23 @@ class WGSLGameEngine {
     const doZip = entryBytes[0] === 0x50 && entryBytes[1] === 0x4b;
 
     // use this to read a file from the current "filesystem" (URL or zip)
+    let readGameFile;
     if (doZip) {
-      this.readFile = async (name) => extractSingleFile(entryBytes, name);
+      readGameFile = async (name) => extractSingleFile(entryBytes, name);
     } else {
       const urlBase = gamePath.replace(/\/?main\.wgsl$/, "") || ".";
-      this.readFile = async (name) =>
+      readGameFile = async (name) =>
         new Uint8Array(
           await fetch(`${urlBase}/${name}`).then((r) => r.arrayBuffer()),
         );
     }
+    // std: files are the built-in library, served next to this script
+    this.readFile = async (name) => {
+      if (!name.startsWith("std:")) return readGameFile(name);
+      const response = await fetch(new URL(`native/std/${name.slice(4)}`, import.meta.url));
+      if (!response.ok) throw new Error(`no library file named ${name}`);
+      return new Uint8Array(await response.arrayBuffer());
+    };
     this.readFileText = async (name) =>
       decoder.decode(await this.readFile(name));
 
@@ -912,6 +1649,19 @@ class WGSLGameEngine {
     this.soundFiles = metadata.sounds;
     this.textureFiles = metadata.textures;
     this.audioCount = metadata.sounds.length;
+    this.soundVoices = metadata.soundVoices;
+    // The voices of each sound, timed on the game clock for @sound("x").is_playing like native.
+    // Loops of a game being replaced stop with it; its one-shots play out
+    for (const voice of (this.voices || []).flat()) {
+      if (voice.ends === null) voice.source?.stop();
+    }
+    this.voices = metadata.sounds.map(() => []);
+    // The @music() track playing stops with its game, and any fading out with it
+    for (const playing of [this.musicCurrent, ...(this.musicFading || [])]) playing?.source?.stop();
+    this.musicFiles = metadata.music;
+    this.musicLoops = metadata.musicLoops;
+    this.musicCurrent = null;
+    this.musicFading = [];
     this.textureCount = metadata.textures.length;
     this.stateSize = metadata.stateSize;
 
@@ -922,6 +1672,18 @@ class WGSLGameEngine {
     this.videoFiles = metadata.videos || [];
     this.cameraIndices = metadata.cameras || [];
     this.oscParams = metadata.oscParams || [];
+    this.dispatches = metadata.dispatches;
+    this.hasInit = metadata.hasInit;
+    this.usesReset = metadata.usesReset;
+    this.usesQuit = metadata.usesQuit;
+    this.usesAudioAnalysis = metadata.usesAudioAnalysis;
+    this.persist = metadata.persist;
+    this.assertCount = metadata.asserts.length;
+    this.prints = metadata.prints;
+    this.printValues = metadata.printValues;
+    this.passes = metadata.passes;
+    this.storage = metadata.storage;
+    this.canvases = metadata.canvases;
     this.oscValues = new Float32Array(64); // 64 OSC float slots
 
     // Load textures
@@ -936,8 +1698,9 @@ class WGSLGameEngine {
     // Load models
     await this.loadModels();
 
-    // Load sounds
+    // Load sounds and music
     await this.loadSounds();
+    await this.loadMusic();
 
     // Create shader module
     this.shaderModule = this.device.createShaderModule({
@@ -961,12 +1724,12 @@ class WGSLGameEngine {
     this.setupBuffers();
 
     // Create explicit bind group layouts
-    // Group 0: sampler (always) and textures (if present)
+    // Group 0: sampler (always) and textures (if present); these, videos and cameras are in every stage
     const renderGroup0Entries = [
       // Sampler (always present since preprocessor always adds it)
       {
         binding: 0,
-        visibility: GPUShaderStage.FRAGMENT,
+        visibility: GPUShaderStage.VERTEX | GPUShaderStage.FRAGMENT,
         sampler: { type: "filtering" },
       },
     ];
@@ -975,7 +1738,7 @@ class WGSLGameEngine {
     for (let i = 0; i < this.textureFiles.length; i++) {
       renderGroup0Entries.push({
         binding: i + 1,
-        visibility: GPUShaderStage.FRAGMENT,
+        visibility: GPUShaderStage.VERTEX | GPUShaderStage.FRAGMENT,
         texture: { sampleType: "float", viewDimension: "2d" },
       });
     }
@@ -985,7 +1748,7 @@ class WGSLGameEngine {
     for (let i = 0; i < this.videoFiles.length; i++) {
       renderGroup0Entries.push({
         binding: jsVideoBase + i,
-        visibility: GPUShaderStage.FRAGMENT,
+        visibility: GPUShaderStage.VERTEX | GPUShaderStage.FRAGMENT,
         texture: { sampleType: "float", viewDimension: "2d" },
       });
     }
@@ -995,6 +1758,26 @@ class WGSLGameEngine {
     for (let i = 0; i < this.cameraIndices.length; i++) {
       renderGroup0Entries.push({
         binding: jsCameraBase + i,
+        visibility: GPUShaderStage.VERTEX | GPUShaderStage.FRAGMENT,
+        texture: { sampleType: "float", viewDimension: "2d" },
+      });
+    }
+
+    // Add @pass() buffer bindings
+    const jsBufferBase = jsCameraBase + this.cameraIndices.length;
+    for (let i = 0; i < this.passes.length; i++) {
+      renderGroup0Entries.push({
+        binding: jsBufferBase + i,
+        visibility: GPUShaderStage.FRAGMENT,
+        texture: { sampleType: "float", viewDimension: "2d" },
+      });
+    }
+
+    // Add @canvas() bindings (sampled; the storage views are only bound for compute)
+    const jsCanvasBase = jsBufferBase + this.passes.length;
+    for (let i = 0; i < this.canvases.length; i++) {
+      renderGroup0Entries.push({
+        binding: jsCanvasBase + i,
         visibility: GPUShaderStage.FRAGMENT,
         texture: { sampleType: "float", viewDimension: "2d" },
       });
@@ -1005,19 +1788,26 @@ class WGSLGameEngine {
       entries: renderGroup0Entries,
     });
 
-    // Group 1: engine buffer (storage for fragment only)
+    // Group 1: engine buffer (fragment only), its read-only copy for vertex, then @storage() buffers and the print buffer
+    const printBinding = this.prints.length > 0 ? [this.storage.length + 2] : [];
     this.renderBindGroupLayout1 = this.device.createBindGroupLayout({
       label: "Render Bind Group Layout 1",
       entries: [
+        { binding: 0, visibility: GPUShaderStage.FRAGMENT, buffer: { type: "storage" } },
         {
-          binding: 0,
+          binding: 1,
+          visibility: GPUShaderStage.VERTEX | GPUShaderStage.FRAGMENT,
+          buffer: { type: "read-only-storage" },
+        },
+        ...[...this.storage.map((_, i) => i + 2), ...printBinding].map((binding) => ({
+          binding,
           visibility: GPUShaderStage.FRAGMENT,
           buffer: { type: "storage" },
-        },
+        })),
       ],
     });
 
-    // Group 2: model buffers (if models exist)
+    // Group 2: model buffers (if models exist), shared with compute
     const modelGroup2Entries = [];
     if (this.models && this.models.length > 0) {
       this.models.forEach((model, i) => {
@@ -1025,13 +1815,13 @@ class WGSLGameEngine {
         // Positions buffer
         modelGroup2Entries.push({
           binding: bindingBase,
-          visibility: GPUShaderStage.VERTEX | GPUShaderStage.FRAGMENT,
+          visibility: GPUShaderStage.VERTEX | GPUShaderStage.FRAGMENT | GPUShaderStage.COMPUTE,
           buffer: { type: "read-only-storage" },
         });
         // Normals buffer
         modelGroup2Entries.push({
           binding: bindingBase + 1,
-          visibility: GPUShaderStage.VERTEX | GPUShaderStage.FRAGMENT,
+          visibility: GPUShaderStage.VERTEX | GPUShaderStage.FRAGMENT | GPUShaderStage.COMPUTE,
           buffer: { type: "read-only-storage" },
         });
       });
@@ -1045,18 +1835,30 @@ class WGSLGameEngine {
           })
         : null;
 
-    // Compute bind group layout for engine buffer (read-write)
-    this.computeBindGroupLayout1 = this.device.createBindGroupLayout({
-      label: "Compute Bind Group Layout 1",
+    // Compute group 0: the sampler, textures, videos and cameras, then write-only storage views of the @canvas() textures
+    const jsCanvasStorageBase = jsCanvasBase + this.canvases.length;
+    this.computeBindGroupLayout0 = this.device.createBindGroupLayout({
+      label: "Compute Bind Group Layout 0",
       entries: [
-        {
-          binding: 0,
+        ...renderGroup0Entries.slice(0, jsBufferBase).map((entry) => ({ ...entry, visibility: GPUShaderStage.COMPUTE })),
+        ...this.canvases.map((canvas, i) => ({
+          binding: jsCanvasStorageBase + i,
           visibility: GPUShaderStage.COMPUTE,
-          buffer: { type: "storage" },
-        },
+          storageTexture: { access: "write-only", format: canvas.format, viewDimension: "2d" },
+        })),
       ],
     });
 
+    // Compute bind group layout for engine, @storage() and print buffers (read-write)
+    this.computeBindGroupLayout1 = this.device.createBindGroupLayout({
+      label: "Compute Bind Group Layout 1",
+      entries: [0, ...this.storage.map((_, i) => i + 2), ...printBinding].map((binding) => ({
+        binding,
+        visibility: GPUShaderStage.COMPUTE,
+        buffer: { type: "storage" },
+      })),
+    });
+
     // Create pipeline layouts
     const renderBindGroupLayouts = [
       this.renderBindGroupLayout0,
@@ -1071,12 +1873,17 @@ class WGSLGameEngine {
       bindGroupLayouts: renderBindGroupLayouts,
     });
 
+    const computeBindGroupLayouts = [
+      this.computeBindGroupLayout0,
+      this.computeBindGroupLayout1,
+    ];
+    if (this.renderBindGroupLayout2) {
+      computeBindGroupLayouts.push(this.renderBindGroupLayout2);
+    }
+
     const computePipelineLayout = this.device.createPipelineLayout({
       label: "Compute Pipeline Layout",
-      bindGroupLayouts: [
-        this.renderBindGroupLayout0,
-        this.computeBindGroupLayout1,
-      ],
+      bindGroupLayouts: computeBindGroupLayouts,
     });
 
     // Setup render pipeline with explicit layout
@@ -1105,14 +1912,45 @@ class WGSLGameEngine {
       },
     });
 
-    // Setup compute pipeline with explicit layout
-    this.updatePipeline = this.device.createComputePipeline({
-      layout: computePipelineLayout,
-      compute: {
-        module: this.shaderModule,
-        entryPoint: "update",
-      },
-    });
+    // The game's init(), run before the first frame and after a reset
+    this.initPipeline = this.hasInit
+      ? this.device.createComputePipeline({
+          layout: computePipelineLayout,
+          compute: { module: this.shaderModule, entryPoint: "init" },
+        })
+      : null;
+    this.initPending = true;
+
+    // One compute pipeline per @dispatch(), run in order each frame
+    this.computePipelines = this.dispatches.map((dispatch) => ({
+      dispatch,
+      pipeline: this.device.createComputePipeline({
+        layout: computePipelineLayout,
+        compute: {
+          module: this.shaderModule,
+          entryPoint: dispatch.entryPoint,
+        },
+      }),
+    }));
+
+    // One render pipeline per @pass(), drawing into an rgba16float target without depth
+    this.passPipelines = this.passes.map((pass) =>
+      this.device.createRenderPipeline({
+        layout: renderPipelineLayout,
+        vertex: {
+          module: this.shaderModule,
+          entryPoint: PASS_VERTEX_ENTRY,
+        },
+        fragment: {
+          module: this.shaderModule,
+          entryPoint: pass.entryPoint,
+          targets: [{ format: "rgba16float" }],
+        },
+        primitive: {
+          topology: "triangle-list",
+        },
+      }),
+    );
 
     // Create bind groups using layouts from the pipelines
     this.setupBindGroups();
@@ -1329,48 +2167,160 @@ class WGSLGameEngine {
   }
 
   async loadSounds() {
-    this.soundBuffers = [];
+    // Decoded once, up front: an OfflineAudioContext can decode before the page has been interacted with
+    const decoder = new OfflineAudioContext(1, 1, 44100);
+    this.sounds = [];
 
     for (const filename of this.soundFiles) {
       try {
         // Use readFile to support both zip and directory
         const soundData = await this.readFile(filename);
-        // Convert Uint8Array to ArrayBuffer
-        this.soundBuffers.push(soundData.buffer);
+        this.sounds.push(await decoder.decodeAudioData(soundData.buffer.slice(0)));
       } catch (err) {
         console.warn(`Failed to load sound ${filename}:`, err);
-        this.soundBuffers.push(null);
+        this.sounds.push(null);
       }
     }
   }
 
-  async initAudio() {
-    if (this.audioContext) return;
-    this.audioContext = new AudioContext();
+  async loadMusic() {
+    const decoder = new OfflineAudioContext(1, 1, 44100);
+    this.music = [];
 
-    // Decode all sound buffers
-    const decodePromises = this.soundBuffers.map(async (buffer, i) => {
-      if (!buffer) return null;
+    for (const [i, filename] of this.musicFiles.entries()) {
       try {
-        return await this.audioContext.decodeAudioData(buffer.slice(0));
+        const musicData = await this.readFile(filename);
+        const buffer = await decoder.decodeAudioData(musicData.buffer.slice(0));
+        const loopEnd = Math.min(this.musicLoops[i].end ?? buffer.duration, buffer.duration);
+        const loopStart = Math.min(this.musicLoops[i].start, loopEnd);
+        this.music.push({ buffer, loopStart, loopEnd });
       } catch (err) {
-        console.warn(`Failed to decode sound ${i}:`, err);
-        return null;
+        console.warn(`Failed to load music ${filename}:`, err);
+        this.music.push(null);
       }
-    });
+    }
+  }
 
-    this.sounds = await Promise.all(decodePromises);
+  initAudio() {
+    if (this.audioContext) return;
+    this.audioContext = new AudioContext();
+    // Everything plays through one node, which an AnalyserNode taps for @engine.spectrum
+    this.audioOutput = new GainNode(this.audioContext);
+    this.audioOutput.connect(this.audioContext.destination);
+    this.analyser = new AnalyserNode(this.audioContext, { fftSize: WAVEFORM_SIZE });
+    this.audioOutput.connect(this.analyser);
+    // Music started before the page was interacted with has only been timed; it's heard from here
+    const playing = this.musicCurrent;
+    if (playing && !playing.paused) {
+      playing.offset = this.musicPosition(playing);
+      playing.startedAt = this.audioContext.currentTime;
+      playing.volume = 1;
+      this.startMusicSource(playing);
+    }
   }
 
-  playSound(index) {
-    if (!this.audioContext || !this.sounds[index]) {
-      return;
+  // The clock music is timed on: the audio device's once there is one, so @engine.music_time
+  // follows what's heard, and the game's before that
+  musicClock() {
+    return this.audioContext ? this.audioContext.currentTime : this.time;
+  }
+
+  // Seconds into its track a playing @music() track is
+  musicPosition(playing) {
+    const { loopStart, loopEnd } = this.music[playing.track];
+    let position = playing.offset + (playing.paused ? 0 : this.musicClock() - playing.startedAt);
+    if (playing.looping && position >= loopEnd && loopEnd > loopStart) {
+      position = loopStart + ((position - loopEnd) % (loopEnd - loopStart));
+    }
+    return position;
+  }
+
+  startMusicSource(playing) {
+    const { buffer, loopStart, loopEnd } = this.music[playing.track];
+    playing.source = new AudioBufferSourceNode(this.audioContext, { buffer, loop: playing.looping, loopStart, loopEnd });
+    playing.gain = new GainNode(this.audioContext, { gain: playing.volume });
+    playing.source.connect(playing.gain).connect(this.audioOutput);
+    playing.source.start(0, playing.offset);
+  }
+
+  // Stop the current track, fading it out over fade seconds if that's more than 0
+  stopMusic(fade) {
+    const playing = this.musicCurrent;
+    this.musicCurrent = null;
+    if (!playing?.source) return;
+    if (fade > 0) {
+      const now = this.audioContext.currentTime;
+      playing.gain.gain.setValueAtTime(playing.gain.gain.value, now);
+      playing.gain.gain.linearRampToValueAtTime(0, now + fade);
+      playing.source.stop(now + fade);
+      this.musicFading.push(playing);
+      playing.source.onended = () => {
+        this.musicFading = this.musicFading.filter((other) => other !== playing);
+      };
+    } else {
+      playing.source.stop();
+    }
+  }
+
+  // Carry out a @music() play(), loop(), pause() or stop(); a play() or loop() with a fade
+  // crossfades from whatever is playing
+  playMusic({ track, action, fade }) {
+    if (!this.music[track]) return;
+    fade = Math.max(fade, 0);
+    const current = this.musicCurrent?.track === track ? this.musicCurrent : null;
+    if (action === "play" || action === "loop") {
+      if (current) {
+        // Resume it if it's paused
+        if (current.paused) {
+          current.paused = false;
+          current.startedAt = this.musicClock();
+          if (this.audioContext) this.startMusicSource(current);
+        }
+        return;
+      }
+      this.stopMusic(fade);
+      const playing = { track, looping: action === "loop", paused: false, offset: 0, startedAt: this.musicClock(), volume: 1, source: null, gain: null };
+      if (this.audioContext) {
+        playing.volume = fade > 0 ? 0 : 1;
+        this.startMusicSource(playing);
+        if (fade > 0) playing.gain.gain.linearRampToValueAtTime(1, this.audioContext.currentTime + fade);
+      }
+      this.musicCurrent = playing;
+    } else if (action === "pause" && current && !current.paused) {
+      current.offset = this.musicPosition(current);
+      current.paused = true;
+      current.volume = 1;
+      current.source?.stop();
+      current.source = null;
+    } else if (action === "stop" && current) {
+      this.stopMusic(fade);
     }
+  }
 
-    const source = this.audioContext.createBufferSource();
-    source.buffer = this.sounds[index];
-    source.connect(this.audioContext.destination);
-    source.start();
+  // Carry out a @sound() play(), loop() or stop(); before the page has been interacted with
+  // there's no AudioContext, so the voice is only timed
+  playSound({ sound, looping, stop, volume, pitch, pan }) {
+    const voices = this.voices[sound];
+    if (!voices) return;
+    if (stop) {
+      for (const voice of voices.splice(0)) voice.source?.stop();
+      return;
+    }
+    const buffer = this.sounds[sound];
+    if (!buffer) return;
+
+    // Past the voice limit, the oldest voice makes way
+    if (voices.length >= this.soundVoices[sound]) voices.shift().source?.stop();
+    pitch = Math.max(pitch, 0.01);
+    let source = null;
+    if (this.audioContext) {
+      source = new AudioBufferSourceNode(this.audioContext, { buffer, loop: looping, playbackRate: pitch });
+      const panner = new StereoPannerNode(this.audioContext, { pan: Math.min(Math.max(pan, -1), 1) });
+      const gain = new GainNode(this.audioContext, { gain: Math.max(volume, 0) });
+      source.connect(panner).connect(gain).connect(this.audioOutput);
+      source.start();
+    }
+    voices.push({ source, ends: looping ? null : this.time + buffer.duration / pitch });
   }
 
   setupBuffers() {
@@ -1383,7 +2333,15 @@ class WGSLGameEngine {
     //   screen_height: f32 at offset 60 (4 bytes)
     //   mouse: vec4f at offset 64 (16 bytes, 16-byte aligned)
     //   state: GameState at offset 80 (aligned to 8 bytes for vec2f)
-    //   audio: array<u32, N> at offset 80 + stateSize
+    //   sound_count: atomic<u32>, sound_triggers: array<_SoundTrigger, 32> (20 bytes each) and
+    //   sound_voices: array<u32, N> at offset 80 + stateSize, with any sounds
+    //   music_time, music_duration, music_track, music_command, music_command_track and
+    //   music_command_fade (4 bytes each) right after, with any music
+    //   audio_level: f32, spectrum: array<f32, 512> and waveform: array<f32, 1024> after those, if
+    //   the game reads them
+    //   osc: array<f32, 64>, keys: array<u32, 194>, then reset, save, load and quit: u32
+    //   persist: the @persist struct, if any, aligned to its struct's alignment
+    //   asserts: array<u32, N> for @assert(), if any, right after
 
     const buttonSize = 12 * 4; // 48 bytes
     const floatDataSize = 8 * 4; // 32 bytes (time, delta, width, height + mouse xyzw)
@@ -1391,13 +2349,19 @@ class WGSLGameEngine {
     // stateSize is already aligned to its struct's alignment by the preprocessor
     const alignedStateSize = this.stateSize;
 
-    const audioSize = this.audioCount * 4;
+    const soundQueueSize = this.audioCount > 0 ? 4 + MAX_SOUND_TRIGGERS * 20 : 0;
+    const audioSize = soundQueueSize + this.audioCount * 4;
+    const musicSize = this.musicFiles.length > 0 ? 6 * 4 : 0;
+    const analysisSize = this.usesAudioAnalysis ? (1 + SPECTRUM_SIZE + WAVEFORM_SIZE) * 4 : 0;
     const oscSize = 64 * 4; // 256 bytes for osc array (64 f32s)
     const keysSize = 194 * 4; // 776 bytes for keys array (194 u32s)
+    const requestSize = 4 * 4; // reset, save, load and quit request flags
+    const requestEnd = buttonSize + floatDataSize + alignedStateSize + audioSize + musicSize + analysisSize + oscSize + keysSize + requestSize;
+    const persistOffset = this.persist ? Math.ceil(requestEnd / this.persist.align) * this.persist.align : requestEnd;
 
     // Total size must be multiple of 16 for storage buffer
-    const totalSizeUnaligned =
-      buttonSize + floatDataSize + alignedStateSize + audioSize + oscSize + keysSize;
+    const persistEnd = persistOffset + (this.persist ? this.persist.size : 0);
+    const totalSizeUnaligned = persistEnd + this.assertCount * 4;
     const totalSize = Math.ceil(totalSizeUnaligned / 16) * 16;
 
     // Create storage buffer for engine state (writable from compute shader)
@@ -1409,26 +2373,75 @@ class WGSLGameEngine {
         GPUBufferUsage.COPY_SRC,
     });
 
+    // Read-only copy of the engine buffer for the vertex stage, refreshed after each compute pass
+    this.engineViewBuffer = this.device.createBuffer({
+      size: totalSize,
+      usage: GPUBufferUsage.STORAGE | GPUBufferUsage.COPY_DST,
+    });
+
+    // Create zero-initialized @storage() buffers
+    this.storageBuffers = this.storage.map((buffer) =>
+      this.device.createBuffer({
+        label: buffer.name,
+        size: buffer.size,
+        usage: GPUBufferUsage.STORAGE | GPUBufferUsage.COPY_DST | GPUBufferUsage.COPY_SRC,
+      }),
+    );
+
+    // Create blank @canvas() textures
+    this.canvasTextures = this.canvases.map((canvas) =>
+      this.device.createTexture({
+        label: canvas.name,
+        size: [canvas.width, canvas.height],
+        format: canvas.format,
+        usage: GPUTextureUsage.TEXTURE_BINDING | GPUTextureUsage.STORAGE_BINDING,
+      }),
+    );
+
     // Create staging buffer for CPU reads
+    // The buffer @print() calls write into, emptied after each frame's readback
+    const printsSize = this.prints.length > 0 ? 4 + MAX_PRINTS_PER_FRAME * (4 + 4 * this.printValues) : 0;
+    this.printsBuffer = printsSize > 0
+      ? this.device.createBuffer({
+          label: "Prints Buffer",
+          size: printsSize,
+          usage: GPUBufferUsage.STORAGE | GPUBufferUsage.COPY_DST | GPUBufferUsage.COPY_SRC,
+        })
+      : null;
+
     this.stagingBuffer = this.device.createBuffer({
-      size: totalSize,
+      size: totalSize + printsSize,
       usage: GPUBufferUsage.MAP_READ | GPUBufferUsage.COPY_DST,
     });
 
-    const oscOffset = buttonSize + floatDataSize + alignedStateSize + audioSize;
+    const musicOffset = buttonSize + floatDataSize + alignedStateSize + audioSize;
+    const oscOffset = musicOffset + musicSize + analysisSize;
     this.bufferOffsets = {
       buttons: 0,
       floats: buttonSize, // 48
       state: buttonSize + floatDataSize, // 80 (8-byte aligned)
-      audio: buttonSize + floatDataSize + alignedStateSize,
+      sounds: buttonSize + floatDataSize + alignedStateSize,
+      soundVoices: buttonSize + floatDataSize + alignedStateSize + soundQueueSize,
+      music: musicOffset,
+      musicCommand: musicOffset + 12,
+      audioLevel: musicOffset + musicSize,
       osc: oscOffset,
       keys: oscOffset + 64 * 4, // after osc (256 bytes for 64 f32s)
+      reset: oscOffset + 64 * 4 + keysSize,
+      save: oscOffset + 64 * 4 + keysSize + 4,
+      load: oscOffset + 64 * 4 + keysSize + 8,
+      quit: oscOffset + 64 * 4 + keysSize + 12,
+      persist: persistOffset,
     };
 
-    // Initialize game state to zero
+    // Initialize game state to zero; the game's init() fills it in
     const initData = new ArrayBuffer(totalSize);
     const initView = new DataView(initData);
 
+    // @engine.persist starts from the save, if there is one for this layout
+    const saved = this.readSave();
+    if (saved) new Uint8Array(initData, persistOffset, saved.length).set(saved);
+
     // Set initial screen size
     initView.setFloat32(this.bufferOffsets.floats + 8, this.canvas.width, true);
     initView.setFloat32(
@@ -1437,18 +2450,6 @@ class WGSLGameEngine {
       true,
     );
 
-    // Set initial player position (center of screen)
-    initView.setFloat32(
-      this.bufferOffsets.state + 0,
-      this.canvas.width / 2,
-      true,
-    ); // player_pos.x
-    initView.setFloat32(
-      this.bufferOffsets.state + 4,
-      this.canvas.height / 2,
-      true,
-    ); // player_pos.y
-
     this.device.queue.writeBuffer(this.engineBuffer, 0, initData);
   }
 
@@ -1488,21 +2489,60 @@ class WGSLGameEngine {
       });
     });
 
-    // Create bind groups for render pipeline using explicit layouts
-    this.renderBindGroup0 = this.device.createBindGroup({
-      layout: this.renderBindGroupLayout0,
-      entries: group0Entries,
+    // Two targets per @pass(), swapped every frame: a pass samples its own (and later passes')
+    // previous frame, while passes after it and fs_render see what it drew this frame
+    this.passTargets = this.passes.map(() =>
+      [0, 1].map(() =>
+        this.device
+          .createTexture({
+            size: [this.canvas.width, this.canvas.height],
+            format: "rgba16float",
+            usage: GPUTextureUsage.RENDER_ATTACHMENT | GPUTextureUsage.TEXTURE_BINDING,
+          })
+          .createView(),
+      ),
+    );
+    const bgBufferBase = bgCameraBase + (this.cameraIndices || []).length;
+    const bgCanvasBase = bgBufferBase + this.passes.length;
+    const assetEntries = [...group0Entries];
+    this.canvasTextures.forEach((texture, i) => {
+      group0Entries.push({
+        binding: bgCanvasBase + i,
+        resource: texture.createView(),
+      });
     });
+    const group0 = (stage, parity) =>
+      this.device.createBindGroup({
+        layout: this.renderBindGroupLayout0,
+        entries: [
+          ...group0Entries,
+          ...this.passTargets.map((views, i) => ({
+            binding: bgBufferBase + i,
+            resource: i < stage ? views[parity] : views[1 - parity],
+          })),
+        ],
+      });
+
+    // Create bind groups for render pipeline using explicit layouts:
+    // group 0 for each stage (the passes in order, then fs_render) on even and odd frames
+    this.renderBindGroups0 = [];
+    for (let stage = 0; stage <= this.passes.length; stage++) {
+      this.renderBindGroups0.push([group0(stage, 0), group0(stage, 1)]);
+    }
+    this.frame = 0;
+
+    // Group 1: engine buffer, its read-only copy for vertex code, then @storage() buffers and the print buffer
+    const storageEntries = [...this.storageBuffers, ...(this.printsBuffer ? [this.printsBuffer] : [])].map((buffer, i) => ({
+      binding: i + 2,
+      resource: { buffer },
+    }));
 
     this.renderBindGroup1 = this.device.createBindGroup({
       layout: this.renderBindGroupLayout1,
       entries: [
-        {
-          binding: 0,
-          resource: {
-            buffer: this.engineBuffer,
-          },
-        },
+        { binding: 0, resource: { buffer: this.engineBuffer } },
+        { binding: 1, resource: { buffer: this.engineViewBuffer } },
+        ...storageEntries,
       ],
     });
 
@@ -1527,18 +2567,22 @@ class WGSLGameEngine {
       });
     }
 
-    // Create bind group for compute pipeline using explicit layout
-    this.computeBindGroup1 = this.device.createBindGroup({
-      layout: this.computeBindGroupLayout1,
+    // Create bind groups for compute pipeline using explicit layouts
+    const bgCanvasStorageBase = bgCanvasBase + this.canvases.length;
+    this.computeBindGroup0 = this.device.createBindGroup({
+      layout: this.computeBindGroupLayout0,
       entries: [
-        {
-          binding: 0,
-          resource: {
-            buffer: this.engineBuffer,
-          },
-        },
+        ...assetEntries,
+        ...this.canvasTextures.map((texture, i) => ({
+          binding: bgCanvasStorageBase + i,
+          resource: texture.createView(),
+        })),
       ],
     });
+    this.computeBindGroup1 = this.device.createBindGroup({
+      layout: this.computeBindGroupLayout1,
+      entries: [{ binding: 0, resource: { buffer: this.engineBuffer } }, ...storageEntries],
+    });
   }
 
   setupInput() {
@@ -1548,6 +2592,10 @@ class WGSLGameEngine {
 
     // Listen on window for broader capture
     window.addEventListener("keydown", (e) => {
+      if (e.code === "F2" && !e.repeat) {
+        this.reset();
+        e.preventDefault();
+      }
       const btn = KEY_MAP[e.code];
       if (btn !== undefined) {
         this.buttons[btn] = 1;
@@ -1685,56 +2733,173 @@ class WGSLGameEngine {
     // Write raw key state at its offset (after osc)
     this.device.queue.writeBuffer(this.engineBuffer, this.bufferOffsets.keys, this.keys.buffer);
 
+    // The voices still playing, for @sound("x").is_playing
+    if (this.audioCount > 0) {
+      for (const voices of this.voices) {
+        voices.splice(0, voices.length, ...voices.filter((voice) => voice.ends === null || voice.ends > this.time));
+      }
+      const playing = new Uint32Array(this.voices.map((voices) => voices.length));
+      this.device.queue.writeBuffer(this.engineBuffer, this.bufferOffsets.soundVoices, playing);
+    }
+
+    // Where the music has got to, for @engine.music_time; a play() ends with its track
+    if (this.musicFiles.length > 0) {
+      const playing = this.musicCurrent;
+      if (playing && !playing.looping && this.musicPosition(playing) >= this.music[playing.track].buffer.duration) {
+        this.musicCurrent = null;
+      }
+      const music = new DataView(new ArrayBuffer(12));
+      if (this.musicCurrent) {
+        const { track, paused } = this.musicCurrent;
+        music.setFloat32(0, this.musicPosition(this.musicCurrent), true);
+        music.setFloat32(4, this.music[track].buffer.duration, true);
+        music.setUint32(8, paused ? 0 : track + 1, true);
+      }
+      this.device.queue.writeBuffer(this.engineBuffer, this.bufferOffsets.music, music.buffer);
+    }
+
+    // What's playing now, for @engine.spectrum, @engine.waveform and @engine.audio_level (silence
+    // until the page has been interacted with)
+    if (this.usesAudioAnalysis) {
+      const analysis = new Float32Array(1 + SPECTRUM_SIZE + WAVEFORM_SIZE);
+      if (this.analyser) {
+        const spectrum = analysis.subarray(1, 1 + SPECTRUM_SIZE);
+        const waveform = analysis.subarray(1 + SPECTRUM_SIZE);
+        const { minDecibels, maxDecibels } = this.analyser;
+        this.analyser.getFloatFrequencyData(spectrum);
+        for (let i = 0; i < SPECTRUM_SIZE; i++) {
+          spectrum[i] = Math.min(Math.max((spectrum[i] - minDecibels) / (maxDecibels - minDecibels), 0), 1);
+        }
+        this.analyser.getFloatTimeDomainData(waveform);
+        analysis[0] = Math.sqrt(waveform.reduce((sum, x) => sum + x * x, 0) / WAVEFORM_SIZE);
+      }
+      this.device.queue.writeBuffer(this.engineBuffer, this.bufferOffsets.audioLevel, analysis);
+    }
+
     // Run compute shader
     const commandEncoder = this.device.createCommandEncoder();
     const computePass = commandEncoder.beginComputePass();
-    computePass.setPipeline(this.updatePipeline);
-    computePass.setBindGroup(0, this.renderBindGroup0); // Group 0 for sampler/textures (required by pipeline layout)
+    computePass.setBindGroup(0, this.computeBindGroup0); // Group 0 for assets and @canvas() storage views
     computePass.setBindGroup(1, this.computeBindGroup1); // Group 1 for engine state
-    computePass.dispatchWorkgroups(1);
+    if (this.renderBindGroup2) {
+      computePass.setBindGroup(2, this.renderBindGroup2); // Group 2 for models
+    }
+    const engine = {
+      time: this.time,
+      delta_time: this.deltaTime,
+      screen_width: this.canvas.width,
+      screen_height: this.canvas.height,
+    };
+    if (this.initPending && this.initPipeline) {
+      computePass.setPipeline(this.initPipeline);
+      computePass.dispatchWorkgroups(1);
+    }
+    this.initPending = false;
+    const maxWorkgroups = this.device.limits.maxComputeWorkgroupsPerDimension;
+    for (const { dispatch, pipeline } of this.computePipelines) {
+      const [x, y, z] = dispatch.workgroups.map((count) => Math.min(Math.max(Math.ceil(count(engine)), 0) || 0, maxWorkgroups));
+      if (x === 0 || y === 0 || z === 0) continue;
+      computePass.setPipeline(pipeline);
+      computePass.dispatchWorkgroups(x, y, z);
+    }
     computePass.end();
 
-    // Copy to staging buffer to read audio triggers
+    // Vertex code reads @engine from a copy taken once compute is done
+    commandEncoder.copyBufferToBuffer(this.engineBuffer, 0, this.engineViewBuffer, 0, this.engineBuffer.size);
+
+    // Copy to staging buffer to read the sound queue, then the music command, the reset/save/load/quit requests and @engine.persist
+    const soundsSize = this.audioCount > 0 ? 4 + MAX_SOUND_TRIGGERS * 20 : 0;
+    const audioSize = soundsSize + (this.musicFiles.length > 0 ? 12 : 0);
+    const readRequests = this.usesReset || this.usesQuit || Boolean(this.persist);
+    const persistSize = this.persist ? this.persist.size : 0;
+    const printsOffset = audioSize + (readRequests ? 16 : 0) + persistSize;
+    const printsSize = this.printsBuffer ? this.printsBuffer.size : 0;
+    const readbackSize = printsOffset + printsSize;
     if (this.audioCount > 0) {
       commandEncoder.copyBufferToBuffer(
         this.engineBuffer,
-        this.bufferOffsets.audio,
+        this.bufferOffsets.sounds,
         this.stagingBuffer,
         0,
-        this.audioCount * 4,
+        soundsSize,
+      );
+      // Empty the queue as it's copied, so each call is played once
+      commandEncoder.clearBuffer(this.engineBuffer, this.bufferOffsets.sounds, 4);
+    }
+    if (this.musicFiles.length > 0) {
+      commandEncoder.copyBufferToBuffer(this.engineBuffer, this.bufferOffsets.musicCommand, this.stagingBuffer, soundsSize, 12);
+      commandEncoder.clearBuffer(this.engineBuffer, this.bufferOffsets.musicCommand, 12);
+    }
+    if (readRequests) {
+      commandEncoder.copyBufferToBuffer(this.engineBuffer, this.bufferOffsets.reset, this.stagingBuffer, audioSize, 16);
+    }
+    if (persistSize > 0) {
+      commandEncoder.copyBufferToBuffer(
+        this.engineBuffer,
+        this.bufferOffsets.persist,
+        this.stagingBuffer,
+        audioSize + 16,
+        persistSize,
       );
     }
+    if (printsSize > 0) {
+      commandEncoder.copyBufferToBuffer(this.printsBuffer, 0, this.stagingBuffer, printsOffset, printsSize);
+    }
 
     this.device.queue.submit([commandEncoder.finish()]);
 
-    // Read audio triggers (async, will play next frame)
-    if (this.audioCount > 0) {
+    // Read audio triggers and the reset request (async, will play next frame)
+    if (readbackSize > 0) {
       this.stagingBuffer
-        .mapAsync(GPUMapMode.READ, 0, this.audioCount * 4)
+        .mapAsync(GPUMapMode.READ, 0, readbackSize)
         .then(() => {
-          const audioData = new Uint32Array(
-            this.stagingBuffer.getMappedRange(0, this.audioCount * 4),
+          const readback = new Uint32Array(
+            this.stagingBuffer.getMappedRange(0, readbackSize),
           );
 
-          // Play sounds and reset triggers
-          for (let i = 0; i < this.audioCount; i++) {
-            if (audioData[i] > 0) {
-              this.playSound(i);
-            }
+          // Play the queued sounds, in order
+          const floats = new Float32Array(readback.buffer, 0, audioSize / 4);
+          for (let i = 0; i < Math.min(this.audioCount > 0 ? readback[0] : 0, MAX_SOUND_TRIGGERS); i++) {
+            const at = 1 + i * 5;
+            const flags = readback[at + 1];
+            this.playSound({
+              sound: readback[at],
+              looping: (flags & 1) !== 0,
+              stop: (flags & 2) !== 0,
+              volume: floats[at + 2],
+              pitch: floats[at + 3],
+              pan: floats[at + 4],
+            });
+          }
+          // Then the frame's last @music() call
+          const musicAction = this.musicFiles.length > 0 ? ["play", "loop", "pause", "stop"][readback[soundsSize / 4] - 1] : undefined;
+          if (musicAction) {
+            this.playMusic({ track: readback[soundsSize / 4 + 1], action: musicAction, fade: floats[soundsSize / 4 + 2] });
           }
+          const [resetRequested, saveRequested, loadRequested, quitRequested] = readRequests
+            ? Array.from(readback.slice(audioSize / 4, audioSize / 4 + 4), (flag) => flag !== 0)
+            : [false, false, false, false];
+          const persistData = saveRequested
+            ? new Uint8Array(readback.buffer, audioSize + 16, persistSize).slice()
+            : null;
+          if (printsSize > 0) this.logPrints(readback.slice(printsOffset / 4));
 
           this.stagingBuffer.unmap();
 
-          // Reset audio triggers
-          const zeros = new Uint32Array(this.audioCount);
-          this.device.queue.writeBuffer(
-            this.engineBuffer,
-            this.bufferOffsets.audio,
-            zeros,
-          );
+          if (readRequests && (resetRequested || saveRequested || loadRequested || quitRequested)) {
+            this.device.queue.writeBuffer(this.engineBuffer, this.bufferOffsets.reset, new Uint32Array(4));
+          }
+          if (printsSize > 0) this.device.queue.writeBuffer(this.printsBuffer, 0, new Uint32Array(1));
+          if (saveRequested) this.writeSave(persistData);
+          if (loadRequested) {
+            const saved = this.readSave();
+            if (saved) this.device.queue.writeBuffer(this.engineBuffer, this.bufferOffsets.persist, saved);
+          }
+          if (resetRequested) this.reset();
+          if (quitRequested) this.quit();
         })
         .catch((err) => {
-          console.warn("Failed to read audio triggers:", err);
+          console.warn("Failed to read the sound queue:", err);
         });
     }
   }
@@ -1742,7 +2907,32 @@ class WGSLGameEngine {
   render() {
     const commandEncoder = this.device.createCommandEncoder();
     const textureView = this.context.getCurrentTexture().createView();
+    const parity = this.frame % 2;
 
+    // Offscreen passes, in declaration order, each a triangle covering its target
+    this.passPipelines.forEach((pipeline, i) => {
+      const pass = commandEncoder.beginRenderPass({
+        colorAttachments: [
+          {
+            view: this.passTargets[i][parity],
+            clearValue: { r: 0.0, g: 0.0, b: 0.0, a: 1.0 },
+            loadOp: "clear",
+            storeOp: "store",
+          },
+        ],
+      });
+      pass.setPipeline(pipeline);
+      pass.setBindGroup(0, this.renderBindGroups0[i][parity]);
+      pass.setBindGroup(1, this.renderBindGroup1);
+      if (this.renderBindGroup2) {
+        pass.setBindGroup(2, this.renderBindGroup2);
+      }
+      pass.draw(3);
+      pass.end();
+    });
+
+    // Draw either model vertices or fullscreen triangle
+    const vertexCount = this.modelVertexCount > 0 ? this.modelVertexCount : 3;
     const renderPass = commandEncoder.beginRenderPass({
       colorAttachments: [
         {
@@ -1761,18 +2951,16 @@ class WGSLGameEngine {
     });
 
     renderPass.setPipeline(this.renderPipeline);
-    renderPass.setBindGroup(0, this.renderBindGroup0);
+    renderPass.setBindGroup(0, this.renderBindGroups0[this.passes.length][parity]);
     renderPass.setBindGroup(1, this.renderBindGroup1);
     if (this.renderBindGroup2) {
       renderPass.setBindGroup(2, this.renderBindGroup2);
     }
-
-    // Draw either model vertices or fullscreen triangle
-    const vertexCount = this.modelVertexCount > 0 ? this.modelVertexCount : 3;
     renderPass.draw(vertexCount);
     renderPass.end();
 
     this.device.queue.submit([commandEncoder.finish()]);
+    this.frame++;
   }
 
   /**
@@ -1865,6 +3053,81 @@ class WGSLGameEngine {
     };
   }
 
+  /**
+   * Clear GameState and run the game's init() before the next frame (same as native /reset message or F2)
+   */
+  reset() {
+    this.device.queue.writeBuffer(this.engineBuffer, this.bufferOffsets.state, new Uint8Array(this.stateSize));
+    this.device.queue.writeBuffer(this.engineBuffer, this.bufferOffsets.reset, new Uint32Array(1));
+    this.initPending = true;
+  }
+
+  /**
+   * Log what a frame's @print()s wrote: each with its location, then how many didn't fit
+   */
+  logPrints(words) {
+    const count = words[0];
+    const floats = new Float32Array(words.buffer, words.byteOffset);
+    const stride = 1 + this.printValues;
+    for (let slot = 0; slot < Math.min(count, MAX_PRINTS_PER_FRAME); slot++) {
+      const base = 1 + slot * stride;
+      const print = this.prints[words[base]];
+      if (!print) continue;
+      const values = Array.from(floats.slice(base + 1, base + 1 + print.values), (value) => {
+        // The shortest decimal that reads back as the same f32, like native
+        for (let digits = 1; digits < 9; digits++) {
+          if (Math.fround(Number(value.toPrecision(digits))) === value) return String(Number(value.toPrecision(digits)));
+        }
+        return String(value);
+      });
+      const text = print.format.replace(/\{\{|\}\}|\{\}/g, (brace) => (brace === "{}" ? values.shift() : brace[0]));
+      console.log(`[print] ${print.location}: ${text}`);
+    }
+    if (count > MAX_PRINTS_PER_FRAME) {
+      console.log(`[print] ${count - MAX_PRINTS_PER_FRAME} more @print()s this frame were dropped`);
+    }
+  }
+
+  /**
+   * The @persist bytes saved in localStorage, if they were written for this layout
+   * (same JSON as a native save file, under the same key)
+   */
+  readSave() {
+    if (!this.persist) return null;
+    const contents = localStorage.getItem(`wgsleng-save:${this.persist.key}`);
+    if (contents === null) return null;
+    try {
+      const save = JSON.parse(contents);
+      if (save.layout !== this.persist.layout) {
+        throw new Error(`saved layout ${save.layout} doesn't match ${this.persist.layout}`);
+      }
+      const bytes = (save.data ?? "").match(/../g) ?? [];
+      if (bytes.length !== this.persist.size || bytes.some((b) => !/^[0-9a-f]{2}$/i.test(b))) {
+        throw new Error(`saved data isn't ${this.persist.size} bytes of hex`);
+      }
+      console.log(`[save] loaded ${this.persist.key}`);
+      return new Uint8Array(bytes.map((b) => parseInt(b, 16)));
+    } catch (err) {
+      console.warn(`[save] ignoring ${this.persist.key}: ${err.message}`);
+      return null;
+    }
+  }
+
+  /**
+   * Write the @persist bytes to localStorage
+   * @param {Uint8Array} data - @engine.persist read back from the engine buffer
+   */
+  writeSave(data) {
+    const hex = Array.from(data, (b) => b.toString(16).padStart(2, "0")).join("");
+    const save = { title: this.gameTitle, layout: this.persist.layout, data: hex };
+    try {
+      localStorage.setItem(`wgsleng-save:${this.persist.key}`, JSON.stringify(save, null, 2));
+      console.log(`[save] wrote ${this.persist.key}`);
+    } catch (err) {
+      console.warn(`[save] failed to write ${this.persist.key}: ${err.message}`);
+    }
+  }
+
   /**
    * Reload the current shader (same as native /reload message)
    */
@@ -1885,6 +3148,16 @@ class WGSLGameEngine {
   stop() {
     this.running = false;
   }
+
+  /**
+   * Stop the game for @engine.quit(), as the native window closes: the loop ends and everything playing goes quiet
+   */
+  quit() {
+    console.log("[quit] the game quit");
+    this.stop();
+    for (const voice of this.voices.flat()) voice.source?.stop();
+    for (const playing of [this.musicCurrent, ...this.musicFading]) playing?.source?.stop();
+  }
 }
 
 /**